@@ -0,0 +1,231 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Converts the BDF font used to render unsupported characters (`glyphs` feature) into tables
+//! which are compiled into the crate.
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+/// The font used unless `THERMAL_PRINT_GLYPH_FONT` names another BDF file
+const DEFAULT_FONT: &str = "fonts/10x20-subset.bdf";
+
+/// A glyph of the font: its character, width in dots, and rows of bits, MSB first.
+struct Glyph {
+    character: char,
+    width: usize,
+    rows: Vec<Vec<u8>>,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=THERMAL_PRINT_GLYPH_FONT");
+    println!("cargo:rerun-if-env-changed=THERMAL_PRINT_GLYPH_RANGES");
+    if env::var_os("CARGO_FEATURE_GLYPHS").is_none() {
+        return;
+    }
+
+    let font = env::var("THERMAL_PRINT_GLYPH_FONT").unwrap_or_else(|_| DEFAULT_FONT.into());
+    println!("cargo:rerun-if-changed={}", font);
+    let bdf = fs::read_to_string(&font)
+        .unwrap_or_else(|e| panic!("cannot read glyph font {}: {}", font, e));
+    let (height, space_width, mut glyphs) =
+        parse_bdf(&bdf).unwrap_or_else(|e| panic!("invalid glyph font {}: {}", font, e));
+    // Printable ASCII is always included, for the text on lines printed as glyphs
+    if let Ok(ranges) = env::var("THERMAL_PRINT_GLYPH_RANGES") {
+        let ranges = parse_ranges(&ranges)
+            .unwrap_or_else(|e| panic!("invalid THERMAL_PRINT_GLYPH_RANGES: {}", e));
+        glyphs.retain(|glyph| {
+            glyph.character.is_ascii()
+                || ranges
+                    .iter()
+                    .any(|range| range.contains(&u32::from(glyph.character)))
+        });
+    }
+
+    let mut index = String::new();
+    let mut data = String::new();
+    let mut offset = 0;
+    for glyph in &glyphs {
+        writeln!(
+            index,
+            "    ({:?}, {}, {}),",
+            glyph.character, glyph.width, offset
+        )
+        .unwrap();
+        for row in &glyph.rows {
+            for byte in row {
+                write!(data, "{},", byte).unwrap();
+            }
+            offset += row.len();
+        }
+        data.push('\n');
+    }
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("glyphs.rs");
+    fs::write(
+        out,
+        format!(
+            "/// Height of all glyphs in dots\n\
+             const GLYPH_HEIGHT: usize = {};\n\
+             /// Width of a space between glyphs in dots\n\
+             const SPACE_WIDTH: usize = {};\n\
+             /// Characters, their width in dots and the offset of their rows in `GLYPH_DATA`,\n\
+             /// sorted by character\n\
+             static GLYPH_INDEX: [(char, u8, u32); {}] = [\n{}];\n\
+             /// Rows of all glyphs, MSB first\n\
+             static GLYPH_DATA: [u8; {}] = [\n{}];\n",
+            height,
+            space_width,
+            glyphs.len(),
+            index,
+            offset,
+            data
+        ),
+    )
+    .unwrap();
+}
+
+/// Parse a BDF font, returning the height and width of its bounding box and the glyphs
+/// of all printable characters, each placed into a box of the font's height.
+fn parse_bdf(bdf: &str) -> Result<(usize, usize, Vec<Glyph>), String> {
+    let mut lines = bdf.lines();
+    let mut bounding_box = None;
+    let mut glyphs = Vec::new();
+    while let Some(line) = lines.next() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("FONTBOUNDINGBOX") => {
+                let values = numbers(words)?;
+                if values.len() != 4 {
+                    return Err("malformed FONTBOUNDINGBOX".into());
+                }
+                bounding_box = Some((values[0], values[1], values[3]));
+            }
+            Some("STARTCHAR") => {
+                let (width, height, descent) =
+                    bounding_box.ok_or("STARTCHAR before FONTBOUNDINGBOX")?;
+                if let Some(glyph) = parse_char(&mut lines, width, height, descent)? {
+                    glyphs.push(glyph);
+                }
+            }
+            _ => {}
+        }
+    }
+    let (width, height, _) = bounding_box.ok_or("missing FONTBOUNDINGBOX")?;
+    glyphs.sort_by_key(|glyph| glyph.character);
+    glyphs.dedup_by_key(|glyph| glyph.character);
+    Ok((height as usize, width as usize, glyphs))
+}
+
+/// Parse a character up to its `ENDCHAR` line. Returns `None` for control characters, which are
+/// never rendered as glyphs.
+fn parse_char<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    font_width: i32,
+    font_height: i32,
+    font_descent: i32,
+) -> Result<Option<Glyph>, String> {
+    let mut character = None;
+    let mut width = font_width;
+    let (mut bbx_width, mut bbx_height, mut bbx_x, mut bbx_y) = (0, 0, 0, 0);
+    let mut bitmap = Vec::new();
+    let mut in_bitmap = false;
+    for line in lines.by_ref() {
+        let mut words = line.split_whitespace();
+        let keyword = words.next();
+        if in_bitmap && keyword != Some("ENDCHAR") {
+            let bits = u64::from_str_radix(line.trim(), 16)
+                .map_err(|_| format!("malformed bitmap row {:?}", line))?;
+            bitmap.push((bits, line.trim().len() * 4));
+            continue;
+        }
+        match keyword {
+            Some("ENCODING") => {
+                character = numbers(words)?
+                    .first()
+                    .and_then(|code| char::from_u32(*code as u32));
+            }
+            Some("DWIDTH") => {
+                width = *numbers(words)?.first().ok_or("malformed DWIDTH")?;
+            }
+            Some("BBX") => {
+                let values = numbers(words)?;
+                if values.len() != 4 {
+                    return Err("malformed BBX".into());
+                }
+                (bbx_width, bbx_height, bbx_x, bbx_y) =
+                    (values[0], values[1], values[2], values[3]);
+            }
+            Some("BITMAP") => in_bitmap = true,
+            Some("ENDCHAR") => break,
+            _ => {}
+        }
+    }
+
+    let character = match character {
+        Some(c) if (c == ' ' || c.is_ascii_graphic() || c as u32 >= 0xA0) && width > 0 => c,
+        _ => return Ok(None),
+    };
+    let width = width as usize;
+    let row_bytes = width.div_ceil(8);
+    let mut rows = vec![vec![0u8; row_bytes]; font_height as usize];
+    // The first bitmap row is at the top of the glyph's bounding box, measured from the baseline
+    let top = font_height + font_descent - bbx_y - bbx_height;
+    for (i, (bits, bit_count)) in bitmap.iter().enumerate() {
+        let y = top + i as i32;
+        if y < 0 || y >= font_height {
+            continue;
+        }
+        for j in 0..bbx_width {
+            let x = bbx_x + j;
+            let set = j < *bit_count as i32 && (bits >> (*bit_count as i32 - 1 - j)) & 1 != 0;
+            if set && x >= 0 && (x as usize) < width {
+                rows[y as usize][x as usize / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+    Ok(Some(Glyph {
+        character,
+        width,
+        rows,
+    }))
+}
+
+/// Parse comma-separated hexadecimal code points and ranges of them, such as `20AC,2190-21FF`.
+fn parse_ranges(ranges: &str) -> Result<Vec<RangeInclusive<u32>>, String> {
+    let code_point = |hex: &str| {
+        u32::from_str_radix(hex.trim(), 16).map_err(|_| format!("malformed code point {:?}", hex))
+    };
+    ranges
+        .split(',')
+        .filter(|range| !range.trim().is_empty())
+        .map(|range| match range.split_once('-') {
+            Some((first, last)) => Ok(code_point(first)?..=code_point(last)?),
+            None => code_point(range).map(|c| c..=c),
+        })
+        .collect()
+}
+
+fn numbers<'a>(words: impl Iterator<Item = &'a str>) -> Result<Vec<i32>, String> {
+    words
+        .map(|word| {
+            word.parse()
+                .map_err(|_| format!("malformed number {:?}", word))
+        })
+        .collect()
+}
@@ -0,0 +1,629 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! An asynchronous variant of the [`Printer`](crate::Printer), built on [`embedded_io_async`] and
+//! [`embedded_hal_async`].
+
+#[cfg(not(feature = "std"))]
+use alloc::{format, vec::Vec};
+use core::fmt::Arguments;
+
+use embedded_hal::digital::{Error as _, InputPin};
+use embedded_hal_async::delay::DelayNs;
+use embedded_io_async::{Read as SerialRead, ReadReady, Write as SerialWrite};
+
+use crate::job::{Job, RasterBands, Step};
+use crate::nv_image::{KeyList, KeyListProgress, ACK, NV_GRAPHICS_LIST_QUERY};
+use crate::status::{
+    DEFAULT_RESPONSE_TIMEOUT_MICROS, PAPER_STATUS_QUERY, PRINTER_STATUS_QUERY, RESPONSE_POLL_MICROS,
+};
+use crate::{
+    BarCodeSystem, BarcodeStyle, BarcodeWidth, Bitmap, BitmapOptions, CharacterSet, Code128,
+    CodeTable, Font, Head, HriPosition, Justification, NoBusyPin, NvImageCommands, PrintMode,
+    PrintSettings, PrinterError, PrinterStatus, QrOptions, RasterBitImageMode, SymbolOptions,
+    TextFallback, TimingProfile, Underline, UserGlyph, BUSY_CHUNK_BYTES, BUSY_POLL_MICROS,
+    DEFAULT_BUSY_TIMEOUT_MICROS,
+};
+
+/// The asynchronous counterpart of [`Printer`](crate::Printer). Instead of blocking while the
+/// printer outputs what it has been sent, it awaits the delay, so other tasks can run in the
+/// meantime.
+///
+/// As [`core::fmt::Write`] cannot be implemented asynchronously, text is printed via
+/// [`AsyncPrinter::write_str`] and [`AsyncPrinter::write_fmt`]:
+///
+/// ```ignore
+/// let mut printer = AsyncPrinter::new(uart, Delay);
+/// printer.init().await?;
+/// printer.write_fmt(format_args!("Hello, {}!\n", name)).await?;
+/// ```
+pub struct AsyncPrinter<Port: SerialWrite, Delay: DelayNs, Busy: InputPin = NoBusyPin> {
+    pub serial: Port,
+    pub delay: Delay,
+    busy: Option<Busy>,
+    head: Head,
+    response_timeout: u32,
+    busy_timeout: u32,
+}
+
+impl<Port: SerialWrite, Delay: DelayNs> AsyncPrinter<Port, Delay> {
+    /// Create a new `AsyncPrinter` with default settings.
+    ///
+    /// You must specify the serial port to be used, as well as a delay implementation of your HAL
+    /// to allow the driver to wait while the printer is outputting text.
+    pub fn new(serial: Port, delay: Delay) -> AsyncPrinter<Port, Delay> {
+        AsyncPrinter::build(serial, delay, None, TimingProfile::default())
+    }
+
+    /// Create a new `AsyncPrinter` with default settings, which estimates how long the printer
+    /// takes to output what it is sent according to `timing`. See [`TimingProfile`] for more
+    /// information.
+    pub fn with_timing(
+        serial: Port,
+        delay: Delay,
+        timing: TimingProfile,
+    ) -> AsyncPrinter<Port, Delay> {
+        AsyncPrinter::build(serial, delay, None, timing)
+    }
+}
+
+impl<Port: SerialWrite, Delay: DelayNs, Busy: InputPin> AsyncPrinter<Port, Delay, Busy> {
+    /// Create a new `AsyncPrinter` with default settings, which uses the printer's DTR line
+    /// connected to `busy` for flow control. See
+    /// [`Printer::with_busy_pin`](crate::Printer::with_busy_pin) for details.
+    pub fn with_busy_pin(
+        serial: Port,
+        delay: Delay,
+        busy: Busy,
+    ) -> AsyncPrinter<Port, Delay, Busy> {
+        AsyncPrinter::build(serial, delay, Some(busy), TimingProfile::default())
+    }
+
+    /// Create a new `AsyncPrinter` with default settings, which uses the printer's DTR line
+    /// connected to `busy` for flow control, with the `timing` of the printer. See
+    /// [`Printer::with_busy_pin_and_timing`](crate::Printer::with_busy_pin_and_timing) for
+    /// details.
+    pub fn with_busy_pin_and_timing(
+        serial: Port,
+        delay: Delay,
+        busy: Busy,
+        timing: TimingProfile,
+    ) -> AsyncPrinter<Port, Delay, Busy> {
+        AsyncPrinter::build(serial, delay, Some(busy), timing)
+    }
+
+    fn build(
+        serial: Port,
+        delay: Delay,
+        busy: Option<Busy>,
+        timing: TimingProfile,
+    ) -> AsyncPrinter<Port, Delay, Busy> {
+        AsyncPrinter {
+            serial,
+            delay,
+            busy,
+            head: Head::new(timing),
+            response_timeout: DEFAULT_RESPONSE_TIMEOUT_MICROS,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT_MICROS,
+        }
+    }
+
+    /// Set how long to wait for the printer to answer a query, in microseconds. Default: 500ms.
+    pub fn set_response_timeout(&mut self, timeout: u32) {
+        self.response_timeout = timeout;
+    }
+
+    /// Set how long to wait for the printer to clear its busy signal, in microseconds. Only
+    /// relevant if a busy pin is connected. Default: 5s.
+    pub fn set_busy_timeout(&mut self, timeout: u32) {
+        self.busy_timeout = timeout;
+    }
+
+    /// Perform `job`: send its data, waiting for the printer to process it. The printer status is
+    /// not checked at its checkpoints.
+    async fn run(&mut self, job: Job) -> Result<(), PrinterError<Port::Error>> {
+        for step in job {
+            match step {
+                Step::Send(bytes, pause) => {
+                    self.send(&bytes).await?;
+                    self.pace(pause).await;
+                }
+                Step::Sleep(duration) => self.sleep(duration).await,
+                Step::Checkpoint => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a control command, which produces no physical output, waiting for the printer to
+    /// receive it.
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), PrinterError<Port::Error>> {
+        self.send(bytes).await?;
+        self.pace(bytes.len() as u64 * self.head.byte_time()).await;
+        Ok(())
+    }
+
+    /// Send `bytes` to the printer. If a busy pin is connected, they are sent in small chunks,
+    /// each once the printer is ready to receive it.
+    async fn send(&mut self, bytes: &[u8]) -> Result<(), PrinterError<Port::Error>> {
+        let chunk_len = if self.busy.is_some() {
+            BUSY_CHUNK_BYTES
+        } else {
+            bytes.len().max(1)
+        };
+        for chunk in bytes.chunks(chunk_len) {
+            self.wait_until_ready().await?;
+            self.serial
+                .write_all(chunk)
+                .await
+                .map_err(PrinterError::Serial)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the specified number of microseconds. We don't want to overrun the printer's
+    /// buffer, so this function is used to wait for the print head to physically produce the
+    /// desired output.
+    async fn sleep(&mut self, duration: u64) {
+        // Pauses longer than `delay_us` accepts, over an hour, are split
+        let mut remaining = duration;
+        loop {
+            let chunk = u32::try_from(remaining).unwrap_or(u32::MAX);
+            self.delay.delay_us(chunk).await;
+            remaining -= u64::from(chunk);
+            if remaining == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Wait for the estimated time the printer needs to process what it has been sent. If a busy
+    /// pin is connected, this is not necessary, as [`wait_until_ready`] takes care of it.
+    async fn pace(&mut self, duration: u64) {
+        if self.busy.is_none() {
+            self.sleep(duration).await;
+        }
+    }
+
+    /// Wait while the printer signals that it is busy, if a busy pin is connected.
+    async fn wait_until_ready(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        if let Some(busy) = self.busy.as_mut() {
+            let mut waited = 0;
+            while busy
+                .is_high()
+                .map_err(|e| PrinterError::BusyPin(e.kind()))?
+            {
+                if waited >= self.busy_timeout {
+                    return Err(PrinterError::Timeout);
+                }
+                self.delay.delay_us(BUSY_POLL_MICROS).await;
+                waited += BUSY_POLL_MICROS;
+            }
+        }
+        Ok(())
+    }
+
+    /// Print `s` as text, transcoded into the selected [`CodeTable`].
+    pub async fn write_str(&mut self, s: &str) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.write_text(s)?;
+        self.run(job).await
+    }
+
+    /// Print a single character.
+    pub async fn write_char(&mut self, c: char) -> Result<(), PrinterError<Port::Error>> {
+        self.write_str(c.encode_utf8(&mut [0; 4])).await
+    }
+
+    /// Print formatted text, as produced by [`format_args!`].
+    pub async fn write_fmt(
+        &mut self,
+        args: Arguments<'_>,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        self.write_str(format!("{}", args).as_str()).await
+    }
+
+    /// Send the initialization sequence and configure tab stops. Also enables flow control via the
+    /// DTR line if a busy pin is connected.
+    pub async fn reset(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.reset(self.busy.is_some());
+        self.run(job).await
+    }
+
+    /// Wake the device from sleep. Also wait for 75ms, as according to the datasheet the printer
+    /// needs at least 50ms in order to be ready to receive commands.
+    pub async fn wake(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.wake();
+        self.run(job).await
+    }
+
+    /// Wait 500ms to allow the printer to boot, then wake it up, disable sleep, and call reset.
+    pub async fn init(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.init(self.busy.is_some());
+        self.run(job).await
+    }
+
+    /// Select print mode(s), such as inverse printing or double-height mode.
+    pub async fn set_print_mode(
+        &mut self,
+        print_mode: PrintMode,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_print_mode(print_mode);
+        self.run(job).await
+    }
+
+    /// Configure print settings. See [`PrintSettings`] for more information.
+    pub async fn set_print_settings(
+        &mut self,
+        print_settings: PrintSettings,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_print_settings(print_settings);
+        self.run(job).await
+    }
+
+    pub async fn set_justification(
+        &mut self,
+        justification: Justification,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_justification(justification);
+        self.run(job).await
+    }
+
+    pub async fn set_underline(
+        &mut self,
+        mode: Underline,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_underline(mode);
+        self.run(job).await
+    }
+
+    pub async fn set_character_set(
+        &mut self,
+        character_set: CharacterSet,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_character_set(character_set);
+        self.run(job).await
+    }
+
+    pub async fn set_code_table(
+        &mut self,
+        code_table: CodeTable,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_code_table(code_table);
+        self.run(job).await
+    }
+
+    /// Set what is printed in place of characters which the selected [`CodeTable`] does not
+    /// contain. See [`TextFallback`] for more information.
+    pub fn set_text_fallback(&mut self, fallback: TextFallback) {
+        self.head.encoder.fallback = fallback;
+    }
+
+    /// Allow switching to one of `code_tables` for characters which the selected [`CodeTable`]
+    /// does not contain. See
+    /// [`Printer::set_code_table_switching`](crate::Printer::set_code_table_switching) for
+    /// details.
+    pub fn set_code_table_switching(&mut self, code_tables: &'static [CodeTable]) {
+        self.head.encoder.switching = code_tables;
+    }
+
+    /// Add transliterations for characters which no code table contains. See
+    /// [`Printer::set_transliterations`](crate::Printer::set_transliterations) for details.
+    pub fn set_transliterations(&mut self, transliterations: &'static [(char, &'static str)]) {
+        self.head.encoder.transliterations = transliterations;
+    }
+
+    /// Enable or disable printing characters which no code table contains as raster images. See
+    /// [`Printer::set_glyph_rendering`](crate::Printer::set_glyph_rendering) for details.
+    #[cfg(feature = "glyphs")]
+    pub fn set_glyph_rendering(&mut self, enabled: bool) {
+        self.head.encoder.glyphs = enabled;
+    }
+
+    /// Define `glyphs` as the user-defined characters starting at the character code `first`. See
+    /// [`Printer::define_user_characters`](crate::Printer::define_user_characters) for details.
+    pub async fn define_user_characters(
+        &mut self,
+        first: u8,
+        glyphs: &[UserGlyph],
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.define_user_characters(first, glyphs)?;
+        self.run(job).await
+    }
+
+    /// Enable or disable the user-defined characters.
+    pub async fn set_user_characters(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_user_characters(enabled);
+        self.run(job).await
+    }
+
+    /// Delete the user-defined character with the code `code`.
+    pub async fn cancel_user_character(
+        &mut self,
+        code: u8,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.cancel_user_character(code)?;
+        self.run(job).await
+    }
+
+    /// Print a bitmap image. See [`Printer::print_bitmap`](crate::Printer::print_bitmap) for details.
+    pub async fn print_bitmap<'a>(
+        &mut self,
+        bmp: impl Into<Bitmap<'a>>,
+        mode: RasterBitImageMode,
+        options: BitmapOptions,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_bitmap(bmp.into(), mode, options)?;
+        self.run(job).await
+    }
+
+    /// Select the commands used for NV images. See
+    /// [`Printer::set_nv_image_commands`](crate::Printer::set_nv_image_commands) for details.
+    pub fn set_nv_image_commands(&mut self, commands: NvImageCommands) {
+        self.head.nv_commands = commands;
+    }
+
+    /// Store `images` in the non-volatile memory of the printer, replacing the images stored
+    /// before. See [`Printer::store_nv_images`](crate::Printer::store_nv_images) for details.
+    pub async fn store_nv_images<'a>(
+        &mut self,
+        images: &[impl Into<Bitmap<'a>> + Copy],
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.store_nv_images(images)?;
+        self.run(job).await
+    }
+
+    /// Delete all images stored in the non-volatile memory of the printer.
+    pub async fn erase_nv_images(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.erase_nv_images();
+        self.run(job).await
+    }
+
+    /// Delete the stored image with `index`. See
+    /// [`Printer::erase_nv_image`](crate::Printer::erase_nv_image) for details.
+    pub async fn erase_nv_image(&mut self, index: u8) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.erase_nv_image(index)?;
+        self.run(job).await
+    }
+
+    /// Print the stored image with `index`. See
+    /// [`Printer::print_nv_image`](crate::Printer::print_nv_image) for details.
+    pub async fn print_nv_image(
+        &mut self,
+        index: u8,
+        mode: RasterBitImageMode,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_nv_image(index, mode)?;
+        self.run(job).await
+    }
+
+    /// Print a raster image `width` dots wide and `height` rows high. See
+    /// [`Printer::print_raster`](crate::Printer::print_raster) for details.
+    pub async fn print_raster(
+        &mut self,
+        width: usize,
+        height: usize,
+        data: &[u8],
+        mode: RasterBitImageMode,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_raster(width, height, data, mode)?;
+        self.run(job).await
+    }
+
+    /// Print a raster image `width` dots wide from an iterator over its rows. See
+    /// [`Printer::print_raster_iter`](crate::Printer::print_raster_iter) for details.
+    pub async fn print_raster_iter<R: AsRef<[u8]>>(
+        &mut self,
+        width: usize,
+        rows: impl IntoIterator<Item = R>,
+        mode: RasterBitImageMode,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let mut bands = RasterBands::new(&self.head, width, mode)?;
+        for row in rows {
+            if let Some(job) = bands.push(&self.head, row.as_ref())? {
+                self.run(job).await?;
+            }
+        }
+        match bands.finish(&self.head) {
+            Some(job) => self.run(job).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Print a QR code encoding `data`. See [`Printer::print_qr`](crate::Printer::print_qr) for
+    /// more information.
+    pub async fn print_qr(
+        &mut self,
+        data: &[u8],
+        options: QrOptions,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_qr(data, options)?;
+        self.run(job).await
+    }
+
+    /// Encode `data` as a two-dimensional symbol and print it as a raster image. See
+    /// [`Printer::print_symbol`](crate::Printer::print_symbol) for more information.
+    pub async fn print_symbol(
+        &mut self,
+        data: &[u8],
+        options: SymbolOptions,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_symbol(data, options)?;
+        self.run(job).await
+    }
+
+    /// Print a barcode with the specified `BarCodeSystem`. See
+    /// [`Printer::print_barcode`](crate::Printer::print_barcode) for
+    /// details.
+    pub async fn print_barcode(
+        &mut self,
+        system: BarCodeSystem,
+        text: &str,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_barcode(system, text)?;
+        self.run(job).await
+    }
+
+    /// Print a Code128 barcode, using the code sets resulting in the shortest barcode. See
+    /// [`Code128`] for more information.
+    pub async fn print_code128(
+        &mut self,
+        barcode: &Code128,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        self.print_barcode(BarCodeSystem::Code128, &barcode.encode()?)
+            .await
+    }
+
+    /// Set the barcode height to the specified number of dots.
+    pub async fn set_barcode_height(
+        &mut self,
+        height: u8,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_height(height);
+        self.run(job).await
+    }
+
+    /// Set the space to the left of the barcode to the specified number of dots.
+    pub async fn set_barcode_left_space(
+        &mut self,
+        space: u8,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_left_space(space);
+        self.run(job).await
+    }
+
+    /// Set the barcode width to the specified value. See [`BarcodeWidth`] for more information.
+    pub async fn set_barcode_width(
+        &mut self,
+        width: BarcodeWidth,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_width(width);
+        self.run(job).await
+    }
+
+    /// Set where the text of a barcode is printed. See [`HriPosition`] for more information.
+    pub async fn set_barcode_text_position(
+        &mut self,
+        position: HriPosition,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_text_position(position);
+        self.run(job).await
+    }
+
+    /// Set the font used for the text of a barcode.
+    pub async fn set_barcode_text_font(
+        &mut self,
+        font: Font,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_text_font(font);
+        self.run(job).await
+    }
+
+    /// Apply all barcode settings at once. See [`BarcodeStyle`] for more information.
+    pub async fn set_barcode_style(
+        &mut self,
+        style: BarcodeStyle,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_style(style);
+        self.run(job).await
+    }
+
+    /// Enable or disable the 90° clockwise rotation mode.
+    pub async fn set_rotation_mode(
+        &mut self,
+        rotate: bool,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_rotation_mode(rotate);
+        self.run(job).await
+    }
+
+    /// Feed the paper by exactly one line.
+    pub async fn feed(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        self.feed_n(1).await
+    }
+
+    /// Feed the paper by the specified number of lines.
+    pub async fn feed_n(&mut self, lines: u8) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.feed_n(lines);
+        self.run(job).await
+    }
+}
+
+impl<Port: SerialWrite + SerialRead + ReadReady, Delay: DelayNs, Busy: InputPin>
+    AsyncPrinter<Port, Delay, Busy>
+{
+    /// Query the printer status. See
+    /// [`Printer::status`](crate::Printer::status) for details.
+    pub async fn status(&mut self) -> Result<PrinterStatus, PrinterError<Port::Error>> {
+        self.discard_input().await?;
+        self.write_bytes(&PRINTER_STATUS_QUERY).await?;
+        let printer_status = self.read_response().await?;
+        self.write_bytes(&PAPER_STATUS_QUERY).await?;
+        let paper_status = self.read_response().await?;
+        Ok(PrinterStatus::from_responses(printer_status, paper_status))
+    }
+
+    /// List the indices of the stored images. See
+    /// [`Printer::nv_images`](crate::Printer::nv_images) for details.
+    pub async fn nv_images(&mut self) -> Result<Vec<u8>, PrinterError<Port::Error>> {
+        if self.head.nv_commands != NvImageCommands::Graphics {
+            return Err(PrinterError::InvalidArgument(
+                "NV images can only be listed via GS ( L",
+            ));
+        }
+        self.discard_input().await?;
+        self.write_bytes(&NV_GRAPHICS_LIST_QUERY).await?;
+        let mut list = KeyList::default();
+        loop {
+            match list.push(self.read_response().await?) {
+                KeyListProgress::Pending => {}
+                KeyListProgress::NextBlock => self.write_bytes(&[ACK]).await?,
+                KeyListProgress::Done => return Ok(list.indices),
+            }
+        }
+    }
+
+    /// Drop any bytes the printer sent without being asked, so they are not mistaken for the
+    /// response to a query.
+    async fn discard_input(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        let mut buffer = [0; 8];
+        while self.serial.read_ready().map_err(PrinterError::Serial)? {
+            self.serial
+                .read(&mut buffer)
+                .await
+                .map_err(PrinterError::Serial)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the printer to send a single byte, giving up after the response timeout.
+    async fn read_response(&mut self) -> Result<u8, PrinterError<Port::Error>> {
+        let mut waited = 0;
+        while !self.serial.read_ready().map_err(PrinterError::Serial)? {
+            if waited >= self.response_timeout {
+                return Err(PrinterError::Timeout);
+            }
+            self.delay.delay_us(RESPONSE_POLL_MICROS).await;
+            waited += RESPONSE_POLL_MICROS;
+        }
+        let mut response = [0];
+        self.serial
+            .read(&mut response)
+            .await
+            .map_err(PrinterError::Serial)?;
+        Ok(response[0])
+    }
+}
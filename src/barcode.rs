@@ -0,0 +1,445 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Validation of barcode texts against the requirements of each [`BarCodeSystem`], and the
+//! encoding of Code128 barcodes with the shortest sequence of code sets.
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+use core::ops::{Range, RangeInclusive};
+
+use crate::{BarCodeSpecialCharacter, BarCodeSystem};
+
+/// Characters supported by Code39, besides the start/stop character `*`
+const CODE39_CHARSET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%+-./";
+/// Characters supported by Codabar between the start and stop characters
+const CODABAR_CHARSET: &[u8] = b"0123456789$+-./:";
+/// Start and stop characters of Codabar
+const CODABAR_START_STOP: &[u8] = b"ABCDabcd";
+
+/// Describes why a text cannot be printed as a barcode of a given [`BarCodeSystem`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BarcodeError {
+    /// The character at the given (byte) position is not supported by the barcode system.
+    InvalidCharacter { position: usize, character: char },
+    /// The text has a length which is not supported by the barcode system.
+    InvalidLength(usize),
+    /// The check digit included in the text does not match the data. Contains the correct one.
+    InvalidCheckDigit(char),
+    /// The UPC-A number cannot be compressed into a UPC-E barcode.
+    NotCompressible,
+    /// Codabar text must start and end with one of `A`, `B`, `C` or `D`.
+    MissingStartStop,
+    /// Code128 text must start with a code set selection (`{A`, `{B` or `{C`).
+    MissingCodeSet,
+}
+
+impl Display for BarcodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BarcodeError::InvalidCharacter {
+                position,
+                character,
+            } => write!(f, "unsupported character {:?} at {}", character, position),
+            BarcodeError::InvalidLength(len) => write!(f, "unsupported length {}", len),
+            BarcodeError::InvalidCheckDigit(expected) => {
+                write!(f, "wrong check digit, expected {}", expected)
+            }
+            BarcodeError::NotCompressible => write!(f, "number cannot be compressed to UPC-E"),
+            BarcodeError::MissingStartStop => write!(f, "missing Codabar start/stop character"),
+            BarcodeError::MissingCodeSet => write!(f, "missing Code128 code set selection"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BarcodeError {}
+
+impl BarCodeSystem {
+    /// Validate `text` and return the data sent to the printer to print it as a barcode of this
+    /// system.
+    ///
+    /// | System    | Characters                               | Length                      |
+    /// |-----------|------------------------------------------|-----------------------------|
+    /// | `UpcA`    | `0`-`9`                                  | 11 or 12                    |
+    /// | `UpcE`    | `0`-`9`, starting with `0` or `1`        | 11 or 12 (UPC-A number)     |
+    /// | `Ean13`   | `0`-`9`                                  | 12 or 13                    |
+    /// | `Ean8`    | `0`-`9`                                  | 7 or 8                      |
+    /// | `Code39`  | `0`-`9`, `A`-`Z`, ` $%+-./`, `*` at ends | 1 to 255                    |
+    /// | `Itf`     | `0`-`9`                                  | even, 2 to 254              |
+    /// | `Codabar` | `0`-`9`, `$+-./:`, `A`-`D` at ends       | 2 to 255                    |
+    /// | `Code93`  | ASCII                                    | 1 to 255                    |
+    /// | `Code128` | ASCII, starting with `{A`/`{B`/`{C`      | 2 to 255                    |
+    ///
+    /// For UPC and EAN, the check digit may be omitted, in which case it is computed and
+    /// appended. If it is included, it is verified.
+    pub fn payload(self, text: &str) -> Result<Vec<u8>, BarcodeError> {
+        let bytes = text.as_bytes();
+        match self {
+            BarCodeSystem::UpcA => with_check_digit(text, 11..=12),
+            BarCodeSystem::UpcE => {
+                let payload = with_check_digit(text, 11..=12)?;
+                if upc_e_compressible(&payload) {
+                    Ok(payload)
+                } else {
+                    Err(BarcodeError::NotCompressible)
+                }
+            }
+            BarCodeSystem::Ean13 => with_check_digit(text, 12..=13),
+            BarCodeSystem::Ean8 => with_check_digit(text, 7..=8),
+            BarCodeSystem::Code39 => {
+                check_length(bytes, 1..=255)?;
+                // The start/stop character may only enclose the data
+                let data = text.strip_prefix('*').unwrap_or(text);
+                let offset = text.len() - data.len();
+                let data = data.strip_suffix('*').unwrap_or(data);
+                check_charset(data, offset, |b| CODE39_CHARSET.contains(&b))?;
+                Ok(bytes.to_vec())
+            }
+            BarCodeSystem::Itf => {
+                check_length(bytes, 2..=254)?;
+                if bytes.len() % 2 != 0 {
+                    return Err(BarcodeError::InvalidLength(bytes.len()));
+                }
+                check_charset(text, 0, |b| b.is_ascii_digit())?;
+                Ok(bytes.to_vec())
+            }
+            BarCodeSystem::Codabar => {
+                check_length(bytes, 2..=255)?;
+                let (first, last) = (bytes[0], bytes[bytes.len() - 1]);
+                if !CODABAR_START_STOP.contains(&first) || !CODABAR_START_STOP.contains(&last) {
+                    return Err(BarcodeError::MissingStartStop);
+                }
+                check_charset(&text[1..text.len() - 1], 1, |b| {
+                    CODABAR_CHARSET.contains(&b)
+                })?;
+                Ok(bytes.to_vec())
+            }
+            BarCodeSystem::Code93 => {
+                check_length(bytes, 1..=255)?;
+                check_charset(text, 0, |_| true)?;
+                Ok(bytes.to_vec())
+            }
+            BarCodeSystem::Code128 => {
+                check_length(bytes, 2..=255)?;
+                check_charset(text, 0, |_| true)?;
+                if !matches!(&bytes[..2], b"{A" | b"{B" | b"{C") {
+                    return Err(BarcodeError::MissingCodeSet);
+                }
+                Ok(bytes.to_vec())
+            }
+        }
+    }
+}
+
+/// Compute the UPC/EAN check digit of `digits`. Returns `None` if `digits` contains anything but
+/// ASCII digits.
+///
+/// # Example
+/// ```
+/// assert_eq!(thermal_print::check_digit("400638133393"), Some('1'));
+/// ```
+pub fn check_digit(digits: &str) -> Option<char> {
+    if digits.bytes().all(|b| b.is_ascii_digit()) {
+        Some(weighted_check_digit(digits.as_bytes()) as char)
+    } else {
+        None
+    }
+}
+
+/// The UPC/EAN check digit of a sequence of ASCII digits, as an ASCII digit.
+fn weighted_check_digit(digits: &[u8]) -> u8 {
+    // The weights alternate between 3 and 1, starting with 3 at the rightmost digit
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| u32::from(digit - b'0') * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+    b'0' + ((10 - sum % 10) % 10) as u8
+}
+
+fn check_length(bytes: &[u8], range: RangeInclusive<usize>) -> Result<(), BarcodeError> {
+    if range.contains(&bytes.len()) {
+        Ok(())
+    } else {
+        Err(BarcodeError::InvalidLength(bytes.len()))
+    }
+}
+
+/// Check that `text` is ASCII and all characters are accepted by `allowed`. `offset` is the
+/// position of `text` in the whole text, for error reporting.
+fn check_charset(
+    text: &str,
+    offset: usize,
+    allowed: impl Fn(u8) -> bool,
+) -> Result<(), BarcodeError> {
+    match text
+        .char_indices()
+        .find(|(_, c)| !c.is_ascii() || !allowed(*c as u8))
+    {
+        Some((position, character)) => Err(BarcodeError::InvalidCharacter {
+            position: position + offset,
+            character,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Validate a UPC/EAN number whose full length (including the check digit) is the end of
+/// `lengths`, and append the check digit if it is omitted.
+fn with_check_digit(text: &str, lengths: RangeInclusive<usize>) -> Result<Vec<u8>, BarcodeError> {
+    let bytes = text.as_bytes();
+    check_length(bytes, lengths.clone())?;
+    check_charset(text, 0, |b| b.is_ascii_digit())?;
+
+    let data_len = *lengths.start();
+    let expected = weighted_check_digit(&bytes[..data_len]);
+    let mut payload = bytes.to_vec();
+    match payload.get(data_len) {
+        None => payload.push(expected),
+        Some(&digit) if digit == expected => {}
+        Some(_) => return Err(BarcodeError::InvalidCheckDigit(expected as char)),
+    }
+    Ok(payload)
+}
+
+/// Whether a UPC-A number (with check digit) can be represented as a UPC-E barcode, which drops
+/// runs of zeros from the manufacturer and product codes.
+fn upc_e_compressible(upc_a: &[u8]) -> bool {
+    // Digits 1 to 5 are the manufacturer code, digits 6 to 10 the product code
+    let zeros = |range: Range<usize>| upc_a[range].iter().all(|d| *d == b'0');
+    matches!(upc_a[0], b'0' | b'1')
+        && (
+            // Manufacturer code ending in 000, 100 or 200 with a product code of at most 999
+            (matches!(upc_a[3], b'0'..=b'2') && zeros(4..8))
+            // Manufacturer code ending in 00 with a product code of at most 99
+            || zeros(4..9)
+            // Manufacturer code ending in 0 with a product code of at most 9
+            || zeros(5..10)
+            // Any manufacturer code with a product code from 5 to 9
+            || (zeros(6..10) && matches!(upc_a[10], b'5'..=b'9'))
+        )
+}
+
+/// One of the code sets of Code128, which determine how data characters are encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CodeSet {
+    /// ASCII control characters, digits and uppercase letters
+    A,
+    /// Digits, uppercase and lowercase letters
+    B,
+    /// Pairs of digits
+    C,
+}
+
+impl CodeSet {
+    const ALL: [CodeSet; 3] = [CodeSet::A, CodeSet::B, CodeSet::C];
+
+    fn selection(self) -> BarCodeSpecialCharacter {
+        match self {
+            CodeSet::A => BarCodeSpecialCharacter::CodeA,
+            CodeSet::B => BarCodeSpecialCharacter::CodeB,
+            CodeSet::C => BarCodeSpecialCharacter::CodeC,
+        }
+    }
+
+    fn contains(self, byte: u8) -> bool {
+        match self {
+            CodeSet::A => byte < 0x60,
+            CodeSet::B => (0x20..0x80).contains(&byte),
+            CodeSet::C => false,
+        }
+    }
+}
+
+/// An element of Code128 data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Code128Item {
+    Char(char),
+    Fnc1,
+}
+
+/// How a step of the encoding consumes the data.
+#[derive(Clone, Copy)]
+enum Code128Step {
+    /// A single character of the current code set, or FNC1
+    Single,
+    /// A single character of the other of code sets A and B
+    Shift,
+    /// A pair of digits in code set C
+    Pair,
+}
+
+/// Builds the text of a Code128 barcode, choosing the code sets which result in the shortest
+/// barcode. Pass the result of [`Code128::encode`] to [`Printer::print_barcode`] or use
+/// [`Printer::print_code128`].
+///
+/// [`Printer::print_barcode`]: crate::Printer::print_barcode
+/// [`Printer::print_code128`]: crate::Printer::print_code128
+///
+/// # Example
+/// A GS1-128 barcode containing a GTIN (AI 01) and a batch number (AI 10):
+/// ```
+/// # use thermal_print::Code128;
+/// let mut barcode = Code128::new();
+/// barcode.fnc1().text("0109501101530003").text("10ABC123");
+/// // The digits are encoded in code set C, the letters in code set A
+/// assert!(barcode.encode().unwrap().starts_with("{C{1"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Code128 {
+    items: Vec<Code128Item>,
+}
+
+impl Code128 {
+    /// Create an empty Code128 barcode.
+    pub fn new() -> Code128 {
+        Code128::default()
+    }
+
+    /// Append ASCII `text`. Non-ASCII characters are reported by [`Code128::encode`].
+    pub fn text(&mut self, text: &str) -> &mut Code128 {
+        self.items.extend(text.chars().map(Code128Item::Char));
+        self
+    }
+
+    /// Append the FNC1 function character. At the start of the barcode, it marks the barcode as
+    /// GS1-128, in which it also separates variable-length element strings.
+    pub fn fnc1(&mut self) -> &mut Code128 {
+        self.items.push(Code128Item::Fnc1);
+        self
+    }
+
+    /// Encode the barcode as the text expected by the printer, including code set selections
+    /// (`{A`, `{B`, `{C`), shifts (`{S`) and function characters (`{1`).
+    ///
+    /// Returns [`BarcodeError::InvalidCharacter`] if the text is not ASCII, and
+    /// [`BarcodeError::InvalidLength`] if the barcode is empty or the encoded text is longer than
+    /// 255 bytes.
+    pub fn encode(&self) -> Result<String, BarcodeError> {
+        let items = &self.items;
+        if items.is_empty() {
+            return Err(BarcodeError::InvalidLength(0));
+        }
+        for (position, item) in items.iter().enumerate() {
+            if let Code128Item::Char(character) = *item {
+                if !character.is_ascii() {
+                    return Err(BarcodeError::InvalidCharacter {
+                        position,
+                        character,
+                    });
+                }
+            }
+        }
+
+        // Find the cheapest encoding: cost[i][set] is the minimal number of symbols to encode the
+        // first i items, ending in the given code set. Any code set can be selected at the start.
+        let n = items.len();
+        let mut cost = vec![[usize::MAX; 3]; n + 1];
+        let mut previous = vec![[None; 3]; n + 1];
+        cost[0] = [0; 3];
+        for i in 0..n {
+            for from in CodeSet::ALL {
+                if cost[i][from as usize] == usize::MAX {
+                    continue;
+                }
+                for to in CodeSet::ALL {
+                    let (consumed, symbols, step) = match code128_step(&items[i..], to) {
+                        Some(step) => step,
+                        None => continue,
+                    };
+                    let total = cost[i][from as usize] + symbols + usize::from(from != to);
+                    if total < cost[i + consumed][to as usize] {
+                        cost[i + consumed][to as usize] = total;
+                        previous[i + consumed][to as usize] = Some((i, from, step));
+                    }
+                }
+            }
+        }
+
+        // Walk back from the cheapest final state
+        let mut set = CodeSet::ALL
+            .into_iter()
+            .min_by_key(|set| cost[n][*set as usize])
+            .unwrap_or(CodeSet::B);
+        let mut path = Vec::new();
+        let mut i = n;
+        while let Some((start, from, step)) = previous[i][set as usize] {
+            path.push((start, set, step));
+            i = start;
+            set = from;
+        }
+
+        let escape = |text: &mut Vec<u8>, special: BarCodeSpecialCharacter| {
+            text.extend_from_slice(&[BarCodeSpecialCharacter::CurlyOpen.into(), special.into()])
+        };
+        let mut text = Vec::new();
+        let mut current = None;
+        for (start, set, step) in path.into_iter().rev() {
+            if current != Some(set) {
+                escape(&mut text, set.selection());
+                current = Some(set);
+            }
+            match (step, items[start]) {
+                (_, Code128Item::Fnc1) => escape(&mut text, BarCodeSpecialCharacter::Fnc1),
+                (Code128Step::Pair, Code128Item::Char(tens)) => {
+                    if let Code128Item::Char(ones) = items[start + 1] {
+                        // Code set C encodes each pair of digits as a single byte
+                        text.push((tens as u8 - b'0') * 10 + (ones as u8 - b'0'));
+                    }
+                }
+                (step, Code128Item::Char(c)) => {
+                    if let Code128Step::Shift = step {
+                        escape(&mut text, BarCodeSpecialCharacter::Shift);
+                    }
+                    if c == '{' {
+                        escape(&mut text, BarCodeSpecialCharacter::CurlyOpen);
+                    } else {
+                        text.push(c as u8);
+                    }
+                }
+            }
+        }
+
+        if text.len() > 255 {
+            return Err(BarcodeError::InvalidLength(text.len()));
+        }
+        // Only ASCII characters and values below 100 were added
+        Ok(text.into_iter().map(char::from).collect())
+    }
+}
+
+/// How the next item(s) can be encoded in code set `set`: the number of items consumed, the
+/// number of symbols used, and the kind of step. `None` if they cannot be encoded in that set.
+/// All characters must be ASCII.
+fn code128_step(items: &[Code128Item], set: CodeSet) -> Option<(usize, usize, Code128Step)> {
+    match (set, items) {
+        (_, [Code128Item::Fnc1, ..]) => Some((1, 1, Code128Step::Single)),
+        (CodeSet::C, [Code128Item::Char(a), Code128Item::Char(b), ..])
+            if a.is_ascii_digit() && b.is_ascii_digit() =>
+        {
+            Some((2, 1, Code128Step::Pair))
+        }
+        (CodeSet::C, _) => None,
+        (_, [Code128Item::Char(c), ..]) if set.contains(*c as u8) => {
+            Some((1, 1, Code128Step::Single))
+        }
+        // A character of the other of code sets A and B, which can be shifted to
+        (_, [Code128Item::Char(_), ..]) => Some((1, 2, Code128Step::Shift)),
+        (_, []) => None,
+    }
+}
@@ -0,0 +1,324 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Conversion of bitmaps into the monochrome raster images the printer understands.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use bitvec::prelude::*;
+use derive_builder::Builder;
+
+use crate::{Bitmap, Justification, PrinterError, DOT_WIDTH};
+
+/// The most pixels of a scaled bitmap, 4096 rows of the full width of the paper (about 50 cm)
+const MAX_SCALED_PIXELS: usize = DOT_WIDTH as usize * 4096;
+/// Luminance below which a pixel is printed by the error diffusion methods
+const MID_GRAY: i16 = 128;
+/// The 4x4 Bayer matrix used for ordered dithering
+const BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+/// Floyd–Steinberg: the neighbours receiving the error as (dx, dy, sixteenths)
+const FLOYD_STEINBERG: [(isize, usize, i16); 4] = [(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)];
+/// Atkinson: the neighbours receiving the error as (dx, dy, eighths). Only 3/4 of the error are
+/// passed on, which keeps the contrast high.
+const ATKINSON: [(isize, usize, i16); 6] = [
+    (1, 0, 1),
+    (2, 0, 1),
+    (-1, 1, 1),
+    (0, 1, 1),
+    (1, 1, 1),
+    (0, 2, 1),
+];
+
+/// Determines how the gray levels of a bitmap are converted into dots. Default: `Threshold(128)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dithering {
+    /// print every pixel whose luminance (0 = black, 255 = white) is below the given level, which
+    /// works best for logos and line art
+    Threshold(u8),
+    /// Floyd–Steinberg error diffusion, suited for photos
+    FloydSteinberg,
+    /// Atkinson error diffusion, which produces more contrast than Floyd–Steinberg
+    Atkinson,
+    /// ordered dithering with a 4x4 Bayer matrix, which produces a regular pattern
+    Bayer,
+}
+
+impl Default for Dithering {
+    fn default() -> Self {
+        Dithering::Threshold(128)
+    }
+}
+
+/// Determines the size at which a bitmap is printed. Default: `Original`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Scaling {
+    /// one dot per pixel
+    #[default]
+    Original,
+    /// scale the image up or down to fill the width of the paper, minus the left margin, keeping
+    /// its aspect ratio
+    FitWidth,
+    /// multiply the width and height by the given factor, e.g. `2.0` to print each pixel as 2x2
+    /// dots
+    Factor(f32),
+}
+
+/// Determines how the pixels of a scaled bitmap are computed. Default: `Nearest`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Resampling {
+    /// repeat or drop pixels, which keeps edges sharp and suits logos and pixel art
+    #[default]
+    Nearest,
+    /// interpolate between the four nearest pixels, which looks smoother for photos
+    Bilinear,
+}
+
+/// Rotates a bitmap clockwise before printing. Default: `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+/// Configures how a bitmap is printed.
+///
+/// The image is rotated first, then scaled, and finally converted into dots. Prefer to use
+/// [`BitmapOptionsBuilder`] to construct.
+#[derive(Builder, Clone, Copy, Debug, Default)]
+#[builder(default, setter(into), no_std)]
+pub struct BitmapOptions {
+    /// the [`Dithering`] method converting gray levels into dots
+    pub dithering: Dithering,
+    /// the [`Scaling`] of the image
+    pub scaling: Scaling,
+    /// the [`Resampling`] method used if the image is scaled
+    pub resampling: Resampling,
+    /// the clockwise [`Rotation`] of the image
+    pub rotation: Rotation,
+    /// pads the image to the width of the paper and aligns it within the area right of the
+    /// margin. If not set, the image is positioned according to the justification of the printer,
+    /// or aligned left if there is a margin.
+    pub alignment: Option<Justification>,
+    /// the number of dots left blank at the left edge of the paper
+    pub left_margin: u16,
+}
+
+/// Convert a bitmap into raster bit image data, padding each row to full bytes. Also returns the
+/// number of bytes per row and the number of rows.
+///
+/// Returns [`PrinterError::InvalidArgument`] if the left margin is not on the paper, or the scaled
+/// bitmap is wider than the paper or has more than [`MAX_SCALED_PIXELS`].
+pub(crate) fn bitmap_raster<E>(
+    bmp: Bitmap,
+    options: BitmapOptions,
+) -> Result<(Vec<u8>, usize, usize), PrinterError<E>> {
+    let (gray, width, height) =
+        rotate(bmp.luminance(), bmp.width(), bmp.height(), options.rotation);
+
+    let left_margin = options.left_margin as usize;
+    if left_margin >= DOT_WIDTH as usize {
+        return Err(PrinterError::InvalidArgument(
+            "left margin beyond the printable area",
+        ));
+    }
+    let available = DOT_WIDTH as usize - left_margin;
+    let (scaled_width, scaled_height) = match options.scaling {
+        _ if width == 0 || height == 0 => (0, 0),
+        Scaling::Original => (width, height),
+        Scaling::FitWidth => {
+            let scaled_height = height
+                .checked_mul(available)
+                .and_then(|size| size.checked_add(width / 2))
+                .ok_or(PrinterError::InvalidArgument("bitmap too large"))?
+                / width;
+            (available, scaled_height)
+        }
+        Scaling::Factor(factor) if factor.is_finite() && factor > 0.0 => {
+            // Round to the nearest size, but keep at least one pixel
+            let scale = |size: usize| ((size as f32 * factor + 0.5) as usize).max(1);
+            (scale(width), scale(height))
+        }
+        Scaling::Factor(_) => return Err(PrinterError::InvalidArgument("invalid scale factor")),
+    };
+    if scaled_width > available {
+        return Err(PrinterError::InvalidArgument(
+            "bitmap wider than the printable area",
+        ));
+    }
+    match scaled_width.checked_mul(scaled_height) {
+        Some(pixels) if pixels <= MAX_SCALED_PIXELS => {}
+        _ => return Err(PrinterError::InvalidArgument("bitmap too large")),
+    }
+    let gray = match options.resampling {
+        _ if (scaled_width, scaled_height) == (width, height) => gray,
+        Resampling::Nearest => resample_nearest(&gray, width, height, scaled_width, scaled_height),
+        Resampling::Bilinear => {
+            resample_bilinear(&gray, width, height, scaled_width, scaled_height)
+        }
+    };
+    let (width, height) = (scaled_width, scaled_height);
+
+    // Aligned images are padded to the width of the paper, and dithered before, so that the
+    // padding stays blank
+    let (offset, raster_width) = match options.alignment {
+        None if left_margin == 0 => (0, width),
+        None | Some(Justification::Left) => (left_margin, DOT_WIDTH as usize),
+        Some(Justification::Center) => (left_margin + (available - width) / 2, DOT_WIDTH as usize),
+        Some(Justification::Right) => (DOT_WIDTH as usize - width, DOT_WIDTH as usize),
+    };
+    let x_bytes = raster_width.div_ceil(8);
+    let mut image_bits = bitvec![u8, Msb0; 0; x_bytes * 8 * height];
+    for (i, dot) in dither(gray, width, options.dithering)
+        .into_iter()
+        .enumerate()
+    {
+        if dot {
+            image_bits.set(i / width * x_bytes * 8 + offset + i % width, true);
+        }
+    }
+    Ok((image_bits.into_vec(), x_bytes, height))
+}
+
+/// Rotate an image of `width` and `height` with the luminances `gray`. Also returns the new width
+/// and height.
+fn rotate(
+    gray: Vec<u8>,
+    width: usize,
+    height: usize,
+    rotation: Rotation,
+) -> (Vec<u8>, usize, usize) {
+    match rotation {
+        Rotation::None => (gray, width, height),
+        Rotation::Clockwise180 => (gray.into_iter().rev().collect(), width, height),
+        // The columns become rows: the left column is the top row after a clockwise rotation
+        Rotation::Clockwise90 => {
+            let rotated = (0..width)
+                .flat_map(|x| (0..height).rev().map(move |y| (x, y)))
+                .map(|(x, y)| gray[y * width + x])
+                .collect();
+            (rotated, height, width)
+        }
+        Rotation::Clockwise270 => {
+            let rotated = (0..width)
+                .rev()
+                .flat_map(|x| (0..height).map(move |y| (x, y)))
+                .map(|(x, y)| gray[y * width + x])
+                .collect();
+            (rotated, height, width)
+        }
+    }
+}
+
+/// Scale an image to `new_width` and `new_height`, taking the pixel closest to the center of each
+/// new pixel.
+fn resample_nearest(
+    gray: &[u8],
+    width: usize,
+    height: usize,
+    new_width: usize,
+    new_height: usize,
+) -> Vec<u8> {
+    let nearest = |i: usize, size: usize, new_size: usize| (2 * i + 1) * size / (2 * new_size);
+    let columns: Vec<usize> = (0..new_width)
+        .map(|x| nearest(x, width, new_width))
+        .collect();
+    (0..new_height)
+        .map(|y| nearest(y, height, new_height))
+        .flat_map(|y| columns.iter().map(move |x| gray[y * width + x]))
+        .collect()
+}
+
+/// Scale an image to `new_width` and `new_height`, interpolating between the four pixels around
+/// the center of each new pixel.
+fn resample_bilinear(
+    gray: &[u8],
+    width: usize,
+    height: usize,
+    new_width: usize,
+    new_height: usize,
+) -> Vec<u8> {
+    // The pixels before and after the center of each new pixel, and the weight of the latter in
+    // 1/256
+    let neighbours = |size: usize, new_size: usize| -> Vec<(usize, usize, u32)> {
+        (0..new_size)
+            .map(|i| {
+                let position = ((2 * i + 1) * size * 128 / new_size).saturating_sub(128);
+                let before = (position / 256).min(size - 1);
+                let after = (before + 1).min(size - 1);
+                (before, after, (position % 256) as u32)
+            })
+            .collect()
+    };
+    let columns = neighbours(width, new_width);
+    let mut scaled = Vec::with_capacity(new_width * new_height);
+    for (top, bottom, weight_y) in neighbours(height, new_height) {
+        for &(left, right, weight_x) in &columns {
+            let pixel = |x: usize, y: usize| u32::from(gray[y * width + x]);
+            let upper = pixel(left, top) * (256 - weight_x) + pixel(right, top) * weight_x;
+            let lower = pixel(left, bottom) * (256 - weight_x) + pixel(right, bottom) * weight_x;
+            let value = (upper * (256 - weight_y) + lower * weight_y + (1 << 15)) >> 16;
+            scaled.push(value as u8);
+        }
+    }
+    scaled
+}
+
+/// Decide which pixels of an image of `width` with the luminances `gray` are printed.
+fn dither(gray: Vec<u8>, width: usize, dithering: Dithering) -> Vec<bool> {
+    match dithering {
+        Dithering::Threshold(level) => gray.into_iter().map(|luma| luma < level).collect(),
+        Dithering::FloydSteinberg => diffuse_error(gray, width, &FLOYD_STEINBERG, 16),
+        Dithering::Atkinson => diffuse_error(gray, width, &ATKINSON, 8),
+        Dithering::Bayer => gray
+            .into_iter()
+            .enumerate()
+            .map(|(i, luma)| {
+                let level = BAYER_MATRIX[i / width % 4][i % width % 4] * 16 + 8;
+                luma < level
+            })
+            .collect(),
+    }
+}
+
+/// Threshold each pixel at mid gray and pass its error on to the neighbours in `kernel`, whose
+/// shares are divided by `divisor`.
+fn diffuse_error(
+    gray: Vec<u8>,
+    width: usize,
+    kernel: &[(isize, usize, i16)],
+    divisor: i16,
+) -> Vec<bool> {
+    let height = gray.len() / width.max(1);
+    let mut levels: Vec<i16> = gray.into_iter().map(i16::from).collect();
+    let mut dots = vec![false; levels.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let level = levels[y * width + x];
+            let dot = level < MID_GRAY;
+            dots[y * width + x] = dot;
+            let error = level - if dot { 0 } else { u8::MAX as i16 };
+            for &(dx, dy, share) in kernel {
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx >= 0 && (nx as usize) < width && ny < height {
+                    levels[ny * width + nx as usize] += error * share / divisor;
+                }
+            }
+        }
+    }
+    dots
+}
@@ -0,0 +1,363 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Decoding of BMP files, including the bit depths and color tables `tinybmp` does not handle.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+
+use tinybmp::{RawBmp, RowOrder};
+
+use crate::image::{luma, on_paper};
+
+/// Size of the file header preceding the DIB header
+const FILE_HEADER_SIZE: usize = 14;
+/// Size of the `BITMAPCOREHEADER` of OS/2 bitmaps, whose color table entries have 3 bytes
+const CORE_HEADER_SIZE: usize = 12;
+/// Size of the `BITMAPINFOHEADER`, after which the channel masks follow if not included
+const INFO_HEADER_SIZE: usize = 40;
+/// Uncompressed pixels in the default format of the bit depth
+const BI_RGB: u32 = 0;
+/// Uncompressed pixels whose channels are given by masks
+const BI_BITFIELDS: u32 = 3;
+/// Like `BI_BITFIELDS`, with an additional alpha mask
+const BI_ALPHABITFIELDS: u32 = 6;
+/// Default channel masks (red, green, blue, alpha) of 16 bit pixels: 5 bits per channel
+const RGB555: [u32; 4] = [0x7C00, 0x03E0, 0x001F, 0];
+/// Default channel masks of 24 and 32 bit pixels, whose fourth byte is unused
+const RGB888: [u32; 4] = [0xFF_0000, 0x00_FF00, 0x00_00FF, 0];
+
+/// Describes why a BMP file cannot be decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BmpError {
+    /// The file is truncated or its headers are inconsistent.
+    Malformed,
+    /// Only 1, 4, 8, 16, 24 and 32 bits per pixel are supported.
+    UnsupportedBitDepth(u16),
+    /// Only uncompressed images are supported, not the given compression method.
+    UnsupportedCompression(u32),
+}
+
+impl Display for BmpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BmpError::Malformed => write!(f, "malformed BMP file"),
+            BmpError::UnsupportedBitDepth(bpp) => write!(f, "unsupported bit depth {}", bpp),
+            BmpError::UnsupportedCompression(method) => {
+                write!(f, "unsupported compression method {}", method)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BmpError {}
+
+/// An image which can be printed via [`Printer::print_bitmap`](crate::Printer::print_bitmap).
+///
+/// Created from the contents of a BMP file via [`Bitmap::from_slice`], which supports 1, 4 and 8
+/// bits per pixel with a color table, 16 and 32 bits per pixel with optional channel masks
+/// including alpha, and 24 bits per pixel, stored bottom-up or top-down. Transparent pixels are
+/// treated as white.
+///
+/// A [`RawBmp`] of `tinybmp` can be converted as well. As `tinybmp` does not expose the color
+/// table, the color indices of such images with 1 or 8 bits per pixel are taken as gray levels:
+/// 0 is black, and the highest index white. Images in other formats are converted from a
+/// reference to a [`GrayImage`](crate::GrayImage).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bitmap<'a> {
+    width: usize,
+    height: usize,
+    pixels: Pixels<'a>,
+}
+
+/// Where the pixels of a [`Bitmap`] come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pixels<'a> {
+    Bmp(BmpPixels<'a>),
+    /// The luminance of each pixel, row by row from the top
+    Luminance(&'a [u8]),
+}
+
+/// The pixel data of a BMP file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BmpPixels<'a> {
+    bpp: u16,
+    top_down: bool,
+    /// Red, green, blue and alpha masks of images with 16 bits per pixel or more
+    masks: [u32; 4],
+    /// The color table and its entry size, if known
+    color_table: Option<(&'a [u8], usize)>,
+    /// The rows of pixels, each padded to a multiple of 4 bytes
+    data: &'a [u8],
+}
+
+impl<'a> Bitmap<'a> {
+    /// Parse the contents of a BMP file.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let logo = Bitmap::from_slice(include_bytes!("../resources/logo.bmp")).unwrap();
+    /// printer.print_bitmap(logo, RasterBitImageMode::Normal, BitmapOptions::default())?;
+    /// ```
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Bitmap<'a>, BmpError> {
+        if bytes.get(0..2) != Some(b"BM") {
+            return Err(BmpError::Malformed);
+        }
+        let data_start = read_u32(bytes, 10)? as usize;
+        let header_size = read_u32(bytes, FILE_HEADER_SIZE)? as usize;
+        let header = FILE_HEADER_SIZE;
+
+        let (width, height, bpp, compression, colors_used) = if header_size == CORE_HEADER_SIZE {
+            let width = read_u16(bytes, header + 4)? as i32;
+            let height = read_u16(bytes, header + 6)? as i32;
+            (width, height, read_u16(bytes, header + 10)?, BI_RGB, 0)
+        } else if header_size >= INFO_HEADER_SIZE {
+            (
+                read_u32(bytes, header + 4)? as i32,
+                read_u32(bytes, header + 8)? as i32,
+                read_u16(bytes, header + 14)?,
+                read_u32(bytes, header + 16)?,
+                read_u32(bytes, header + 32)? as usize,
+            )
+        } else {
+            return Err(BmpError::Malformed);
+        };
+        if width < 0 || height == i32::MIN {
+            return Err(BmpError::Malformed);
+        }
+        if !matches!(bpp, 1 | 4 | 8 | 16 | 24 | 32) {
+            return Err(BmpError::UnsupportedBitDepth(bpp));
+        }
+
+        let mut table_start = header.checked_add(header_size).ok_or(BmpError::Malformed)?;
+        let masks = match compression {
+            BI_RGB if bpp == 16 => RGB555,
+            // Headers from version 4 on contain an alpha mask, which is also used without
+            // BI_BITFIELDS by some encoders
+            BI_RGB if bpp == 32 && header_size > INFO_HEADER_SIZE + 12 => {
+                let mut masks = RGB888;
+                masks[3] = read_u32(bytes, header + INFO_HEADER_SIZE + 12)?;
+                masks
+            }
+            BI_RGB => RGB888,
+            BI_BITFIELDS | BI_ALPHABITFIELDS if bpp == 16 || bpp == 32 => {
+                let count = if compression == BI_BITFIELDS && header_size < INFO_HEADER_SIZE + 16 {
+                    3
+                } else {
+                    4
+                };
+                // The masks follow the BITMAPINFOHEADER, or are part of a larger header
+                let mut masks = [0; 4];
+                for (i, mask) in masks.iter_mut().enumerate().take(count) {
+                    *mask = read_u32(bytes, header + INFO_HEADER_SIZE + i * 4)?;
+                }
+                if header_size == INFO_HEADER_SIZE {
+                    table_start = table_start
+                        .checked_add(count * 4)
+                        .ok_or(BmpError::Malformed)?;
+                }
+                masks
+            }
+            _ => return Err(BmpError::UnsupportedCompression(compression)),
+        };
+
+        let color_table = if bpp <= 8 {
+            let entry_size = if header_size == CORE_HEADER_SIZE {
+                3
+            } else {
+                4
+            };
+            let entries = match colors_used {
+                0 => 1 << bpp,
+                entries => entries.min(1 << bpp),
+            };
+            let table = entries
+                .checked_mul(entry_size)
+                .and_then(|len| len.checked_add(table_start))
+                .and_then(|table_end| bytes.get(table_start..table_end))
+                .ok_or(BmpError::Malformed)?;
+            Some((table, entry_size))
+        } else {
+            None
+        };
+
+        // A negative height marks a top-down image
+        let top_down = height < 0;
+        let (width, height) = (width as usize, height.unsigned_abs() as usize);
+        let data_end = row_bytes(width, bpp)
+            .and_then(|len| len.checked_mul(height))
+            .and_then(|len| len.checked_add(data_start))
+            .ok_or(BmpError::Malformed)?;
+        let data = bytes.get(data_start..data_end).ok_or(BmpError::Malformed)?;
+        Ok(Bitmap {
+            width,
+            height,
+            pixels: Pixels::Bmp(BmpPixels {
+                bpp,
+                top_down,
+                masks,
+                color_table,
+                data,
+            }),
+        })
+    }
+
+    /// A bitmap of the gray levels `luminance`, which has `width * height` entries.
+    pub(crate) fn from_luminance(width: usize, height: usize, luminance: &'a [u8]) -> Self {
+        Bitmap {
+            width,
+            height,
+            pixels: Pixels::Luminance(luminance),
+        }
+    }
+
+    /// The width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The luminance of each pixel, row by row from the top, from 0 (black) to 255 (white).
+    ///
+    /// Colors are weighted according to ITU-R BT.601, and transparent pixels are blended with the
+    /// white paper.
+    pub(crate) fn luminance(&self) -> Vec<u8> {
+        let bmp = match self.pixels {
+            Pixels::Bmp(bmp) => bmp,
+            Pixels::Luminance(luminance) => return luminance.to_vec(),
+        };
+        let mut gray = vec![u8::MAX; self.width * self.height];
+        // Rows too long to address cannot be part of the data either
+        let row_len = match row_bytes(self.width, bmp.bpp) {
+            Some(row_len) => row_len,
+            None => return gray,
+        };
+        for y in 0..self.height {
+            // Bottom-up images start with the last row
+            let stored_row = if bmp.top_down { y } else { self.height - 1 - y };
+            // Rows missing from a truncated RawBmp are left white
+            let row = match stored_row
+                .checked_mul(row_len)
+                .and_then(|start| bmp.data.get(start..)?.get(..row_len))
+            {
+                Some(row) => row,
+                None => continue,
+            };
+            for x in 0..self.width {
+                gray[y * self.width + x] = bmp.pixel_luminance(row, x);
+            }
+        }
+        gray
+    }
+}
+
+impl BmpPixels<'_> {
+    /// The luminance of the pixel in column `x` of `row`.
+    fn pixel_luminance(&self, row: &[u8], x: usize) -> u8 {
+        const WHITE: u32 = u8::MAX as u32;
+        let bpp = self.bpp as usize;
+        if bpp <= 8 {
+            let shift = 8 - bpp - x * bpp % 8;
+            let index = (row[x * bpp / 8] >> shift) as usize & ((1 << bpp) - 1);
+            return match self.color_table {
+                Some((table, entry_size)) => match table.get(index * entry_size..) {
+                    // Entries are stored as blue, green, red
+                    Some([blue, green, red, ..]) => {
+                        luma(u32::from(*red), u32::from(*green), u32::from(*blue)) as u8
+                    }
+                    _ => 0,
+                },
+                None => (index * WHITE as usize / ((1 << bpp) - 1)) as u8,
+            };
+        }
+
+        let bytes = &row[x * bpp / 8..][..bpp / 8];
+        let color = bytes
+            .iter()
+            .rev()
+            .fold(0, |color, byte| color << 8 | u32::from(*byte));
+        let [red, green, blue, alpha] = self.masks;
+        let luma = luma(
+            channel(color, red),
+            channel(color, green),
+            channel(color, blue),
+        );
+        let alpha = match alpha {
+            0 => WHITE,
+            mask => channel(color, mask),
+        };
+        on_paper(luma, alpha)
+    }
+}
+
+impl<'a> From<RawBmp<'a>> for Bitmap<'a> {
+    fn from(bmp: RawBmp<'a>) -> Self {
+        let header = bmp.header();
+        let bpp = header.bpp.bits();
+        let masks = match header.channel_masks {
+            Some(masks) => [masks.red, masks.green, masks.blue, masks.alpha],
+            None if bpp == 16 => RGB555,
+            None => RGB888,
+        };
+        Bitmap {
+            width: header.image_size.width as usize,
+            height: header.image_size.height as usize,
+            pixels: Pixels::Bmp(BmpPixels {
+                bpp,
+                top_down: header.row_order == RowOrder::TopDown,
+                masks,
+                color_table: None,
+                data: bmp.image_data(),
+            }),
+        }
+    }
+}
+
+/// The number of bytes of a row of `width` pixels, which is padded to a multiple of 4 bytes, or
+/// `None` if it overflows.
+fn row_bytes(width: usize, bpp: u16) -> Option<usize> {
+    width.checked_mul(bpp as usize)?.div_ceil(32).checked_mul(4)
+}
+
+/// The value of the channel selected by `mask`, scaled to 0..=255.
+fn channel(color: u32, mask: u32) -> u32 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    // Masks may be up to 32 bits wide, so the product needs more
+    let value = ((color & mask) >> shift) as u64 * u8::MAX as u64 / (mask >> shift) as u64;
+    value as u32
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, BmpError> {
+    match bytes.get(offset..offset + 2) {
+        Some(&[low, high]) => Ok(u16::from_le_bytes([low, high])),
+        _ => Err(BmpError::Malformed),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, BmpError> {
+    match bytes.get(offset..offset + 4) {
+        Some(&[b0, b1, b2, b3]) => Ok(u32::from_le_bytes([b0, b1, b2, b3])),
+        _ => Err(BmpError::Malformed),
+    }
+}
@@ -0,0 +1,145 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! A drawing surface for `embedded-graphics`, which is printed as a raster image.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use embedded_graphics_core::pixelcolor::BinaryColor;
+use embedded_graphics_core::prelude::{Dimensions, DrawTarget, Point, Size};
+use embedded_graphics_core::primitives::Rectangle;
+use embedded_graphics_core::Pixel;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::InputPin;
+use embedded_io::Write as SerialWrite;
+
+use crate::{raster_band_rows, Printer, PrinterError, RasterBitImageMode, StatusCheck, DOT_WIDTH};
+
+/// Bytes per row of the canvas
+const ROW_BYTES: usize = DOT_WIDTH as usize / 8;
+/// Number of rows the canvas keeps in memory before printing the topmost ones
+const WINDOW_ROWS: usize = 240;
+
+/// A canvas as wide as the paper, which `embedded-graphics` can draw on and which is printed as a
+/// raster image. Created via [`Printer::canvas`]. [`BinaryColor::On`] produces a dot.
+///
+/// The canvas grows downwards as pixels are drawn. To keep the memory bounded, only the last 240
+/// rows are kept: when a pixel is drawn further below, the topmost rows are printed in bands, and
+/// pixels drawn into them afterwards are discarded. So draw from top to bottom, with no single
+/// drawable taller than 240 dots. Pixels left or right of the paper are discarded as well.
+///
+/// Call [`PrinterCanvas::finish`] when done, which prints the remaining rows. Rows which have not
+/// been printed are lost if the canvas is dropped instead.
+pub struct PrinterCanvas<
+    'a,
+    Port: SerialWrite,
+    Delay: DelayNs,
+    Busy: InputPin,
+    Status: StatusCheck<Port>,
+> {
+    printer: &'a mut Printer<Port, Delay, Busy, Status>,
+    /// The rows kept in memory, MSB first
+    rows: Vec<u8>,
+    /// The y coordinate of the first row in `rows`
+    top: usize,
+}
+
+impl<'a, Port: SerialWrite, Delay: DelayNs, Busy: InputPin, Status: StatusCheck<Port>>
+    PrinterCanvas<'a, Port, Delay, Busy, Status>
+{
+    pub(crate) fn new(printer: &'a mut Printer<Port, Delay, Busy, Status>) -> Self {
+        PrinterCanvas {
+            printer,
+            rows: Vec::new(),
+            top: 0,
+        }
+    }
+
+    /// Print the rows which have not been printed yet.
+    pub fn finish(mut self) -> Result<(), PrinterError<Port::Error>> {
+        let rows = self.rows.len() / ROW_BYTES;
+        self.print_rows(rows)
+    }
+
+    /// Print the topmost `count` rows in memory and drop them.
+    fn print_rows(&mut self, count: usize) -> Result<(), PrinterError<Port::Error>> {
+        let len = count * ROW_BYTES;
+        self.printer.print_raster(
+            DOT_WIDTH as usize,
+            count,
+            &self.rows[..len],
+            RasterBitImageMode::Normal,
+        )?;
+        self.rows.drain(..len);
+        self.top += count;
+        Ok(())
+    }
+
+    /// Make room for the row `y` in memory, printing rows above it if necessary.
+    fn reserve_row(&mut self, y: usize) -> Result<(), PrinterError<Port::Error>> {
+        while y >= self.top + WINDOW_ROWS {
+            // Print at least a full band at once, as the printer pauses between bands. Rows which
+            // were never drawn are printed blank.
+            let count = (y + 1 - self.top - WINDOW_ROWS)
+                .max(raster_band_rows(ROW_BYTES))
+                .min(WINDOW_ROWS);
+            if self.rows.len() < count * ROW_BYTES {
+                self.rows.resize(count * ROW_BYTES, 0);
+            }
+            self.print_rows(count)?;
+        }
+        let rows = y + 1 - self.top;
+        if self.rows.len() < rows * ROW_BYTES {
+            self.rows.resize(rows * ROW_BYTES, 0);
+        }
+        Ok(())
+    }
+}
+
+impl<Port: SerialWrite, Delay: DelayNs, Busy: InputPin, Status: StatusCheck<Port>> Dimensions
+    for PrinterCanvas<'_, Port, Delay, Busy, Status>
+{
+    /// The area drawn so far, including the rows which have been printed already.
+    fn bounding_box(&self) -> Rectangle {
+        let height = self.top + self.rows.len() / ROW_BYTES;
+        Rectangle::new(Point::zero(), Size::new(DOT_WIDTH, height as u32))
+    }
+}
+
+impl<Port: SerialWrite, Delay: DelayNs, Busy: InputPin, Status: StatusCheck<Port>> DrawTarget
+    for PrinterCanvas<'_, Port, Delay, Busy, Status>
+{
+    type Color = BinaryColor;
+    type Error = PrinterError<Port::Error>;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (x, y) = (point.x as usize, point.y as usize);
+            if point.x < 0 || point.y < 0 || x >= DOT_WIDTH as usize || y < self.top {
+                continue;
+            }
+            self.reserve_row(y)?;
+            let byte = &mut self.rows[(y - self.top) * ROW_BYTES + x / 8];
+            match color {
+                BinaryColor::On => *byte |= 0x80 >> (x % 8),
+                BinaryColor::Off => *byte &= !(0x80 >> (x % 8)),
+            }
+        }
+        Ok(())
+    }
+}
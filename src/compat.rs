@@ -0,0 +1,164 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Adapters for HALs which still implement the `embedded-hal` 0.2 traits.
+//!
+//! Wrap the serial port in a [`SerialCompat`] and the delay in a [`DelayCompat`] to use them with
+//! a [`Printer`](crate::Printer):
+//!
+//! ```ignore
+//! let mut printer = Printer::new(SerialCompat::new(serial), DelayCompat(delay));
+//! ```
+
+use core::fmt::Debug;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal_02::{blocking::delay::DelayUs, serial};
+use embedded_io::{ErrorKind, ErrorType, ReadReady};
+
+/// Error of a serial port implementing the `embedded-hal` 0.2 traits.
+#[derive(Debug)]
+pub struct CompatError<E>(pub E);
+
+impl<E: Debug> embedded_io::Error for CompatError<E> {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// Implements [`embedded_io::Write`] (and [`embedded_io::Read`] and [`ReadReady`], if supported)
+/// for an `embedded-hal` 0.2 serial port.
+///
+/// If the port fails after some bytes of a write have been transmitted, the write returns their
+/// number, and the error is returned by the next write or flush.
+pub struct SerialCompat<S: serial::Write<u8>> {
+    serial: S,
+    /// A byte which was received while checking whether the port is ready for reading
+    peeked: Option<u8>,
+    /// The error which ended the last write after some bytes were transmitted
+    failed: Option<S::Error>,
+}
+
+impl<S: serial::Write<u8>> SerialCompat<S> {
+    /// Wrap an `embedded-hal` 0.2 serial port.
+    pub fn new(serial: S) -> SerialCompat<S> {
+        SerialCompat {
+            serial,
+            peeked: None,
+            failed: None,
+        }
+    }
+
+    /// Return the wrapped serial port.
+    pub fn into_inner(self) -> S {
+        self.serial
+    }
+}
+
+impl<S: serial::Write<u8>> ErrorType for SerialCompat<S>
+where
+    S::Error: Debug,
+{
+    type Error = CompatError<S::Error>;
+}
+
+impl<S: serial::Write<u8>> embedded_io::Write for SerialCompat<S>
+where
+    S::Error: Debug,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if let Some(e) = self.failed.take() {
+            return Err(CompatError(e));
+        }
+        for (count, byte) in buf.iter().enumerate() {
+            if let Err(e) = nb::block!(self.serial.write(*byte)) {
+                // The bytes already transmitted must not be sent again by the caller
+                if count == 0 {
+                    return Err(CompatError(e));
+                }
+                self.failed = Some(e);
+                return Ok(count);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if let Some(e) = self.failed.take() {
+            return Err(CompatError(e));
+        }
+        nb::block!(self.serial.flush()).map_err(CompatError)
+    }
+}
+
+impl<S> embedded_io::Read for SerialCompat<S>
+where
+    S: serial::Write<u8> + serial::Read<u8, Error = <S as serial::Write<u8>>::Error>,
+    <S as serial::Write<u8>>::Error: Debug,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Block for the first byte only, then hand out whatever else is already available
+        buf[0] = match self.peeked.take() {
+            Some(byte) => byte,
+            None => nb::block!(self.serial.read()).map_err(CompatError)?,
+        };
+        let mut count = 1;
+        while count < buf.len() {
+            match self.serial.read() {
+                Ok(byte) => {
+                    buf[count] = byte;
+                    count += 1;
+                }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(e)) => return Err(CompatError(e)),
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl<S> ReadReady for SerialCompat<S>
+where
+    S: serial::Write<u8> + serial::Read<u8, Error = <S as serial::Write<u8>>::Error>,
+    <S as serial::Write<u8>>::Error: Debug,
+{
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        if self.peeked.is_none() {
+            match self.serial.read() {
+                Ok(byte) => self.peeked = Some(byte),
+                Err(nb::Error::WouldBlock) => {}
+                Err(nb::Error::Other(e)) => return Err(CompatError(e)),
+            }
+        }
+        Ok(self.peeked.is_some())
+    }
+}
+
+/// Implements [`DelayNs`] for an `embedded-hal` 0.2 microsecond delay.
+pub struct DelayCompat<D>(pub D);
+
+impl<D: DelayUs<u32>> DelayNs for DelayCompat<D> {
+    fn delay_ns(&mut self, ns: u32) {
+        // Round up, sleeping too long is harmless but sleeping too short is not
+        self.0.delay_us(ns / 1000 + u32::from(ns % 1000 != 0));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.0.delay_us(us);
+    }
+}
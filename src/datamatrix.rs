@@ -0,0 +1,337 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! An encoder for ECC 200 Data Matrix codes. Data is encoded in ASCII mode, which is the most
+//! compact mode for digits, and into square symbols only.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use self::Anchor::{End, Start};
+use crate::symbol::{ModuleMatrix, ReedSolomon};
+
+/// The square symbol sizes: width and height in modules, width and height of a data region,
+/// number of data codewords, number of error correction codewords, and number of interleaved
+/// blocks
+const SYMBOL_SIZES: [(usize, usize, usize, usize, usize); 24] = [
+    (10, 8, 3, 5, 1),
+    (12, 10, 5, 7, 1),
+    (14, 12, 8, 10, 1),
+    (16, 14, 12, 12, 1),
+    (18, 16, 18, 14, 1),
+    (20, 18, 22, 18, 1),
+    (22, 20, 30, 20, 1),
+    (24, 22, 36, 24, 1),
+    (26, 24, 44, 28, 1),
+    (32, 14, 62, 36, 1),
+    (36, 16, 86, 42, 1),
+    (40, 18, 114, 48, 1),
+    (44, 20, 144, 56, 1),
+    (48, 22, 174, 68, 1),
+    (52, 24, 204, 84, 2),
+    (64, 14, 280, 112, 2),
+    (72, 16, 368, 144, 4),
+    (80, 18, 456, 192, 4),
+    (88, 20, 576, 224, 4),
+    (96, 22, 696, 272, 4),
+    (104, 24, 816, 336, 6),
+    (120, 18, 1050, 408, 6),
+    (132, 20, 1304, 496, 8),
+    (144, 22, 1558, 620, 10),
+];
+
+/// Codeword switching to the upper half of the byte range for the following character
+const UPPER_SHIFT: u8 = 235;
+/// First codeword padding the data to the symbol capacity
+const PAD: u8 = 129;
+
+/// Encode `data` in the smallest square Data Matrix code possible. Returns `None` if the data
+/// does not fit into a Data Matrix code.
+pub(crate) fn encode_data_matrix(data: &[u8]) -> Option<ModuleMatrix> {
+    let mut codewords = ascii_codewords(data);
+    let &(size, region_size, data_len, ecc_len, blocks) = SYMBOL_SIZES
+        .iter()
+        .find(|(_, _, data_len, _, _)| codewords.len() <= *data_len)?;
+
+    pad(&mut codewords, data_len);
+    add_ecc(&mut codewords, ecc_len, blocks);
+
+    // Place the codewords in the mapping matrix, which is the symbol without its finder and timing
+    // patterns
+    let regions = size / (region_size + 2);
+    let mut placement = Placement::new(regions * region_size);
+    placement.place(&codewords);
+
+    let mut matrix = ModuleMatrix::new(size);
+    for y in 0..size {
+        for x in 0..size {
+            let (region_x, region_y) = (x % (region_size + 2), y % (region_size + 2));
+            let dark = if region_x == 0 || region_y == region_size + 1 {
+                // Solid finder pattern on the left and bottom of each region
+                true
+            } else if region_y == 0 {
+                // Alternating timing pattern on the top and right
+                x % 2 == 0
+            } else if region_x == region_size + 1 {
+                y % 2 == 1
+            } else {
+                let row = y / (region_size + 2) * region_size + region_y - 1;
+                let column = x / (region_size + 2) * region_size + region_x - 1;
+                placement.get(row, column)
+            };
+            matrix.set(x, y, dark);
+        }
+    }
+    Some(matrix)
+}
+
+/// Encode `data` in ASCII mode, compressing pairs of digits into a single codeword.
+fn ascii_codewords(data: &[u8]) -> Vec<u8> {
+    let mut codewords = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        match (data[i], data.get(i + 1)) {
+            (a, Some(b)) if a.is_ascii_digit() && b.is_ascii_digit() => {
+                codewords.push(130 + (a - b'0') * 10 + (b - b'0'));
+                i += 1;
+            }
+            (a, _) if a < 128 => codewords.push(a + 1),
+            (a, _) => codewords.extend_from_slice(&[UPPER_SHIFT, a - 127]),
+        }
+        i += 1;
+    }
+    codewords
+}
+
+/// Pad `codewords` to the symbol capacity. All but the first pad codeword are randomized
+/// depending on their position.
+fn pad(codewords: &mut Vec<u8>, data_len: usize) {
+    if codewords.len() < data_len {
+        codewords.push(PAD);
+    }
+    while codewords.len() < data_len {
+        let position = codewords.len() + 1;
+        let value = PAD as usize + (149 * position) % 253 + 1;
+        codewords.push(if value > 254 { value - 254 } else { value } as u8);
+    }
+}
+
+/// Append the error correction codewords. The data codewords are distributed over the blocks
+/// in turns, and so are the error correction codewords of each block.
+fn add_ecc(codewords: &mut Vec<u8>, ecc_len: usize, blocks: usize) {
+    let data_len = codewords.len();
+    let reed_solomon = ReedSolomon::new(0x2D, 1, ecc_len / blocks);
+    codewords.resize(data_len + ecc_len, 0);
+    for block in 0..blocks {
+        let data: Vec<u8> = codewords[..data_len]
+            .iter()
+            .skip(block)
+            .step_by(blocks)
+            .copied()
+            .collect();
+        for (i, ecc) in reed_solomon.remainder(&data).into_iter().enumerate() {
+            codewords[data_len + i * blocks + block] = ecc;
+        }
+    }
+}
+
+/// Places codewords in the mapping matrix in the diagonal pattern defined by the standard.
+struct Placement {
+    size: usize,
+    modules: Vec<Option<bool>>,
+}
+
+impl Placement {
+    fn new(size: usize) -> Placement {
+        Placement {
+            size,
+            modules: vec![None; size * size],
+        }
+    }
+
+    fn get(&self, row: usize, column: usize) -> bool {
+        self.modules[row * self.size + column].unwrap_or(false)
+    }
+
+    fn is_placed(&self, row: i32, column: i32) -> bool {
+        self.modules[row as usize * self.size + column as usize].is_some()
+    }
+
+    fn place(&mut self, codewords: &[u8]) {
+        let size = self.size as i32;
+        let mut codewords = codewords.iter().copied();
+        let (mut row, mut column) = (4, 0);
+        loop {
+            // The corner cases, which only occur for some sizes
+            if row == size && column == 0 {
+                self.corner(&mut codewords, &CORNER_1);
+            }
+            if row == size - 2 && column == 0 && size % 4 != 0 {
+                self.corner(&mut codewords, &CORNER_2);
+            }
+            if row == size - 2 && column == 0 && size % 8 == 4 {
+                self.corner(&mut codewords, &CORNER_3);
+            }
+            if row == size + 4 && column == 2 && size % 8 == 0 {
+                self.corner(&mut codewords, &CORNER_4);
+            }
+
+            // Sweep upward diagonally
+            loop {
+                if row < size && column >= 0 && !self.is_placed(row, column) {
+                    self.utah(row, column, codewords.next().unwrap_or(0));
+                }
+                row -= 2;
+                column += 2;
+                if row < 0 || column >= size {
+                    break;
+                }
+            }
+            row += 1;
+            column += 3;
+
+            // Sweep downward diagonally
+            loop {
+                if row >= 0 && column < size && !self.is_placed(row, column) {
+                    self.utah(row, column, codewords.next().unwrap_or(0));
+                }
+                row += 2;
+                column -= 2;
+                if row >= size || column < 0 {
+                    break;
+                }
+            }
+            row += 3;
+            column += 1;
+
+            if row >= size && column >= size {
+                break;
+            }
+        }
+
+        // Fixed pattern in the bottom right corner if it is left unfilled
+        let last = self.size - 1;
+        if self.modules[last * self.size + last].is_none() {
+            for (row, column, dark) in [(0, 0, true), (0, 1, false), (1, 0, false), (1, 1, true)] {
+                self.modules[(last - row) * self.size + last - column] = Some(dark);
+            }
+        }
+    }
+
+    /// Place a single bit of a codeword, wrapping around the edges of the matrix.
+    fn module(&mut self, mut row: i32, mut column: i32, codeword: u8, bit: u8) {
+        let size = self.size as i32;
+        if row < 0 {
+            row += size;
+            column += 4 - ((size + 4) % 8);
+        }
+        if column < 0 {
+            column += size;
+            row += 4 - ((size + 4) % 8);
+        }
+        let dark = (codeword >> (8 - bit)) & 1 != 0;
+        self.modules[row as usize * self.size + column as usize] = Some(dark);
+    }
+
+    /// Place a codeword in the standard shape, with its last bit at (`row`, `column`).
+    fn utah(&mut self, row: i32, column: i32, codeword: u8) {
+        let shape = [
+            (-2, -2),
+            (-2, -1),
+            (-1, -2),
+            (-1, -1),
+            (-1, 0),
+            (0, -2),
+            (0, -1),
+            (0, 0),
+        ];
+        for (bit, (dr, dc)) in shape.iter().enumerate() {
+            self.module(row + dr, column + dc, codeword, bit as u8 + 1);
+        }
+    }
+
+    /// Place the next codeword in one of the special corner shapes.
+    fn corner(&mut self, codewords: &mut impl Iterator<Item = u8>, shape: &[(Anchor, Anchor); 8]) {
+        let codeword = codewords.next().unwrap_or(0);
+        let size = self.size as i32;
+        for (bit, (row, column)) in shape.iter().enumerate() {
+            self.module(
+                row.resolve(size),
+                column.resolve(size),
+                codeword,
+                bit as u8 + 1,
+            );
+        }
+    }
+}
+
+/// A coordinate of a corner shape, relative to the start or the end of an axis.
+#[derive(Clone, Copy)]
+enum Anchor {
+    Start(i32),
+    End(i32),
+}
+
+impl Anchor {
+    fn resolve(self, size: i32) -> i32 {
+        match self {
+            Anchor::Start(offset) => offset,
+            Anchor::End(offset) => size - offset,
+        }
+    }
+}
+
+const CORNER_1: [(Anchor, Anchor); 8] = [
+    (End(1), Start(0)),
+    (End(1), Start(1)),
+    (End(1), Start(2)),
+    (Start(0), End(2)),
+    (Start(0), End(1)),
+    (Start(1), End(1)),
+    (Start(2), End(1)),
+    (Start(3), End(1)),
+];
+
+const CORNER_2: [(Anchor, Anchor); 8] = [
+    (End(3), Start(0)),
+    (End(2), Start(0)),
+    (End(1), Start(0)),
+    (Start(0), End(4)),
+    (Start(0), End(3)),
+    (Start(0), End(2)),
+    (Start(0), End(1)),
+    (Start(1), End(1)),
+];
+
+const CORNER_3: [(Anchor, Anchor); 8] = [
+    (End(3), Start(0)),
+    (End(2), Start(0)),
+    (End(1), Start(0)),
+    (Start(0), End(2)),
+    (Start(0), End(1)),
+    (Start(1), End(1)),
+    (Start(2), End(1)),
+    (Start(3), End(1)),
+];
+
+const CORNER_4: [(Anchor, Anchor); 8] = [
+    (End(1), Start(0)),
+    (End(1), End(1)),
+    (Start(0), End(3)),
+    (Start(0), End(2)),
+    (Start(0), End(1)),
+    (Start(1), End(3)),
+    (Start(1), End(2)),
+    (Start(1), End(1)),
+];
@@ -0,0 +1,179 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Decoding of the first frame of GIF files into gray levels.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use weezl::decode::Decoder;
+use weezl::BitOrder;
+
+use crate::image::luma;
+use crate::{GrayImage, ImageError};
+
+/// Introduces an extension block
+const EXTENSION: u8 = 0x21;
+/// Introduces an image descriptor
+const IMAGE: u8 = 0x2C;
+/// The label of the graphic control extension, which selects the transparent color
+const GRAPHIC_CONTROL: u8 = 0xF9;
+/// Rows of interlaced images are stored in passes as (first row, row step)
+const INTERLACE_PASSES: [(usize, usize); 4] = [(0, 8), (4, 8), (2, 4), (1, 2)];
+
+impl GrayImage {
+    /// Decode the first frame of a GIF file. Available with the `gif` feature.
+    ///
+    /// The image is the frame, cropped to the logical screen on which it would be shown.
+    /// Transparent pixels are left white.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let logo = GrayImage::from_gif(include_bytes!("../resources/logo.gif")).unwrap();
+    /// printer.print_bitmap(&logo, RasterBitImageMode::Normal, BitmapOptions::default())?;
+    /// ```
+    pub fn from_gif(bytes: &[u8]) -> Result<GrayImage, ImageError> {
+        if !matches!(bytes.get(0..6), Some(b"GIF87a" | b"GIF89a")) {
+            return Err(ImageError::Malformed);
+        }
+        let screen_width = usize::from(read_u16(bytes, 6)?);
+        let screen_height = usize::from(read_u16(bytes, 8)?);
+        let flags = *bytes.get(10).ok_or(ImageError::Malformed)?;
+        let mut position = 13;
+        let global_colors = color_table(bytes, &mut position, flags)?;
+
+        let mut transparent = None;
+        loop {
+            match bytes.get(position) {
+                Some(&EXTENSION) => {
+                    let label = *bytes.get(position + 1).ok_or(ImageError::Malformed)?;
+                    position += 2;
+                    let data = sub_blocks(bytes, &mut position)?;
+                    if label == GRAPHIC_CONTROL {
+                        // The transparent color index is valid if the lowest flag is set
+                        transparent = match data.as_slice() {
+                            [flags, _, _, index, ..] if flags & 1 != 0 => Some(*index),
+                            _ => None,
+                        };
+                    }
+                }
+                Some(&IMAGE) => break,
+                // Including the trailer, which would end a file without images
+                _ => return Err(ImageError::Malformed),
+            }
+        }
+
+        let frame = bytes
+            .get(position + 1..position + 10)
+            .ok_or(ImageError::Malformed)?;
+        let left = usize::from(u16::from_le_bytes([frame[0], frame[1]]));
+        let top = usize::from(u16::from_le_bytes([frame[2], frame[3]]));
+        let frame_width = usize::from(u16::from_le_bytes([frame[4], frame[5]]));
+        let frame_height = usize::from(u16::from_le_bytes([frame[6], frame[7]]));
+        let frame_flags = frame[8];
+        position += 10;
+        let colors = match color_table(bytes, &mut position, frame_flags)? {
+            Some(colors) => colors,
+            None => global_colors.ok_or(ImageError::Malformed)?,
+        };
+
+        let code_size = *bytes.get(position).ok_or(ImageError::Malformed)?;
+        if !(1..=11).contains(&code_size) {
+            return Err(ImageError::Malformed);
+        }
+        position += 1;
+        let compressed = sub_blocks(bytes, &mut position)?;
+        let indices = Decoder::new(BitOrder::Lsb, code_size)
+            .decode(&compressed)
+            .map_err(|_| ImageError::Malformed)?;
+        if indices.len() < frame_width * frame_height {
+            return Err(ImageError::Malformed);
+        }
+
+        // Only the part of the frame on the screen is kept, so the image is never larger than the
+        // decoded data
+        let width = frame_width.min(screen_width.saturating_sub(left));
+        let height = frame_height.min(screen_height.saturating_sub(top));
+        let mut gray = vec![u8::MAX; width * height];
+        let rows: Vec<usize> = if frame_flags & 0x40 != 0 {
+            INTERLACE_PASSES
+                .iter()
+                .flat_map(|&(start, step)| (start..frame_height).step_by(step))
+                .collect()
+        } else {
+            (0..frame_height).collect()
+        };
+        for (row, y) in indices.chunks(frame_width.max(1)).zip(rows) {
+            for (x, &index) in row.iter().enumerate() {
+                // Pixels outside of the logical screen are not shown
+                if x >= width || y >= height || Some(index) == transparent {
+                    continue;
+                }
+                let index = usize::from(index);
+                let color = colors
+                    .get(index * 3..index * 3 + 3)
+                    .ok_or(ImageError::Malformed)?;
+                gray[y * width + x] = luma(
+                    u32::from(color[0]),
+                    u32::from(color[1]),
+                    u32::from(color[2]),
+                ) as u8;
+            }
+        }
+        GrayImage::from_luminance(width, height, gray)
+    }
+}
+
+/// Read the color table at `position` if `flags` indicate one, and move past it.
+fn color_table<'a>(
+    bytes: &'a [u8],
+    position: &mut usize,
+    flags: u8,
+) -> Result<Option<&'a [u8]>, ImageError> {
+    if flags & 0x80 == 0 {
+        return Ok(None);
+    }
+    // The lowest bits give the number of entries, 3 bytes each
+    let len = 3 << ((flags & 0x07) + 1);
+    let table = bytes
+        .get(*position..*position + len)
+        .ok_or(ImageError::Malformed)?;
+    *position += len;
+    Ok(Some(table))
+}
+
+/// Concatenate the data sub-blocks at `position`, and move past the terminating empty block.
+fn sub_blocks(bytes: &[u8], position: &mut usize) -> Result<Vec<u8>, ImageError> {
+    let mut data = Vec::new();
+    loop {
+        let len = usize::from(*bytes.get(*position).ok_or(ImageError::Malformed)?);
+        *position += 1;
+        if len == 0 {
+            return Ok(data);
+        }
+        data.extend_from_slice(
+            bytes
+                .get(*position..*position + len)
+                .ok_or(ImageError::Malformed)?,
+        );
+        *position += len;
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, ImageError> {
+    match bytes.get(offset..offset + 2) {
+        Some(&[low, high]) => Ok(u16::from_le_bytes([low, high])),
+        _ => Err(ImageError::Malformed),
+    }
+}
@@ -0,0 +1,107 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Rendering of characters which no code table contains as raster images, using the bitmap font
+//! converted by the build script.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use bitvec::prelude::*;
+
+use crate::{Head, RasterBitImageMode, DOT_WIDTH};
+
+include!(concat!(env!("OUT_DIR"), "/glyphs.rs"));
+
+/// Whether the font contains a glyph for `c`.
+pub(crate) fn has_glyph(c: char) -> bool {
+    glyph(c).is_some()
+}
+
+/// The width in dots and the offset into [`GLYPH_DATA`] of the glyph of `c`.
+fn glyph(c: char) -> Option<(usize, usize)> {
+    GLYPH_INDEX
+        .binary_search_by_key(&c, |&(character, _, _)| character)
+        .ok()
+        .map(|i| (GLYPH_INDEX[i].1 as usize, GLYPH_INDEX[i].2 as usize))
+}
+
+/// Render `chars` as raster bit images, one per line of paper, for the print mode of `head`:
+/// each line is as tall as a line of text in the current font, and double-width and
+/// double-height printing are applied via the returned [`RasterBitImageMode`]. Spaces and
+/// characters without a glyph are left blank.
+///
+/// Returns the raster mode and the images together with their number of bytes per row.
+pub(crate) fn render_glyphs(
+    chars: &[char],
+    head: &Head,
+) -> (RasterBitImageMode, Vec<(Vec<u8>, usize)>) {
+    let double_width = head.print_mode & (1 << 5) != 0;
+    let double_height = head.print_mode & (1 << 4) != 0;
+    let mode = match (double_width, double_height) {
+        (false, false) => RasterBitImageMode::Normal,
+        (true, false) => RasterBitImageMode::DoubleWidth,
+        (false, true) => RasterBitImageMode::DoubleHeight,
+        (true, true) => RasterBitImageMode::Quadruple,
+    };
+    let max_width = DOT_WIDTH as usize / if double_width { 2 } else { 1 };
+    let char_height = head.char_height as usize / if double_height { 2 } else { 1 };
+    let height = char_height.max(GLYPH_HEIGHT);
+    let top = (height - GLYPH_HEIGHT) / 2;
+
+    // Break the characters into lines which fit onto the paper
+    let mut lines: Vec<Vec<(usize, Option<usize>)>> = vec![Vec::new()];
+    let mut line_width = 0;
+    for &c in chars {
+        let (width, offset) = match glyph(c) {
+            Some((width, offset)) => (width, Some(offset)),
+            None => (SPACE_WIDTH, None),
+        };
+        if line_width + width > max_width {
+            lines.push(Vec::new());
+            line_width = 0;
+        }
+        line_width += width;
+        if let Some(line) = lines.last_mut() {
+            line.push((width, offset));
+        }
+    }
+
+    let images = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let width: usize = line.iter().map(|(width, _)| width).sum();
+            let x_bytes = width.div_ceil(8);
+            let mut image_bits = bitvec![u8, Msb0; 0; x_bytes * 8 * height];
+            let mut x = 0;
+            for &(glyph_width, offset) in line {
+                if let Some(offset) = offset {
+                    let row_bytes = glyph_width.div_ceil(8);
+                    for y in 0..GLYPH_HEIGHT {
+                        let row = &GLYPH_DATA[offset + y * row_bytes..][..row_bytes];
+                        for (i, bit) in row.view_bits::<Msb0>()[..glyph_width].iter().enumerate() {
+                            if *bit {
+                                image_bits.set((top + y) * x_bytes * 8 + x + i, true);
+                            }
+                        }
+                    }
+                }
+                x += glyph_width;
+            }
+            (image_bits.into_vec(), x_bytes)
+        })
+        .collect();
+    (mode, images)
+}
@@ -0,0 +1,110 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Images decoded into gray levels, which are printed like bitmaps.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use crate::Bitmap;
+
+/// Describes why an image file cannot be decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ImageError {
+    /// The file is truncated, corrupted or its headers are inconsistent.
+    Malformed,
+    /// The file uses a feature the decoder does not support, such as the given color format.
+    Unsupported(&'static str),
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Malformed => write!(f, "malformed image file"),
+            ImageError::Unsupported(feature) => write!(f, "unsupported image format: {}", feature),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ImageError {}
+
+/// An image stored as the luminance of its pixels, which can be printed via
+/// [`Printer::print_bitmap`](crate::Printer::print_bitmap) by passing a reference to it.
+///
+/// Decoded from PNG, PBM or GIF files with the `png`, `pbm` and `gif` features, or created from
+/// gray levels computed at runtime via [`GrayImage::from_luminance`]. It is converted into dots
+/// with the same [`BitmapOptions`](crate::BitmapOptions) as BMP files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrayImage {
+    width: usize,
+    height: usize,
+    /// The luminance of each pixel, row by row from the top
+    luminance: Vec<u8>,
+}
+
+impl GrayImage {
+    /// Create an image from the luminance of each pixel, row by row from the top, from 0 (black)
+    /// to 255 (white).
+    ///
+    /// Returns [`ImageError::Malformed`] if `luminance` does not contain `width * height` values.
+    pub fn from_luminance(
+        width: usize,
+        height: usize,
+        luminance: Vec<u8>,
+    ) -> Result<GrayImage, ImageError> {
+        if width.checked_mul(height) != Some(luminance.len()) {
+            return Err(ImageError::Malformed);
+        }
+        Ok(GrayImage {
+            width,
+            height,
+            luminance,
+        })
+    }
+
+    /// The width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The luminance of each pixel, row by row from the top, from 0 (black) to 255 (white).
+    pub fn luminance(&self) -> &[u8] {
+        &self.luminance
+    }
+}
+
+impl<'a> From<&'a GrayImage> for Bitmap<'a> {
+    fn from(image: &'a GrayImage) -> Self {
+        Bitmap::from_luminance(image.width, image.height, &image.luminance)
+    }
+}
+
+/// The luminance of a color according to ITU-R BT.601.
+pub(crate) fn luma(red: u32, green: u32, blue: u32) -> u32 {
+    (299 * red + 587 * green + 114 * blue) / 1000
+}
+
+/// The luminance of a pixel with the given luminance and opacity (both 0..=255) on white paper.
+pub(crate) fn on_paper(luma: u32, alpha: u32) -> u8 {
+    const WHITE: u32 = u8::MAX as u32;
+    ((luma * alpha + WHITE * (WHITE - alpha)) / WHITE) as u8
+}
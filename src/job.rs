@@ -0,0 +1,467 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The data sent to the printer for each operation, and the time the printer needs to process
+//! it. Shared by the [`Printer`](crate::Printer) and the `AsyncPrinter`, which only send it.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::bitmap::bitmap_raster;
+use crate::codepage::TextRun;
+#[cfg(feature = "glyphs")]
+use crate::glyph::render_glyphs;
+use crate::nv_image::{
+    erase_all_command, erase_command, print_command, store_commands, NV_WRITE_MICROS,
+};
+use crate::qr::{native_qr_command, native_qr_dots};
+use crate::status::STATUS_CHECK_ROWS;
+use crate::symbol::encode_symbol;
+use crate::user_char::{cancel_user_character_command, define_user_characters_command};
+use crate::{
+    append_raster_row, barcode_header, hri_font, print_mode_command, raster_band_rows,
+    raster_header, raster_row_bytes, BarCodeSystem, BarcodeStyle, BarcodeWidth, Bitmap,
+    BitmapOptions, CharacterSet, CodeTable, Font, Head, HriPosition, Justification,
+    NvImageCommands, PrintMode, PrintSettings, PrinterError, QrOptions, QrRendering,
+    RasterBitImageMode, SymbolOptions, Underline, UserGlyph, ESC, FLOW_CONTROL_SEQUENCE, GS,
+    INIT_SEQUENCE,
+};
+
+/// A single step of a [`Job`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Step {
+    /// Send the bytes, then wait the given number of microseconds for the printer to receive and
+    /// output them. The wait is skipped if a busy pin is connected.
+    Send(Vec<u8>, u64),
+    /// Wait the given number of microseconds, even if a busy pin is connected.
+    Sleep(u64),
+    /// A point before a line of text or a band of an image, at which the printer status can be
+    /// checked.
+    Checkpoint,
+}
+
+/// The steps of an operation of the printer.
+pub(crate) type Job = Vec<Step>;
+
+/// Collects the rows of a raster image into bands which fit into the receive buffer of the
+/// printer, each of which is sent in a single `GS v 0` command.
+pub(crate) struct RasterBands {
+    width: usize,
+    x_bytes: usize,
+    band_rows: usize,
+    mode: RasterBitImageMode,
+    band: Vec<u8>,
+}
+
+impl RasterBands {
+    /// Start an image `width` dots wide, printed in `mode`. Returns
+    /// [`PrinterError::InvalidArgument`] if it is wider than the paper.
+    pub(crate) fn new<E>(
+        head: &Head,
+        width: usize,
+        mode: RasterBitImageMode,
+    ) -> Result<RasterBands, PrinterError<E>> {
+        let x_bytes = raster_row_bytes(width)?;
+        let band_rows = if head.status_checks {
+            // Status queries cannot be sent while the printer receives image data, so split the
+            // image into smaller bands if we need to check in between
+            raster_band_rows(x_bytes).min(STATUS_CHECK_ROWS as usize)
+        } else {
+            raster_band_rows(x_bytes)
+        };
+        Ok(RasterBands {
+            width,
+            x_bytes,
+            band_rows,
+            mode,
+            band: Vec::with_capacity(x_bytes * band_rows),
+        })
+    }
+
+    /// Add the next `row` of the image, returning the job sending the band once it is full.
+    /// Returns [`PrinterError::InvalidArgument`] if the row does not match the width.
+    pub(crate) fn push<E>(
+        &mut self,
+        head: &Head,
+        row: &[u8],
+    ) -> Result<Option<Job>, PrinterError<E>> {
+        append_raster_row(&mut self.band, row, self.width)?;
+        if self.x_bytes == 0 || self.band.len() < self.x_bytes * self.band_rows {
+            return Ok(None);
+        }
+        let job = self.band_job(head);
+        self.band.clear();
+        Ok(Some(job))
+    }
+
+    /// The job sending the rows added since the last full band, if any.
+    pub(crate) fn finish(self, head: &Head) -> Option<Job> {
+        if self.band.is_empty() {
+            None
+        } else {
+            Some(self.band_job(head))
+        }
+    }
+
+    /// Check the status before each band, as the printer cannot answer while it receives the
+    /// image data, then send the band and wait for each of its rows to be printed.
+    fn band_job(&self, head: &Head) -> Job {
+        let rows = self.band.len() / self.x_bytes;
+        let mut bytes = raster_header(self.mode, self.x_bytes as u16, rows as u16).to_vec();
+        bytes.extend_from_slice(&self.band);
+        let pause = bytes.len() as u64 * head.byte_time + head.image_time(rows);
+        vec![Step::Checkpoint, Step::Send(bytes, pause)]
+    }
+}
+
+impl Head {
+    /// The step sending a control command, which produces no physical output.
+    fn command(&self, bytes: &[u8]) -> Step {
+        Step::Send(bytes.to_vec(), bytes.len() as u64 * self.byte_time)
+    }
+
+    /// The steps sending printable `bytes` line by line, keeping track of the physical position
+    /// of the print head. Each line is preceded by a [`Step::Checkpoint`], including the first one
+    /// and those which are wrapped by the printer, so that no line is sent to a faulty printer.
+    fn output(&mut self, bytes: &[u8]) -> Job {
+        let mut job = Vec::new();
+        let mut start = 0;
+        let mut pause = 0;
+        for (i, byte) in bytes.iter().enumerate() {
+            // The column is reset at the end of each line
+            if self.current_column == 0 {
+                if start < i {
+                    job.push(Step::Send(bytes[start..i].to_vec(), pause));
+                    start = i;
+                    pause = 0;
+                }
+                job.push(Step::Checkpoint);
+            }
+            pause += self.advance(*byte);
+        }
+        if start < bytes.len() {
+            job.push(Step::Send(bytes[start..].to_vec(), pause));
+        }
+        job
+    }
+
+    /// Transcode `text` into the selected [`CodeTable`], switching code tables as needed and
+    /// restoring the selected one afterwards.
+    pub(crate) fn write_text<E>(&mut self, text: &str) -> Result<Job, PrinterError<E>> {
+        let runs = self
+            .encoder
+            .encode(text)
+            .map_err(PrinterError::UnsupportedCharacter)?;
+        let selected = self.encoder.code_table;
+        let mut active = selected;
+        let mut job = Vec::new();
+        for run in runs {
+            match run {
+                TextRun::Text(code_table, bytes) => {
+                    if code_table != active {
+                        job.push(self.command(&[ESC, 0x74, code_table.into()]));
+                        active = code_table;
+                    }
+                    job.extend(self.output(&bytes));
+                }
+                #[cfg(feature = "glyphs")]
+                TextRun::Glyphs(chars) => job.extend(self.print_glyphs(&chars)?),
+            }
+        }
+        if active != selected {
+            job.push(self.command(&[ESC, 0x74, selected.into()]));
+        }
+        Ok(job)
+    }
+
+    /// Print `chars` as raster images, starting on a new line.
+    #[cfg(feature = "glyphs")]
+    fn print_glyphs<E>(&mut self, chars: &[char]) -> Result<Job, PrinterError<E>> {
+        let mut job = Vec::new();
+        if self.current_column != 0 {
+            job.extend(self.output(b"\n"));
+        }
+        let (mode, images) = render_glyphs(chars, self);
+        for (image, x_bytes) in images {
+            job.extend(self.print_raster(x_bytes * 8, image.len() / x_bytes, &image, mode)?);
+        }
+        Ok(job)
+    }
+
+    /// Send the initialization sequence and configure tab stops, and flow control if
+    /// `flow_control` is set.
+    pub(crate) fn reset(&mut self, flow_control: bool) -> Job {
+        let mut job = vec![self.command(&INIT_SEQUENCE)];
+        self.justification = Justification::Left;
+        if flow_control {
+            job.push(self.command(&FLOW_CONTROL_SEQUENCE));
+        }
+        job.push(self.command(&self.tab_stops()));
+        job.extend(self.set_print_settings(PrintSettings::default()));
+        job.extend(self.set_character_set(CharacterSet::default()));
+        job.extend(self.set_code_table(CodeTable::default()));
+        job.extend(self.set_barcode_style(BarcodeStyle::default()));
+        job
+    }
+
+    /// Wake the device from sleep. According to the datasheet the printer needs at least 50ms in
+    /// order to be ready to receive commands, so wait for 75ms.
+    pub(crate) fn wake(&self) -> Job {
+        vec![self.command(&[ESC, 0x38, 0x00, 0x00]), Step::Sleep(75_000)]
+    }
+
+    /// Wait 500ms for the printer to boot, then wake it up, disable sleep, reset it and feed a
+    /// line.
+    pub(crate) fn init(&mut self, flow_control: bool) -> Job {
+        let mut job = vec![Step::Sleep(500_000)];
+        job.extend(self.wake());
+        job.push(self.command(&[ESC, 0x38, 0x00, 0x00]));
+        job.extend(self.reset(flow_control));
+        job.extend(self.feed_n(1));
+        job
+    }
+
+    pub(crate) fn set_print_mode(&mut self, print_mode: PrintMode) -> Job {
+        self.adjust_char_values(print_mode);
+        vec![self.command(&print_mode_command(print_mode))]
+    }
+
+    pub(crate) fn set_print_settings(&mut self, print_settings: PrintSettings) -> Job {
+        let settings_bytes: [u8; 3] = print_settings.into();
+        self.apply_print_settings(print_settings);
+        vec![self.command(&[ESC, 0x37]), self.command(&settings_bytes)]
+    }
+
+    pub(crate) fn set_justification(&mut self, justification: Justification) -> Job {
+        self.justification = justification;
+        vec![self.command(&[ESC, 0x61, justification.into()])]
+    }
+
+    pub(crate) fn set_underline(&self, mode: Underline) -> Job {
+        vec![self.command(&[ESC, 0x2D, mode.into()])]
+    }
+
+    pub(crate) fn set_character_set(&self, character_set: CharacterSet) -> Job {
+        vec![self.command(&[ESC, 0x52, character_set.into()])]
+    }
+
+    pub(crate) fn set_code_table(&mut self, code_table: CodeTable) -> Job {
+        self.encoder.code_table = code_table;
+        vec![self.command(&[ESC, 0x74, code_table.into()])]
+    }
+
+    pub(crate) fn define_user_characters<E>(
+        &self,
+        first: u8,
+        glyphs: &[UserGlyph],
+    ) -> Result<Job, PrinterError<E>> {
+        let command = define_user_characters_command(first, glyphs, self.font())?;
+        Ok(vec![self.command(&command)])
+    }
+
+    pub(crate) fn set_user_characters(&self, enabled: bool) -> Job {
+        vec![self.command(&[ESC, 0x25, enabled.into()])]
+    }
+
+    pub(crate) fn cancel_user_character<E>(&self, code: u8) -> Result<Job, PrinterError<E>> {
+        Ok(vec![self.command(&cancel_user_character_command(code)?)])
+    }
+
+    pub(crate) fn print_bitmap<E>(
+        &mut self,
+        bmp: Bitmap<'_>,
+        mode: RasterBitImageMode,
+        options: BitmapOptions,
+    ) -> Result<Job, PrinterError<E>> {
+        let (image, x_bytes, rows) = bitmap_raster(bmp, options)?;
+        self.print_raster(x_bytes * 8, rows, &image, mode)
+    }
+
+    pub(crate) fn store_nv_images<'a, E>(
+        &mut self,
+        images: &[impl Into<Bitmap<'a>> + Copy],
+    ) -> Result<Job, PrinterError<E>> {
+        let images = images
+            .iter()
+            .map(|bmp| {
+                bitmap_raster((*bmp).into(), BitmapOptions::default())
+                    .map(|(image, x_bytes, _)| (image, x_bytes))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let commands = store_commands(self.nv_commands, &images)?;
+        let mut job = Vec::new();
+        if let NvImageCommands::Graphics = self.nv_commands {
+            // Unlike FS q, defining NV graphics keeps the other images
+            job.extend(self.erase_nv_images());
+        }
+        for command in commands {
+            let pause = command.len() as u64 * self.byte_time + NV_WRITE_MICROS;
+            job.push(Step::Send(command, pause));
+        }
+        self.nv_image_rows = images
+            .iter()
+            .map(|(image, x_bytes)| image.len() / *x_bytes)
+            .collect();
+        Ok(job)
+    }
+
+    pub(crate) fn erase_nv_images(&mut self) -> Job {
+        let command = erase_all_command(self.nv_commands);
+        let pause = command.len() as u64 * self.byte_time + NV_WRITE_MICROS;
+        self.nv_image_rows.clear();
+        vec![Step::Send(command, pause)]
+    }
+
+    pub(crate) fn erase_nv_image<E>(&self, index: u8) -> Result<Job, PrinterError<E>> {
+        let command = erase_command(self.nv_commands, index)?;
+        let pause = command.len() as u64 * self.byte_time + NV_WRITE_MICROS;
+        Ok(vec![Step::Send(command.to_vec(), pause)])
+    }
+
+    pub(crate) fn print_nv_image<E>(
+        &self,
+        index: u8,
+        mode: RasterBitImageMode,
+    ) -> Result<Job, PrinterError<E>> {
+        let command = print_command(self.nv_commands, index, mode)?;
+        let rows = self.nv_image_rows(index, mode);
+        let pause = command.len() as u64 * self.byte_time + self.image_time(rows);
+        Ok(vec![Step::Send(command, pause)])
+    }
+
+    /// Print a raster image whose `height` rows are packed into `data`, in bands.
+    pub(crate) fn print_raster<E>(
+        &self,
+        width: usize,
+        height: usize,
+        data: &[u8],
+        mode: RasterBitImageMode,
+    ) -> Result<Job, PrinterError<E>> {
+        let x_bytes = raster_row_bytes(width)?;
+        if data.len() != x_bytes * height {
+            return Err(PrinterError::InvalidArgument(
+                "raster data does not match the image size",
+            ));
+        }
+        let mut bands = RasterBands::new(self, width, mode)?;
+        let mut job = Vec::new();
+        for row in data.chunks(x_bytes.max(1)) {
+            job.extend(bands.push(self, row)?.into_iter().flatten());
+        }
+        job.extend(bands.finish(self).into_iter().flatten());
+        Ok(job)
+    }
+
+    /// Print a QR code, encoded either by the printer or the driver, restoring the
+    /// justification afterwards.
+    pub(crate) fn print_qr<E>(
+        &mut self,
+        data: &[u8],
+        options: QrOptions,
+    ) -> Result<Job, PrinterError<E>> {
+        // Also checks the module size, which the printer is limited to as well
+        let symbol_options = options.symbol_options()?;
+        if options.rendering == QrRendering::Raster {
+            return self.print_symbol(data, symbol_options);
+        }
+
+        let dots = native_qr_dots(data, &options)
+            .ok_or(PrinterError::InvalidArgument("symbol data too long"))?;
+        let justification = self.justification;
+        let mut job = self.set_justification(options.justification);
+        let command = native_qr_command(data, &options);
+        let pause = command.len() as u64 * self.byte_time + self.image_time(dots);
+        job.push(Step::Send(command, pause));
+        job.extend(self.set_justification(justification));
+        Ok(job)
+    }
+
+    /// Print a two-dimensional symbol as a raster image, restoring the justification afterwards.
+    pub(crate) fn print_symbol<E>(
+        &mut self,
+        data: &[u8],
+        options: SymbolOptions,
+    ) -> Result<Job, PrinterError<E>> {
+        let (matrix, quiet_zone) = encode_symbol(data, &options)?;
+        let (image, x_bytes) = matrix.raster(options.scale, quiet_zone);
+        let justification = self.justification;
+        let mut job = self.set_justification(options.justification);
+        let rows = image.len() / x_bytes;
+        job.extend(self.print_raster(x_bytes * 8, rows, &image, RasterBitImageMode::Normal)?);
+        job.extend(self.set_justification(justification));
+        Ok(job)
+    }
+
+    pub(crate) fn print_barcode<E>(
+        &self,
+        system: BarCodeSystem,
+        text: &str,
+    ) -> Result<Job, PrinterError<E>> {
+        let payload = system.payload(text)?;
+        let mut command = barcode_header(system, payload.len() as u8).to_vec();
+        command.extend_from_slice(&payload);
+        let pause = command.len() as u64 * self.byte_time + self.barcode_time();
+        Ok(vec![Step::Send(command, pause)])
+    }
+
+    pub(crate) fn set_barcode_height(&mut self, height: u8) -> Job {
+        self.barcode_height = height;
+        vec![self.command(&[GS, 0x68, height])]
+    }
+
+    pub(crate) fn set_barcode_left_space(&self, space: u8) -> Job {
+        vec![self.command(&[GS, 0x78, space])]
+    }
+
+    pub(crate) fn set_barcode_width(&self, width: BarcodeWidth) -> Job {
+        vec![self.command(&[GS, 0x77, width.into()])]
+    }
+
+    pub(crate) fn set_barcode_text_position(&mut self, position: HriPosition) -> Job {
+        self.barcode_text_position = position;
+        vec![self.command(&[GS, 0x48, position.into()])]
+    }
+
+    pub(crate) fn set_barcode_text_font(&mut self, font: Font) -> Job {
+        self.barcode_text_font = font;
+        vec![self.command(&[GS, 0x66, hri_font(font)])]
+    }
+
+    pub(crate) fn set_barcode_style(&mut self, style: BarcodeStyle) -> Job {
+        let mut job = self.set_barcode_height(style.height);
+        job.extend(self.set_barcode_width(style.width));
+        job.extend(self.set_barcode_left_space(style.left_space));
+        job.extend(self.set_barcode_text_position(style.text_position));
+        job.extend(self.set_barcode_text_font(style.text_font));
+        job
+    }
+
+    pub(crate) fn set_rotation_mode(&self, rotate: bool) -> Job {
+        vec![self.command(&[ESC, 0x56, rotate.into()])]
+    }
+
+    /// Feed the paper by `lines` lines of the current height and spacing via `ESC d`.
+    pub(crate) fn feed_n(&mut self, lines: u8) -> Job {
+        let dot_feed_time: u64 = self.dot_feed_time.into();
+        let char_height: u64 = self.char_height.into();
+        let line_spacing: u64 = self.line_spacing.into();
+        self.prev_byte = '\n';
+        self.current_column = 0;
+        let pause = lines as u64 * (char_height + line_spacing) * dot_feed_time;
+        vec![Step::Send(
+            vec![ESC, 0x64, lines],
+            3 * self.byte_time + pause,
+        )]
+    }
+}
@@ -12,12 +12,20 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
-//! Support for the CSN-A2 thermal printer via [`embedded_hal`]. This crate also supports bitmap printing
-//! via [`tinybmp`].
+//! Support for the CSN-A2 thermal printer via [`embedded_io`] and [`embedded_hal`]. This crate also
+//! supports printing BMP files, including those parsed via [`tinybmp`], and PNG, PBM and GIF files
+//! with the `png`, `pbm` and `gif` features.
 //!
 //! # Usage
 //! Create a new [`Printer`] on a serial port on your platform and write text via the implemented [`core::fmt::Write`] trait. You can use the [`write!`] and [`writeln!`] macros to accomplish this.
 //!
+//! The serial port must implement [`embedded_io::Write`], the delay [`embedded_hal::delay::DelayNs`].
+//! Ports and delays implementing the `embedded-hal` 0.2 traits can be wrapped in the adapters of
+//! the `compat` module, which is available via the `embedded-hal-02` feature.
+//!
+//! With the `async` feature, the [`AsyncPrinter`] offers the same functionality on top of
+//! [`embedded_io_async`] and [`embedded_hal_async`].
+//!
 //! See the [`Printer`] struct documentation for advanced capabilities, such as printing barcodes and
 //! bitmaps.
 
@@ -26,16 +34,66 @@
 #[cfg(not(feature = "std"))]
 extern crate alloc;
 
+#[cfg(feature = "async")]
+mod asynch;
+mod barcode;
+mod bitmap;
+mod bmp;
+#[cfg(feature = "embedded-graphics")]
+mod canvas;
+mod codepage;
+#[cfg(feature = "embedded-hal-02")]
+pub mod compat;
+mod datamatrix;
+#[cfg(feature = "gif")]
+mod gif;
+#[cfg(feature = "glyphs")]
+mod glyph;
+mod image;
+mod job;
+mod nv_image;
+#[cfg(feature = "pbm")]
+mod pbm;
+#[cfg(feature = "png")]
+mod png;
+mod qr;
+mod status;
+mod symbol;
+mod translit;
+mod user_char;
+
+#[cfg(feature = "async")]
+pub use asynch::AsyncPrinter;
+pub use barcode::{check_digit, BarcodeError, Code128};
+pub use bitmap::{BitmapOptions, BitmapOptionsBuilder, Dithering, Resampling, Rotation, Scaling};
+pub use bmp::{Bitmap, BmpError};
+#[cfg(feature = "embedded-graphics")]
+pub use canvas::PrinterCanvas;
+pub use codepage::TextFallback;
+pub use image::{GrayImage, ImageError};
+pub use nv_image::NvImageCommands;
+pub use qr::{QrErrorCorrection, QrModel, QrOptions, QrOptionsBuilder, QrRendering};
+pub use status::{NoStatusPolicy, PrinterStatus, StatusCheck, StatusPolicy};
+pub use symbol::{SymbolOptions, SymbolOptionsBuilder, Symbology};
+pub use user_char::UserGlyph;
+
 #[cfg(not(feature = "std"))]
-use alloc::format;
-use bitvec::prelude::*;
-use core::fmt::{Arguments, Error, Write};
+use alloc::{format, vec::Vec};
+use codepage::TextEncoder;
+use core::convert::Infallible;
+use core::fmt::{self, Arguments, Debug, Display, Error, Formatter, Write};
 use core::iter::zip;
 
 use derive_builder::Builder;
-use embedded_hal::{blocking::delay, serial};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, Error as _, InputPin};
+use embedded_io::{Read as SerialRead, ReadReady, Write as SerialWrite};
+use job::{Job, RasterBands, Step};
 use num_enum::IntoPrimitive;
-use tinybmp::RawBmp;
+use nv_image::{KeyList, KeyListProgress, ACK, NV_GRAPHICS_LIST_QUERY};
+use status::{
+    DEFAULT_RESPONSE_TIMEOUT_MICROS, PAPER_STATUS_QUERY, PRINTER_STATUS_QUERY, RESPONSE_POLL_MICROS,
+};
 
 const ESC: u8 = 0x1B; // Escape
 const HT: u8 = 0x09; // Horizontal tab
@@ -46,59 +104,63 @@ const GS: u8 = 0x1D;
 const INIT_SEQUENCE: [u8; 2] = [ESC, AT];
 const TAB_STOP_SEQUENCE: [u8; 2] = [ESC, b'D'];
 const MODE_SEQUENCE: [u8; 2] = [ESC, MARK];
+/// `GS a n` with bit 5 set: signal whether the printer is busy via the DTR line
+const FLOW_CONTROL_SEQUENCE: [u8; 3] = [GS, 0x61, 1 << 5];
 /// Modes: Inverse, Upside-Down, Underline
 const MODE_ORDER: [[u8; 2]; 3] = [[GS, 0x42], [ESC, 0x7B], [ESC, 0x45]];
 
 const TAB_WIDTH: u8 = 4;
-/// Determines a cutoff value each pixel in a [`RawBmp`] is compared against. Pixels below this
-/// value get printed as a dot, pixels above not.
+/// Determines a cutoff value each pixel in a [`RawBmp`](tinybmp::RawBmp) is compared against.
+/// Pixels below this value get printed as a dot, pixels above not.
+#[deprecated(note = "bitmaps are converted by luminance, see `BitmapOptions`")]
 pub const PIXEL_COLOR_CUTOFF: u32 = 0x0000FFFF;
-const BAUDRATE: u64 = 19_200;
+const DEFAULT_BAUDRATE: u32 = 19_200;
+/// Time estimate for the printer to feed the paper by a single row of dots
+const DEFAULT_DOT_FEED_MICROS: u32 = 2_100;
 /// Maximum number of horizontal dots the printer can handle
 const DOT_WIDTH: u32 = 384;
-/// Time estimate for the printer to process one byte of data
-const BYTE_TIME_MICROS: u64 = ((11 * 1000000) + (BAUDRATE / 2)) / BAUDRATE;
-
-/// Specifies the used internal printer font. User-defined fonts are currently not supported by
-/// this driver.
-#[derive(Clone, Copy)]
+/// Maximum number of bytes of raster image data sent in a single `GS v 0` command, which
+/// the receive buffer of the printer can hold
+const RASTER_BAND_BYTES: usize = 4096;
+/// Interval in which the busy pin is polled while the printer is busy, in microseconds
+const BUSY_POLL_MICROS: u32 = 100;
+/// Number of bytes the `AsyncPrinter` sends at once if a busy pin is connected. The printer
+/// signals that it is busy while its receive buffer still has room for a few bytes, so these
+/// must not be more.
+#[cfg(feature = "async")]
+const BUSY_CHUNK_BYTES: usize = 16;
+/// How long to wait for the printer to become ready by default, in microseconds
+const DEFAULT_BUSY_TIMEOUT_MICROS: u32 = 5_000_000;
+
+/// Specifies the used internal printer font. Single characters of either font can be replaced by
+/// user-defined ones, see [`Printer::define_user_characters`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum Font {
+    #[default]
     FontA,
     FontB,
 }
 
-impl Default for Font {
-    fn default() -> Self {
-        Self::FontA
-    }
-}
-
 /// Determines whether text is aligned left, center, or right.
+#[derive(IntoPrimitive, Clone, Copy, Debug, Default)]
+#[repr(u8)]
 pub enum Justification {
-    Left,
+    #[default]
+    Left = 0,
     Center,
     Right,
 }
 
-impl Default for Justification {
-    fn default() -> Self {
-        Self::Left
-    }
-}
-
 /// Sets no, normal, or thick underlining.
+#[derive(IntoPrimitive, Default)]
+#[repr(u8)]
 pub enum Underline {
-    None,
+    #[default]
+    None = 0,
     Normal,
     Double,
 }
 
-impl Default for Underline {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 /// Defines the raster bit-image mode.
 ///
 /// | Mode           | Vertical Density  | Horizontal Density    |
@@ -108,25 +170,21 @@ impl Default for Underline {
 /// | `DoubleHeight` | 101.6dpi          | 203.2dpi              |
 /// | `Quadruple`    | 101.6dpi          | 101.6dpi              |
 ///
-#[derive(IntoPrimitive)]
+#[derive(IntoPrimitive, Clone, Copy, Default)]
 #[repr(u8)]
 pub enum RasterBitImageMode {
+    #[default]
     Normal = 0,
     DoubleWidth,
     DoubleHeight,
     Quadruple,
 }
 
-impl Default for RasterBitImageMode {
-    fn default() -> Self {
-        Self::Normal
-    }
-}
-
 /// Determines the used international character set. Default: `USA`.
-#[derive(IntoPrimitive)]
+#[derive(IntoPrimitive, Default)]
 #[repr(u8)]
 pub enum CharacterSet {
+    #[default]
     USA = 0,
     France,
     Germany,
@@ -145,16 +203,16 @@ pub enum CharacterSet {
     China,
 }
 
-impl Default for CharacterSet {
-    fn default() -> Self {
-        Self::USA
-    }
-}
-
 /// Determines the used code page. Default: `CP437`.
-#[derive(IntoPrimitive)]
+///
+/// Text is transcoded into the selected code page, see [`CodeTable::encode`]. The characters of
+/// `MIK`, `CP755`, `Iran`, `IranII`, `Latvian`, `Thai` and `Thai2` beyond ASCII are not known to
+/// the driver, so only ASCII text can be printed with them; the [`TextFallback`] applies to all
+/// other characters.
+#[derive(IntoPrimitive, Clone, Copy, Debug, Default, PartialEq, Eq)]
 #[repr(u8)]
 pub enum CodeTable {
+    #[default]
     CP437 = 0,
     Katakana = 1,
     CP850 = 2,
@@ -201,18 +259,15 @@ pub enum CodeTable {
     CP874 = 47,
 }
 
-impl Default for CodeTable {
-    fn default() -> Self {
-        Self::CP437
-    }
-}
-
 /// Defines the barcode system to be used.
 ///
 /// Some systems are considered binary-level, and some are multi-level systems, which is important for setting the barcode width. See [`BarcodeWidth`] for more information.
-#[derive(IntoPrimitive)]
+///
+/// Each system accepts a specific set of characters and lengths, see [`BarCodeSystem::payload`].
+#[derive(IntoPrimitive, Clone, Copy, Debug, Default, PartialEq, Eq)]
 #[repr(u8)]
 pub enum BarCodeSystem {
+    #[default]
     UpcA = 65,
     UpcE = 66,
     Ean13 = 67,
@@ -224,16 +279,12 @@ pub enum BarCodeSystem {
     Code128 = 73,
 }
 
-impl Default for BarCodeSystem {
-    fn default() -> Self {
-        Self::UpcA
-    }
-}
-
-/// These are special characters described in the printer documentation.
+/// These are special characters described in the printer documentation. In Code128 barcode text,
+/// each of them is preceded by `{` to select a code set, shift to the other of code sets A and B
+/// for one character, or insert a function character. A literal `{` is written as `{{`.
 ///
-/// TODO: Find out what exactly this is useful for.
-#[derive(IntoPrimitive)]
+/// [`Code128`] inserts them as needed.
+#[derive(IntoPrimitive, Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum BarCodeSpecialCharacter {
     Shift = 0x53,
@@ -261,16 +312,59 @@ pub enum BarCodeSpecialCharacter {
 ///
 /// `UpcA`, `UpcE`, `Ean8`, `Ean13` `Code93` and `Code128` are considered multi-level barcodes.
 /// `Code39`, `Itf` and `Codabar` are binary-level codes.
-#[derive(IntoPrimitive)]
+#[derive(IntoPrimitive, Clone, Copy, Debug, Default, PartialEq, Eq)]
 #[repr(u8)]
 pub enum BarcodeWidth {
     Width2 = 2,
+    #[default]
     Width3 = 3,
     Width4 = 4,
     Width5 = 5,
     Width6 = 6,
 }
 
+/// Determines where the human-readable interpretation (HRI) of a barcode, i.e. its text, is
+/// printed. The default is `None`.
+#[derive(IntoPrimitive, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum HriPosition {
+    #[default]
+    None = 0,
+    Above = 1,
+    Below = 2,
+    Both = 3,
+}
+
+/// Configures the appearance of barcodes: their size, position and human-readable text.
+///
+/// Prefer to use [`BarcodeStyleBuilder`] to construct.
+#[derive(Builder, Clone, Copy, Debug, PartialEq, Eq)]
+#[builder(default, setter(into), no_std)]
+pub struct BarcodeStyle {
+    /// the height of the barcode in dots. Default: 162
+    pub height: u8,
+    /// the [`BarcodeWidth`] to be used
+    pub width: BarcodeWidth,
+    /// the space to the left of the barcode in dots. Default: 0
+    pub left_space: u8,
+    /// where the text of the barcode is printed
+    pub text_position: HriPosition,
+    /// the [`Font`] used for the text of the barcode
+    pub text_font: Font,
+}
+
+impl Default for BarcodeStyle {
+    fn default() -> Self {
+        BarcodeStyle {
+            height: 162,
+            width: BarcodeWidth::default(),
+            left_space: 0,
+            text_position: HriPosition::default(),
+            text_font: Font::default(),
+        }
+    }
+}
+
 /// Configures print options such as the font used, white-on-black, upside-down or bold printing,
 /// as well as double-width, double-height and strikethrough modes via individual `bool` flags.
 ///
@@ -325,126 +419,207 @@ impl Default for PrintSettings {
     }
 }
 
-impl Into<u8> for PrintMode {
-    fn into(self) -> u8 {
+impl PrintSettings {
+    /// Estimate the time in microseconds it takes to print a single row of dots: the print head
+    /// fires its dots in groups of [`PrintSettings::dots`], each of which takes the heating time
+    /// plus the recovery interval.
+    fn dot_print_time(&self) -> u32 {
+        let dots_per_group = (self.dots as u32 + 1) * 8;
+        let groups = DOT_WIDTH.div_ceil(dots_per_group);
+        groups * (self.time as u32 + self.interval as u32) * 10
+    }
+}
+
+/// Describes the timing of a printer, which the driver uses to estimate how long the printer takes
+/// to output what it is sent. Irrelevant if a busy pin is connected.
+///
+/// Prefer to use [`TimingProfileBuilder`] to construct.
+#[derive(Builder, Clone, Copy, Debug, PartialEq, Eq)]
+#[builder(default, setter(into), no_std)]
+pub struct TimingProfile {
+    /// The baud rate of the serial connection. Must match the printer's configuration (usually
+    /// printed on its self-test page). Default: 19200
+    pub baud_rate: u32,
+    /// The time it takes to print a single row of dots, in microseconds. If `None`, it is derived
+    /// from the heating time and interval of the active [`PrintSettings`]. Default: `None`
+    #[builder(setter(strip_option))]
+    pub dot_print_time: Option<u32>,
+    /// The time it takes to feed the paper by a single row of dots, in microseconds. Default: 2100
+    pub dot_feed_time: u32,
+}
+
+impl Default for TimingProfile {
+    fn default() -> Self {
+        TimingProfile {
+            baud_rate: DEFAULT_BAUDRATE,
+            dot_print_time: None,
+            dot_feed_time: DEFAULT_DOT_FEED_MICROS,
+        }
+    }
+}
+
+impl TimingProfile {
+    /// Time estimate for the printer to receive one byte of data, in microseconds
+    fn byte_time(&self) -> u64 {
+        let baud_rate = self.baud_rate as u64;
+        ((11 * 1000000) + (baud_rate / 2)) / baud_rate
+    }
+}
+
+impl From<PrintMode> for u8 {
+    fn from(print_mode: PrintMode) -> u8 {
         let mut mode = 0;
 
-        match self.font {
+        match print_mode.font {
             Font::FontA => mode &= 1 << 0,
             Font::FontB => mode |= 1 << 0,
         }
 
         // Somehow this seems to be broken (some bit-flags are ignored); use the custom (mode-specific) commands defined in the datasheet instead
-        if self.inverse {
+        if print_mode.inverse {
             mode |= 1 << 1;
         }
 
-        if self.upside_down {
+        if print_mode.upside_down {
             mode |= 1 << 2;
         }
 
-        if self.emph {
+        if print_mode.emph {
             mode |= 1 << 3;
         }
 
-        if self.double_height {
+        if print_mode.double_height {
             mode |= 1 << 4;
         }
 
-        if self.double_width {
+        if print_mode.double_width {
             mode |= 1 << 5;
         }
 
-        if self.delete_line {
+        if print_mode.delete_line {
             mode |= 1 << 6;
         }
         mode
     }
 }
 
-impl Into<[u8; 3]> for PrintSettings {
-    fn into(self) -> [u8; 3] {
-        [self.dots, self.time, self.interval]
+impl From<PrintSettings> for [u8; 3] {
+    fn from(settings: PrintSettings) -> [u8; 3] {
+        [settings.dots, settings.time, settings.interval]
     }
 }
 
-/// A representation of the thermal printer. Implements the [`core::fmt::Write`] trait for printing
-/// normal text.
-pub struct Printer<Port: serial::Write<u8>, Delay: delay::DelayUs<u32>> {
-    pub serial: Port,
-    pub delay: Delay,
+/// Errors reported by the [`Printer`]. `E` is the error type of the underlying serial port.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PrinterError<E> {
+    /// The serial port failed to transmit (or receive) data.
+    Serial(E),
+    /// An argument was outside of the range accepted by the printer.
+    InvalidArgument(&'static str),
+    /// The printer did not answer a query in time.
+    Timeout,
+    /// The printer reported that it is out of paper.
+    PaperOut,
+    /// The printer reported that its print head is overheated.
+    Overheat,
+    /// The printer reported that its supply voltage is out of range.
+    VoltageOutOfRange,
+    /// The busy pin could not be read.
+    BusyPin(digital::ErrorKind),
+    /// The text cannot be printed as a barcode of the requested system.
+    InvalidBarcode(BarcodeError),
+    /// The character is not contained in the selected [`CodeTable`], and
+    /// [`TextFallback::Error`] is configured.
+    UnsupportedCharacter(char),
+}
+
+impl<E: Debug> Display for PrinterError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PrinterError::Serial(e) => write!(f, "serial error: {:?}", e),
+            PrinterError::InvalidArgument(reason) => write!(f, "invalid argument: {}", reason),
+            PrinterError::Timeout => write!(f, "the printer did not respond in time"),
+            PrinterError::PaperOut => write!(f, "the printer is out of paper"),
+            PrinterError::Overheat => write!(f, "the print head is overheated"),
+            PrinterError::VoltageOutOfRange => write!(f, "the supply voltage is out of range"),
+            PrinterError::BusyPin(kind) => write!(f, "busy pin error: {}", kind),
+            PrinterError::InvalidBarcode(e) => write!(f, "invalid barcode: {}", e),
+            PrinterError::UnsupportedCharacter(c) => {
+                write!(f, "character {:?} is not in the selected code table", c)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: Debug> std::error::Error for PrinterError<E> {}
+
+impl<E> From<BarcodeError> for PrinterError<E> {
+    fn from(e: BarcodeError) -> Self {
+        PrinterError::InvalidBarcode(e)
+    }
+}
+
+/// Keeps track of the physical position of the print head, so that the time it takes the printer
+/// to output what it is sent can be estimated. Shared between all printer implementations.
+pub(crate) struct Head {
+    timing: TimingProfile,
+    byte_time: u64,
     prev_byte: char,
     max_column: u8,
     char_height: u8,
     char_width: u8,
     line_spacing: u8,
     barcode_height: u8,
+    barcode_text_position: HriPosition,
+    barcode_text_font: Font,
     dot_print_time: u32,
     dot_feed_time: u32,
     current_column: u8,
     print_mode: u8,
+    pub(crate) justification: Justification,
+    pub(crate) encoder: TextEncoder,
+    nv_commands: NvImageCommands,
+    /// The heights of the NV images stored by the driver
+    nv_image_rows: Vec<usize>,
+    /// Whether the printer status is checked during long jobs
+    status_checks: bool,
 }
 
-impl<Port: serial::Write<u8>, Delay: delay::DelayUs<u32>> Printer<Port, Delay> {
-    /// Create a new `Printer` with default settings.
-    ///
-    /// You must specify the serial port to be used, as well as a delay implementation of your HAL
-    /// to allow the driver to block while the printer is outputting text.
-    pub fn new(serial: Port, delay: Delay) -> Printer<Port, Delay> {
-        Printer {
-            serial,
-            delay,
+impl Head {
+    pub(crate) fn new(timing: TimingProfile) -> Head {
+        Head {
+            timing,
+            byte_time: timing.byte_time(),
             prev_byte: '\n',
             max_column: 32,
             char_height: 24,
             char_width: 12,
             line_spacing: 6,
-            barcode_height: 162,
-            dot_print_time: 0,
-            dot_feed_time: 0,
+            barcode_height: BarcodeStyle::default().height,
+            barcode_text_position: HriPosition::None,
+            barcode_text_font: Font::FontA,
+            dot_print_time: timing
+                .dot_print_time
+                .unwrap_or_else(|| PrintSettings::default().dot_print_time()),
+            dot_feed_time: timing.dot_feed_time,
             current_column: 0,
             print_mode: 0,
+            justification: Justification::Left,
+            encoder: TextEncoder::default(),
+            nv_commands: NvImageCommands::default(),
+            nv_image_rows: Vec::new(),
+            status_checks: false,
         }
     }
 
-    /// Lower-level function to directly write an array of bytes to the output sink. Wraps around
-    /// [`write_byte`].
-    ///
-    /// Functions producing physical output on the printer should use [`write`] instead.
-    fn write_bytes(&mut self, bytes: &[u8]) {
-        for b in bytes.iter() {
-            self.write_byte(*b).unwrap();
-        }
-    }
-
-    /// Write a single byte to the underlying serial output.
-    ///
-    /// Functions producing physical output on the printer should use [`write_one`] instead.
-    fn write_byte(&mut self, byte: u8) -> Result<(), ()> {
-        let result = self.serial.write(byte);
-        self.sleep(BYTE_TIME_MICROS);
-        match result {
-            Ok(_) => Ok(()),
-            Err(_) => Err(()),
-        }
-    }
-
-    /// Writes multiple bytes to the printer. Wraps around [`write_one`].
-    fn write(&mut self, bytes: &[u8]) {
-        for b in bytes.iter() {
-            self.write_one(*b).unwrap();
-        }
-    }
-
-    /// Write a single byte to the printer, keeping track of the physical position of the print
-    /// head and blocking accordingly. Control commands should be issued via [`write_byte`]
-    /// instead.
-    fn write_one(&mut self, byte: u8) -> Result<(), ()> {
-        let result = self.serial.write(byte);
-
+    /// Account for a byte producing physical output and return the number of microseconds the
+    /// printer needs to process it.
+    pub(crate) fn advance(&mut self, byte: u8) -> u64 {
         // To keep up with the physical hardware, we try to estimate the time it takes for the
         // printer to output what we're sending it
-        let mut wait_duration: u64 = BYTE_TIME_MICROS;
+        let mut wait_duration: u64 = self.byte_time;
 
         // Check if we're encountering a line break
         if byte == b'\n' || self.current_column == self.max_column {
@@ -470,63 +645,84 @@ impl<Port: serial::Write<u8>, Delay: delay::DelayUs<u32>> Printer<Port, Delay> {
             self.current_column += 1;
             self.prev_byte = byte as char;
         }
-        self.sleep(wait_duration);
-        match result {
-            Ok(_) => Ok(()),
-            Err(_) => Err(()),
-        }
+        wait_duration
     }
 
-    /// Halt the program for the specified number of microseconds. We don't want to overrun the
-    /// printer's buffer, so this function is used to wait for the print head to physically produce
-    /// the desired output.
-    fn sleep(&mut self, duration: u64) {
-        self.delay.delay_us(duration as u32);
+    /// Time estimate for the printer to receive one byte of data, in microseconds.
+    pub(crate) fn byte_time(&self) -> u64 {
+        self.byte_time
     }
 
-    /// Configure the tab stop widths on the printer.
-    fn update_tabs(&mut self) {
-        self.write_bytes(&TAB_STOP_SEQUENCE);
-        for i in 1..(self.max_column / TAB_WIDTH) {
-            self.write_byte(i * TAB_WIDTH).unwrap();
+    /// Update the time it takes to print a row of dots after the heat settings changed, unless
+    /// the [`TimingProfile`] specifies it explicitly.
+    pub(crate) fn apply_print_settings(&mut self, print_settings: PrintSettings) {
+        if self.timing.dot_print_time.is_none() {
+            self.dot_print_time = print_settings.dot_print_time();
         }
-        self.write_byte(0x00).unwrap();
     }
 
-    /// Send the initialization sequence and configure tab stops.
-    pub fn reset(&mut self) {
-        // Init
-        self.write_bytes(&INIT_SEQUENCE);
+    /// Time in microseconds the printer needs to print and feed a single row of dots.
+    pub(crate) fn dot_row_time(&self) -> u64 {
+        (self.dot_print_time + self.dot_feed_time) as u64
+    }
 
-        // Configure tab stops
-        self.update_tabs();
-        self.set_print_settings(PrintSettings::default());
-        self.set_character_set(CharacterSet::default());
-        self.set_code_table(CodeTable::default());
-        self.set_barcode_height(self.barcode_height);
+    /// Time in microseconds the printer needs to print an image `rows` dots high.
+    pub(crate) fn image_time(&self, rows: usize) -> u64 {
+        rows as u64 * self.dot_row_time()
     }
 
-    /// Wake the device from sleep. Also block for 75ms, as according to the datasheet the
-    /// printer needs at least 50ms in order to be ready to receive commands.
-    pub fn wake(&mut self) {
-        self.write_bytes(&[ESC, 0x38, 0x00, 0x00]);
-        self.sleep(75_000);
+    /// The number of dot rows printed for the NV image with `index` in `mode`. Assumes a square
+    /// image as wide as the paper if the image was not stored by the driver.
+    pub(crate) fn nv_image_rows(&self, index: u8, mode: RasterBitImageMode) -> usize {
+        let rows = usize::from(index)
+            .checked_sub(1)
+            .and_then(|i| self.nv_image_rows.get(i))
+            .copied()
+            .unwrap_or(DOT_WIDTH as usize);
+        match mode {
+            RasterBitImageMode::DoubleHeight | RasterBitImageMode::Quadruple => rows * 2,
+            RasterBitImageMode::Normal | RasterBitImageMode::DoubleWidth => rows,
+        }
     }
 
-    /// Block for 500ms to allow the printer to boot, then wake it up, disable sleep, and call reset.
-    pub fn init(&mut self) {
-        // Allow time for the printer to initialize
-        self.sleep(500_000);
-        self.wake();
-        // Disable sleep
-        self.write_bytes(&[ESC, 0x38, 0x00, 0x00]);
-        self.reset();
-        self.feed();
+    /// Time in microseconds the printer needs to print a barcode.
+    pub(crate) fn barcode_time(&self) -> u64 {
+        let text_height: u64 = match self.barcode_text_font {
+            Font::FontA => 24,
+            Font::FontB => 17,
+        };
+        let text_rows = match self.barcode_text_position {
+            HriPosition::None => 0,
+            HriPosition::Above | HriPosition::Below => text_height,
+            HriPosition::Both => text_height * 2,
+        };
+        (self.barcode_height as u64 + text_rows) * self.dot_row_time()
+    }
+
+    /// The command configuring tab stops every [`TAB_WIDTH`] columns.
+    pub(crate) fn tab_stops(&self) -> Vec<u8> {
+        let mut command = TAB_STOP_SEQUENCE.to_vec();
+        for i in 1..(self.max_column / TAB_WIDTH) {
+            command.push(i * TAB_WIDTH);
+        }
+        command.push(0x00);
+        command
+    }
+
+    /// The font of the current print mode.
+    pub(crate) fn font(&self) -> Font {
+        if self.print_mode & 1 != 0 {
+            Font::FontB
+        } else {
+            Font::FontA
+        }
     }
 
     /// Update internal representations of char height and width depending on the configured font
     /// and print modes.
-    fn adjust_char_values(&mut self, print_mode: PrintMode) {
+    pub(crate) fn adjust_char_values(&mut self, print_mode: PrintMode) {
+        self.print_mode = print_mode.into();
+
         // Check font
         self.char_height = match print_mode.font {
             Font::FontA => 24,
@@ -549,167 +745,938 @@ impl<Port: serial::Write<u8>, Delay: delay::DelayUs<u32>> Printer<Port, Delay> {
 
         self.max_column = (DOT_WIDTH / self.char_width as u32) as u8;
     }
+}
 
-    /// Select print mode(s), such as inverse printing or double-height mode.
-    pub fn set_print_mode(&mut self, print_mode: PrintMode) {
-        let mode_byte: u8 = print_mode.into();
-        self.print_mode = mode_byte;
+/// The commands selecting `print_mode`: the `ESC !` sequence, followed by the dedicated commands
+/// for the modes which are ignored by it.
+pub(crate) fn print_mode_command(print_mode: PrintMode) -> Vec<u8> {
+    let mut command = MODE_SEQUENCE.to_vec();
+    command.push(print_mode.into());
+
+    // For some modes a custom command seems to be necessary
+    let modes = [
+        print_mode.inverse as u8,
+        print_mode.upside_down as u8,
+        print_mode.emph as u8,
+    ];
+    for (cmd, n) in zip(MODE_ORDER, modes) {
+        command.extend_from_slice(&cmd);
+        command.push(n);
+    }
+    command
+}
+
+/// The `GS v 0` raster bit image command header for an image of `rows` rows of `x_bytes` each.
+/// Both are sent as 16 bit little-endian numbers (xL xH yL yH).
+pub(crate) fn raster_header(mode: RasterBitImageMode, x_bytes: u16, rows: u16) -> [u8; 8] {
+    let [x_low, x_high] = x_bytes.to_le_bytes();
+    let [y_low, y_high] = rows.to_le_bytes();
+    [GS, 0x76, 0, mode.into(), x_low, x_high, y_low, y_high]
+}
+
+/// The number of rows of `x_bytes` each which are sent in a single `GS v 0` command, so that
+/// a band fits into the receive buffer of the printer.
+pub(crate) fn raster_band_rows(x_bytes: usize) -> usize {
+    (RASTER_BAND_BYTES / x_bytes.max(1)).max(1)
+}
 
-        self.write_bytes(&MODE_SEQUENCE);
-        self.write_byte(mode_byte).unwrap();
+/// The number of bytes per row of a raster image `width` dots wide, which must fit onto the paper.
+pub(crate) fn raster_row_bytes<E>(width: usize) -> Result<usize, PrinterError<E>> {
+    if width > DOT_WIDTH as usize {
+        return Err(PrinterError::InvalidArgument(
+            "raster image wider than the printable area",
+        ));
+    }
+    Ok(width.div_ceil(8))
+}
 
-        // For some modes a custom command seems to be necessary
-        let modes = [
-            print_mode.inverse as u8,
-            print_mode.upside_down as u8,
-            print_mode.emph as u8,
-        ];
-        for pair in zip(MODE_ORDER, modes) {
-            let (cmd, n) = pair;
-            self.write_bytes(&cmd);
-            self.write_byte(n).unwrap();
+/// Append a `row` of a raster image `width` dots wide to `band`, clearing the bits of its last
+/// byte beyond `width`.
+pub(crate) fn append_raster_row<E>(
+    band: &mut Vec<u8>,
+    row: &[u8],
+    width: usize,
+) -> Result<(), PrinterError<E>> {
+    if row.len() != width.div_ceil(8) {
+        return Err(PrinterError::InvalidArgument(
+            "raster row length does not match the width",
+        ));
+    }
+    band.extend_from_slice(row);
+    if width % 8 != 0 {
+        if let Some(last) = band.last_mut() {
+            *last &= 0xFF << (8 - width % 8);
         }
+    }
+    Ok(())
+}
+
+/// The `GS k` command header for printing a barcode payload of `len` bytes.
+pub(crate) fn barcode_header(system: BarCodeSystem, len: u8) -> [u8; 4] {
+    [GS, 0x6B, system.into(), len]
+}
+
+/// The parameter of the `GS f` command selecting `font` for the text of barcodes.
+pub(crate) fn hri_font(font: Font) -> u8 {
+    match font {
+        Font::FontA => 0,
+        Font::FontB => 1,
+    }
+}
+
+/// Placeholder for printers without a busy pin connected. Being uninhabited, there is no way to
+/// construct it.
+pub enum NoBusyPin {}
+
+impl digital::ErrorType for NoBusyPin {
+    type Error = Infallible;
+}
+
+impl InputPin for NoBusyPin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        match *self {}
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        match *self {}
+    }
+}
+
+/// A representation of the thermal printer. Implements the [`core::fmt::Write`] trait for printing
+/// normal text.
+///
+/// Since [`core::fmt::Error`] carries no information, the cause of a failed [`write!`] can be
+/// retrieved via [`Printer::take_error`].
+///
+/// If the serial port is also connected to the printer's TX line (i.e. it implements
+/// [`embedded_io::Read`] and [`ReadReady`]), the printer status can be queried via
+/// [`Printer::status`], and checked automatically during long jobs via
+/// [`Printer::with_status_policy`].
+///
+/// By default, the driver estimates how long the printer takes to output what it is sent, and
+/// blocks accordingly. If the printer's DTR line is connected to an input pin, construct the
+/// printer via [`Printer::with_busy_pin`] instead, which waits for the printer to signal that it is
+/// ready before sending each byte. This is both faster and more reliable.
+pub struct Printer<
+    Port: SerialWrite,
+    Delay: DelayNs,
+    Busy: InputPin = NoBusyPin,
+    Status: StatusCheck<Port> = NoStatusPolicy,
+> {
+    pub serial: Port,
+    pub delay: Delay,
+    busy: Option<Busy>,
+    head: Head,
+    response_timeout: u32,
+    busy_timeout: u32,
+    status_policy: Option<Status>,
+    last_error: Option<PrinterError<Port::Error>>,
+}
+
+impl<Port: SerialWrite, Delay: DelayNs> Printer<Port, Delay> {
+    /// Create a new `Printer` with default settings.
+    ///
+    /// You must specify the serial port to be used, as well as a delay implementation of your HAL
+    /// to allow the driver to block while the printer is outputting text.
+    pub fn new(serial: Port, delay: Delay) -> Printer<Port, Delay> {
+        Printer::build(serial, delay, None, TimingProfile::default())
+    }
+
+    /// Create a new `Printer` with default settings, which estimates how long the printer takes
+    /// to output what it is sent according to `timing`. Use this if your printer is configured for
+    /// a baud rate other than 19200, or to calibrate the estimates. See [`TimingProfile`] for more
+    /// information.
+    pub fn with_timing(serial: Port, delay: Delay, timing: TimingProfile) -> Printer<Port, Delay> {
+        Printer::build(serial, delay, None, timing)
+    }
+}
 
-        self.adjust_char_values(print_mode);
+impl<Port: SerialWrite, Delay: DelayNs, Busy: InputPin> Printer<Port, Delay, Busy> {
+    /// Create a new `Printer` with default settings, which uses the printer's DTR line connected
+    /// to `busy` for flow control. The printer signals that it is busy by pulling the line high.
+    ///
+    /// Flow control is enabled on the printer by [`Printer::reset`] (and thus [`Printer::init`]).
+    pub fn with_busy_pin(serial: Port, delay: Delay, busy: Busy) -> Printer<Port, Delay, Busy> {
+        Printer::build(serial, delay, Some(busy), TimingProfile::default())
+    }
+
+    /// Create a new `Printer` with default settings, which uses the printer's DTR line connected
+    /// to `busy` for flow control like [`Printer::with_busy_pin`], with the `timing` of the
+    /// printer. See [`TimingProfile`] for more information.
+    pub fn with_busy_pin_and_timing(
+        serial: Port,
+        delay: Delay,
+        busy: Busy,
+        timing: TimingProfile,
+    ) -> Printer<Port, Delay, Busy> {
+        Printer::build(serial, delay, Some(busy), timing)
+    }
+
+    fn build(
+        serial: Port,
+        delay: Delay,
+        busy: Option<Busy>,
+        timing: TimingProfile,
+    ) -> Printer<Port, Delay, Busy> {
+        Printer {
+            serial,
+            delay,
+            busy,
+            head: Head::new(timing),
+            response_timeout: DEFAULT_RESPONSE_TIMEOUT_MICROS,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT_MICROS,
+            status_policy: None,
+            last_error: None,
+        }
+    }
+}
+
+impl<Port: SerialWrite, Delay: DelayNs, Busy: InputPin, Status: StatusCheck<Port>>
+    Printer<Port, Delay, Busy, Status>
+{
+    /// Set how long to wait for the printer to answer a query, in microseconds. Default: 500ms.
+    pub fn set_response_timeout(&mut self, timeout: u32) {
+        self.response_timeout = timeout;
+    }
+
+    /// Set how long to wait for the printer to clear its busy signal, in microseconds. Only
+    /// relevant if a busy pin is connected. Default: 5s.
+    pub fn set_busy_timeout(&mut self, timeout: u32) {
+        self.busy_timeout = timeout;
+    }
+
+    /// Transcode `text` into the selected [`CodeTable`] and print it.
+    fn write_text(&mut self, text: &str) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.write_text(text)?;
+        self.run(job)
+    }
+
+    /// Return (and clear) the error which caused the last [`core::fmt::Write`] call to fail.
+    pub fn take_error(&mut self) -> Option<PrinterError<Port::Error>> {
+        self.last_error.take()
+    }
+
+    /// Perform `job`: send its data, waiting for the printer to process it, and check the printer
+    /// status at its checkpoints.
+    fn run(&mut self, job: Job) -> Result<(), PrinterError<Port::Error>> {
+        for step in job {
+            match step {
+                Step::Send(bytes, pause) => {
+                    self.send(&bytes)?;
+                    self.pace(pause);
+                }
+                Step::Sleep(duration) => self.sleep(duration),
+                Step::Checkpoint => self.check_status()?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a control command, which produces no physical output, waiting for the printer to
+    /// receive it.
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), PrinterError<Port::Error>> {
+        self.send(bytes)?;
+        self.pace(bytes.len() as u64 * self.head.byte_time());
+        Ok(())
+    }
+
+    /// Send `bytes` to the printer. If a busy pin is connected, each byte is sent once the
+    /// printer is ready to receive it.
+    fn send(&mut self, bytes: &[u8]) -> Result<(), PrinterError<Port::Error>> {
+        if self.busy.is_none() {
+            return self.serial.write_all(bytes).map_err(PrinterError::Serial);
+        }
+        for b in bytes.iter() {
+            self.wait_until_ready()?;
+            self.serial.write_all(&[*b]).map_err(PrinterError::Serial)?;
+        }
+        Ok(())
+    }
+
+    /// Check the printer status if a [`StatusPolicy`] is configured. Called before each chunk
+    /// of long jobs, so printing stops when the printer reports a fault.
+    fn check_status(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        match self.status_policy {
+            Some(policy) => policy.check(self),
+            None => Ok(()),
+        }
+    }
+
+    /// Halt the program for the specified number of microseconds. We don't want to overrun the
+    /// printer's buffer, so this function is used to wait for the print head to physically produce
+    /// the desired output.
+    fn sleep(&mut self, duration: u64) {
+        // Pauses longer than `delay_us` accepts, over an hour, are split
+        let mut remaining = duration;
+        loop {
+            let chunk = u32::try_from(remaining).unwrap_or(u32::MAX);
+            self.delay.delay_us(chunk);
+            remaining -= u64::from(chunk);
+            if remaining == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Block for the estimated time the printer needs to process what it has been sent. If a busy
+    /// pin is connected, this is not necessary, as [`wait_until_ready`] takes care of it.
+    fn pace(&mut self, duration: u64) {
+        if self.busy.is_none() {
+            self.sleep(duration);
+        }
+    }
+
+    /// Block while the printer signals that it is busy, if a busy pin is connected.
+    fn wait_until_ready(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        if let Some(busy) = self.busy.as_mut() {
+            let mut waited = 0;
+            while busy
+                .is_high()
+                .map_err(|e| PrinterError::BusyPin(e.kind()))?
+            {
+                if waited >= self.busy_timeout {
+                    return Err(PrinterError::Timeout);
+                }
+                self.delay.delay_us(BUSY_POLL_MICROS);
+                waited += BUSY_POLL_MICROS;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the initialization sequence and configure tab stops. Also enables flow control via the
+    /// DTR line if a busy pin is connected.
+    pub fn reset(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.reset(self.busy.is_some());
+        self.run(job)
+    }
+
+    /// Wake the device from sleep. Also block for 75ms, as according to the datasheet the
+    /// printer needs at least 50ms in order to be ready to receive commands.
+    pub fn wake(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.wake();
+        self.run(job)
+    }
+
+    /// Block for 500ms to allow the printer to boot, then wake it up, disable sleep, and call reset.
+    pub fn init(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.init(self.busy.is_some());
+        self.run(job)
+    }
+
+    /// Select print mode(s), such as inverse printing or double-height mode.
+    pub fn set_print_mode(
+        &mut self,
+        print_mode: PrintMode,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_print_mode(print_mode);
+        self.run(job)
     }
 
     /// Configure print settings. See [`PrintSettings`] for more information.
-    pub fn set_print_settings(&mut self, print_settings: PrintSettings) {
-        let settings_bytes: [u8; 3] = print_settings.into();
-        self.write_bytes(&[ESC, 0x37]);
-        self.write_bytes(&settings_bytes);
+    pub fn set_print_settings(
+        &mut self,
+        print_settings: PrintSettings,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_print_settings(print_settings);
+        self.run(job)
     }
 
-    pub fn set_justification(&mut self, justification: Justification) {
-        let justification_byte = match justification {
-            Justification::Left => 0x00,
-            Justification::Center => 0x01,
-            Justification::Right => 0x02,
-        };
-        self.write_bytes(&[ESC, 0x61, justification_byte]);
+    pub fn set_justification(
+        &mut self,
+        justification: Justification,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_justification(justification);
+        self.run(job)
     }
 
-    pub fn set_underline(&mut self, mode: Underline) {
-        let underline_byte = match mode {
-            Underline::None => 0x00,
-            Underline::Normal => 0x01,
-            Underline::Double => 0x02,
-        };
-        self.write_bytes(&[ESC, 0x2D, underline_byte]);
+    pub fn set_underline(&mut self, mode: Underline) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_underline(mode);
+        self.run(job)
+    }
+
+    pub fn set_character_set(
+        &mut self,
+        character_set: CharacterSet,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_character_set(character_set);
+        self.run(job)
+    }
+
+    pub fn set_code_table(
+        &mut self,
+        code_table: CodeTable,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_code_table(code_table);
+        self.run(job)
+    }
+
+    /// Set what is printed in place of characters which the selected [`CodeTable`] does not
+    /// contain. See [`TextFallback`] for more information.
+    pub fn set_text_fallback(&mut self, fallback: TextFallback) {
+        self.head.encoder.fallback = fallback;
+    }
+
+    /// Allow switching to one of `code_tables` for characters which the selected [`CodeTable`]
+    /// does not contain, so text in several scripts can be printed at once. The code table
+    /// containing the most of the following characters is chosen, and the selected code table is
+    /// restored at the end of the text. Only list code tables your printer supports. Disabled by
+    /// default, which corresponds to an empty slice.
+    ///
+    /// # Example
+    /// ```ignore
+    /// printer.set_code_table_switching(&[CodeTable::WCP1251, CodeTable::WCP1253]);
+    /// writeln!(printer, "Hello, Привет, Γειά σου")?;
+    /// ```
+    pub fn set_code_table_switching(&mut self, code_tables: &'static [CodeTable]) {
+        self.head.encoder.switching = code_tables;
+    }
+
+    /// Add transliterations for characters which no code table contains, which take precedence
+    /// over the built-in ones. Only used with [`TextFallback::Transliterate`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// printer.set_text_fallback(TextFallback::Transliterate);
+    /// printer.set_transliterations(&[('✓', "[x]"), ('☐', "[ ]")]);
+    /// ```
+    pub fn set_transliterations(&mut self, transliterations: &'static [(char, &'static str)]) {
+        self.head.encoder.transliterations = transliterations;
     }
 
-    pub fn set_character_set(&mut self, character_set: CharacterSet) {
-        self.write_bytes(&[ESC, 0x52, character_set.into()]);
+    /// Enable or disable printing characters which no code table contains as raster images,
+    /// rendered from the bitmap font compiled into the driver. Characters the font lacks are
+    /// still subject to the [`TextFallback`]. Disabled by default.
+    ///
+    /// Raster images cannot be printed in the middle of a line, so each line of text containing
+    /// such characters is printed as a whole as raster images, including its other characters.
+    /// Text sent for the same line by an earlier call is printed on its own line before. The
+    /// size of the glyphs follows the font and the double-width and double-height modes of the
+    /// [`PrintMode`].
+    ///
+    /// The default font has glyphs of 10x20 dots, which are smaller than the 12x24 dots of
+    /// [`Font::FontA`], and contains no CJK characters. See the `glyphs` feature in the README
+    /// for how to compile in another font.
+    #[cfg(feature = "glyphs")]
+    pub fn set_glyph_rendering(&mut self, enabled: bool) {
+        self.head.encoder.glyphs = enabled;
     }
 
-    pub fn set_code_table(&mut self, code_table: CodeTable) {
-        self.write_bytes(&[ESC, 0x74, code_table.into()]);
+    /// Define `glyphs` as the user-defined characters starting at the character code `first`,
+    /// for the font selected via [`Printer::set_print_mode`]. Once enabled via
+    /// [`Printer::set_user_characters`], printing these codes (which must be between `0x20` and
+    /// `0x7E`) prints the glyphs instead, so custom symbols can be printed inline with text.
+    ///
+    /// Returns [`PrinterError::InvalidArgument`] if the codes are out of range, or if a glyph was
+    /// created for another font.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let logo = UserGlyph::from_bmp(Font::FontA, Bitmap::from_slice(LOGO).unwrap()).unwrap();
+    /// printer.define_user_characters(b'~', &[logo])?;
+    /// printer.set_user_characters(true)?;
+    /// writeln!(printer, "~ ACME Corp.")?;
+    /// ```
+    pub fn define_user_characters(
+        &mut self,
+        first: u8,
+        glyphs: &[UserGlyph],
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.define_user_characters(first, glyphs)?;
+        self.run(job)
+    }
+
+    /// Enable or disable the user-defined characters. While disabled, their codes print the
+    /// characters of the code table.
+    pub fn set_user_characters(&mut self, enabled: bool) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_user_characters(enabled);
+        self.run(job)
+    }
+
+    /// Delete the user-defined character with the code `code`, which then prints the character of
+    /// the code table again.
+    pub fn cancel_user_character(&mut self, code: u8) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.cancel_user_character(code)?;
+        self.run(job)
     }
 
     /// Print a bitmap image. This command is not affected by print modes, but justification is
     /// respected.
     ///
-    /// Since only monochrome images can be printed, the raw color value of each pixel is matched
-    /// against [`PIXEL_COLOR_CUTOFF`]. If a pixel color is below this value, it produces a dot in
-    /// the output (reasoning that darker pixels should be printed, while lighter ones should not
-    /// be), otherwise not.
+    /// The image is either a [`Bitmap`] parsed by the driver, which supports all common BMP
+    /// formats, a [`RawBmp`](tinybmp::RawBmp) of `tinybmp`, whose color table is not
+    /// available (see [`Bitmap`] for the consequences), or a reference to a [`GrayImage`], e.g.
+    /// decoded from a PNG file. Since only monochrome images can be printed, the luminance of
+    /// each pixel is computed from its color, and converted into dots as configured by the
+    /// [`Dithering`] of `options`. The default threshold prints all pixels darker than mid gray,
+    /// which suits logos; photos look much better with error diffusion such as
+    /// [`Dithering::FloydSteinberg`].
+    ///
+    /// The image can be rotated, scaled and placed on the paper via `options`, see
+    /// [`BitmapOptions`]. Returns [`PrinterError::InvalidArgument`] if it is wider than the paper
+    /// (384 dots) right of the left margin after scaling; [`Scaling::FitWidth`] avoids this. The
+    /// same error is returned if the left margin is 384 dots or more, or the scaled image is
+    /// taller than 4096 rows of the full width.
+    /// Tall images are sent in bands which fit into the buffer of the printer. If a
+    /// [`StatusPolicy`] is configured, the bands are as high as a line of text, and the printer
+    /// status is checked before each of them.
     ///
     /// # Example
-    /// ```
-    /// printer.init();
+    /// ```ignore
+    /// printer.init()?;
     /// printer.print_bitmap(
-    ///     RawBmp::from_slice(
+    ///     Bitmap::from_slice(
     ///         include_bytes!("../resources/ferris.bmp")
     ///     ).unwrap(),
-    ///     RasterBitImageMode::Normal
-    /// );
+    ///     RasterBitImageMode::Normal,
+    ///     BitmapOptionsBuilder::default()
+    ///         .dithering(Dithering::FloydSteinberg)
+    ///         .build()
+    ///         .unwrap()
+    /// )?;
     /// ```
-    pub fn print_bitmap(&mut self, bmp: RawBmp, mode: RasterBitImageMode) {
-        let x_bits = bmp.header().image_size.width;
-        let x_bytes = (x_bits / 8) as u8 + u8::from(x_bits % 8 != 0);
-        let y_bits = bmp.header().image_size.height;
+    pub fn print_bitmap<'a>(
+        &mut self,
+        bmp: impl Into<Bitmap<'a>>,
+        mode: RasterBitImageMode,
+        options: BitmapOptions,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_bitmap(bmp.into(), mode, options)?;
+        self.run(job)
+    }
 
-        // I don't understand what xH and yH are, but setting them to 0 seems to work.
-        self.write_bytes(&[GS, 0x76, 0, mode.into(), x_bytes, 0, y_bits as u8, 0]);
+    /// Create a canvas as wide as the paper to draw on via `embedded-graphics`, e.g. to share the
+    /// code rendering a receipt with a display. It is printed as a raster image while drawing
+    /// progresses downwards, see [`PrinterCanvas`] for details. Justification is respected.
+    ///
+    /// # Example
+    /// ```ignore
+    /// use embedded_graphics::mono_font::{ascii::FONT_10X20, MonoTextStyle};
+    /// use embedded_graphics::pixelcolor::BinaryColor;
+    /// use embedded_graphics::prelude::*;
+    /// use embedded_graphics::primitives::{Circle, PrimitiveStyle};
+    /// use embedded_graphics::text::Text;
+    ///
+    /// let mut canvas = printer.canvas();
+    /// Text::new("Total: 42.00", Point::new(0, 20), MonoTextStyle::new(&FONT_10X20, BinaryColor::On))
+    ///     .draw(&mut canvas)?;
+    /// Circle::new(Point::new(160, 40), 64)
+    ///     .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 3))
+    ///     .draw(&mut canvas)?;
+    /// canvas.finish()?;
+    /// ```
+    #[cfg(feature = "embedded-graphics")]
+    pub fn canvas(&mut self) -> PrinterCanvas<'_, Port, Delay, Busy, Status> {
+        PrinterCanvas::new(self)
+    }
+
+    /// Select the commands used for NV images, depending on the firmware of the printer. See
+    /// [`NvImageCommands`] for more information.
+    pub fn set_nv_image_commands(&mut self, commands: NvImageCommands) {
+        self.head.nv_commands = commands;
+    }
+
+    /// Store `images` in the non-volatile (NV) memory of the printer under the indices 1, 2, and
+    /// so on, replacing the images stored before. Stored images survive switching the printer off
+    /// and are printed via [`Printer::print_nv_image`] without sending them again, which makes
+    /// printing a logo on every receipt much faster.
+    ///
+    /// NV memory wears out when written, so only store images when they change. Returns
+    /// [`PrinterError::InvalidArgument`] if there are no or too many images, if a bitmap is too
+    /// large, or if the images do not fit into a single `GS ( L` command.
+    ///
+    /// # Example
+    /// ```ignore
+    /// printer.store_nv_images(&[Bitmap::from_slice(include_bytes!("../resources/logo.bmp")).unwrap()])?;
+    /// // On every receipt
+    /// printer.print_nv_image(1, RasterBitImageMode::Normal)?;
+    /// ```
+    pub fn store_nv_images<'a>(
+        &mut self,
+        images: &[impl Into<Bitmap<'a>> + Copy],
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.store_nv_images(images)?;
+        self.run(job)
+    }
 
-        let mut image_bits = bitvec![u8, Msb0;];
-        for pixel in bmp.pixels() {
-            let column = pixel.position.x as u32;
+    /// Delete all images stored in the non-volatile memory of the printer.
+    pub fn erase_nv_images(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.erase_nv_images();
+        self.run(job)
+    }
 
-            image_bits.push(pixel.color < PIXEL_COLOR_CUTOFF);
+    /// Delete the stored image with `index`, keeping the others. Only supported by
+    /// [`NvImageCommands::Graphics`], otherwise [`PrinterError::InvalidArgument`] is returned.
+    pub fn erase_nv_image(&mut self, index: u8) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.erase_nv_image(index)?;
+        self.run(job)
+    }
 
-            if column == ((x_bits - 1) as u32) && x_bits % 8 > 0 {
-                let fill_bits = 8 - (x_bits % 8);
+    /// Print the stored image with `index` (starting at 1) in `mode`. Justification is respected.
+    ///
+    /// The time the printer needs is estimated from the height of the image if it was stored by
+    /// this `Printer`, and assuming a square image of the paper width otherwise.
+    pub fn print_nv_image(
+        &mut self,
+        index: u8,
+        mode: RasterBitImageMode,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_nv_image(index, mode)?;
+        self.run(job)
+    }
 
-                for _ in 0..fill_bits {
-                    image_bits.push(false);
-                }
+    /// Print a raster image `width` dots wide and `height` rows high in `mode`. This command is
+    /// not affected by print modes, but justification is respected.
+    ///
+    /// `data` contains the rows from top to bottom, each packed into `(width + 7) / 8` bytes with
+    /// the leftmost dot in the MSB. Bits beyond `width` are ignored. This prints images generated
+    /// at runtime without encoding them as a bitmap first; like for [`Printer::print_bitmap`],
+    /// the image is sent in bands.
+    ///
+    /// Returns [`PrinterError::InvalidArgument`] if the image is wider than the paper (384 dots),
+    /// or if `data` does not contain `height` rows.
+    ///
+    /// # Example
+    /// ```ignore
+    /// // A 16x16 checkerboard of 4x4 squares
+    /// let data: Vec<u8> = (0..16).flat_map(|y| if y / 4 % 2 == 0 { [0xF0, 0xF0] } else { [0x0F, 0x0F] }).collect();
+    /// printer.print_raster(16, 16, &data, RasterBitImageMode::Normal)?;
+    /// ```
+    pub fn print_raster(
+        &mut self,
+        width: usize,
+        height: usize,
+        data: &[u8],
+        mode: RasterBitImageMode,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_raster(width, height, data, mode)?;
+        self.run(job)
+    }
+
+    /// Print a raster image `width` dots wide in `mode`, whose rows are taken from `rows`, each
+    /// packed as for [`Printer::print_raster`]. Only a band of rows is kept in memory at a time,
+    /// so the rows can be generated while printing.
+    ///
+    /// Returns [`PrinterError::InvalidArgument`] if the image is wider than the paper, or if a row
+    /// does not have the length matching `width`. The rows before it may have been printed
+    /// already.
+    ///
+    /// # Example
+    /// ```ignore
+    /// // Horizontal stripes across the paper, each 4 dots high
+    /// let rows = (0..64).map(|y| [if y % 8 < 4 { 0xFF } else { 0x00 }; 48]);
+    /// printer.print_raster_iter(384, rows, RasterBitImageMode::Normal)?;
+    /// ```
+    pub fn print_raster_iter<R: AsRef<[u8]>>(
+        &mut self,
+        width: usize,
+        rows: impl IntoIterator<Item = R>,
+        mode: RasterBitImageMode,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let mut bands = RasterBands::new(&self.head, width, mode)?;
+        for row in rows {
+            if let Some(job) = bands.push(&self.head, row.as_ref())? {
+                self.run(job)?;
             }
         }
-        for (i, byte) in image_bits.as_raw_slice().iter().enumerate() {
-            self.write_byte(*byte).unwrap();
-            if i as u8 % x_bytes == 0 {
-                self.sleep((self.dot_print_time + self.dot_feed_time) as u64);
-            }
+        match bands.finish(&self.head) {
+            Some(job) => self.run(job),
+            None => Ok(()),
         }
     }
 
+    /// Print a QR code encoding `data`. See [`QrOptions`] for the available settings.
+    ///
+    /// By default, the QR code is encoded by the printer via the `GS ( k` commands. For firmware
+    /// revisions which do not support these, select [`QrRendering::Raster`], which prints it via
+    /// [`Printer::print_symbol`] instead. The justification is restored afterwards.
+    ///
+    /// Returns [`PrinterError::InvalidArgument`] if `data` does not fit into a QR code, if the
+    /// module size is not between 1 and 16, or if the QR code is rendered as a raster image
+    /// ([`QrRendering::Raster`]) and wider than the paper.
+    ///
+    /// # Example
+    /// ```ignore
+    /// printer.print_qr(
+    ///     b"https://www.rust-lang.org",
+    ///     QrOptionsBuilder::default()
+    ///         .module_size(4)
+    ///         .justification(Justification::Center)
+    ///         .build()
+    ///         .unwrap(),
+    /// )?;
+    /// ```
+    pub fn print_qr(
+        &mut self,
+        data: &[u8],
+        options: QrOptions,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_qr(data, options)?;
+        self.run(job)
+    }
+
+    /// Encode `data` as a two-dimensional symbol, such as a QR code or a Data Matrix code, and
+    /// print it as a raster image. Unlike [`Printer::print_qr`], this does not rely on the
+    /// printer's firmware. See [`SymbolOptions`] for the available settings. The justification is
+    /// restored afterwards.
+    ///
+    /// Returns [`PrinterError::InvalidArgument`] if `data` does not fit into the symbology, if the
+    /// scale is zero, or if the symbol is wider than the paper.
+    ///
+    /// # Example
+    /// ```ignore
+    /// printer.print_symbol(
+    ///     b"4006381333931",
+    ///     SymbolOptionsBuilder::default()
+    ///         .symbology(Symbology::DataMatrix)
+    ///         .scale(6)
+    ///         .quiet_zone(2)
+    ///         .build()
+    ///         .unwrap(),
+    /// )?;
+    /// ```
+    pub fn print_symbol(
+        &mut self,
+        data: &[u8],
+        options: SymbolOptions,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_symbol(data, options)?;
+        self.run(job)
+    }
+
     /// Print a barcode with the specified `BarCodeSystem`. Note that each system requires a
-    /// specific range of characters.
-    pub fn print_barcode(&mut self, system: BarCodeSystem, text: &str) {
-        self.write_bytes(&[GS, 0x6B, system.into(), text.len() as u8]);
-        for b in text.chars() {
-            self.write_byte(b as u8).unwrap();
-        }
-        self.sleep(self.barcode_height as u64 * (self.dot_print_time + self.dot_feed_time) as u64)
+    /// specific range of characters, see [`BarCodeSystem::payload`]. The check digit of UPC and
+    /// EAN barcodes is appended if it is omitted.
+    ///
+    /// Returns [`PrinterError::InvalidBarcode`] if `text` is not valid for `system`.
+    pub fn print_barcode(
+        &mut self,
+        system: BarCodeSystem,
+        text: &str,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.print_barcode(system, text)?;
+        self.run(job)
+    }
+
+    /// Print a Code128 barcode, using the code sets resulting in the shortest barcode. See
+    /// [`Code128`] for more information.
+    ///
+    /// Returns [`PrinterError::InvalidBarcode`] if the barcode cannot be encoded.
+    pub fn print_code128(&mut self, barcode: &Code128) -> Result<(), PrinterError<Port::Error>> {
+        self.print_barcode(BarCodeSystem::Code128, &barcode.encode()?)
     }
 
     /// Set the barcode height to the specified number of dots.
-    pub fn set_barcode_height(&mut self, height: u8) {
-        self.write_bytes(&[GS, 0x68, height]);
+    pub fn set_barcode_height(&mut self, height: u8) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_height(height);
+        self.run(job)
     }
 
     /// Set the space to the left of the barcode to the specified number of dots.
-    pub fn set_barcode_left_space(&mut self, space: u8) {
-        self.write_bytes(&[GS, 0x78, space]);
+    pub fn set_barcode_left_space(&mut self, space: u8) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_left_space(space);
+        self.run(job)
     }
 
     /// Set the barcode width to the specified value. See [`BarcodeWidth`] for more information.
-    pub fn set_barcode_width(&mut self, width: BarcodeWidth) {
-        self.write_bytes(&[GS, 0x77, width.into()]);
+    pub fn set_barcode_width(
+        &mut self,
+        width: BarcodeWidth,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_width(width);
+        self.run(job)
+    }
+
+    /// Set where the text of a barcode is printed. See [`HriPosition`] for more information.
+    pub fn set_barcode_text_position(
+        &mut self,
+        position: HriPosition,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_text_position(position);
+        self.run(job)
+    }
+
+    /// Set the font used for the text of a barcode.
+    pub fn set_barcode_text_font(&mut self, font: Font) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_text_font(font);
+        self.run(job)
+    }
+
+    /// Apply all barcode settings at once. See [`BarcodeStyle`] for more information.
+    pub fn set_barcode_style(
+        &mut self,
+        style: BarcodeStyle,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_barcode_style(style);
+        self.run(job)
     }
 
     /// Enable or disable the 90° clockwise rotation mode.
-    pub fn set_rotation_mode(&mut self, rotate: bool) {
-        self.write_bytes(&[ESC, 0x56, rotate.into()]);
+    pub fn set_rotation_mode(&mut self, rotate: bool) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.set_rotation_mode(rotate);
+        self.run(job)
     }
 
     /// Feed the paper by exactly one line.
-    pub fn feed(&mut self) {
-        self.feed_n(1);
+    pub fn feed(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        self.feed_n(1)
     }
 
     /// Feed the paper by the specified number of lines.
-    pub fn feed_n(&mut self, lines: u8) {
-        self.write_bytes(&[ESC, 0x4A, lines]);
+    pub fn feed_n(&mut self, lines: u8) -> Result<(), PrinterError<Port::Error>> {
+        let job = self.head.feed_n(lines);
+        self.run(job)
+    }
+}
 
-        let dot_feed_time: u64 = self.dot_feed_time.into();
-        let char_height: u64 = self.char_height.into();
-        self.sleep(dot_feed_time * char_height);
-        self.prev_byte = '\n';
-        self.current_column = 0;
+impl<Port: SerialWrite + SerialRead + ReadReady, Delay: DelayNs, Busy: InputPin>
+    Printer<Port, Delay, Busy>
+{
+    /// Check the printer status before lines of text and bands of bitmaps, and react to faults
+    /// according to `policy`. See [`StatusPolicy`] for more information.
+    ///
+    /// Depending on the policy, faults are reported as [`PrinterError::PaperOut`],
+    /// [`PrinterError::Overheat`] or [`PrinterError::VoltageOutOfRange`].
+    pub fn with_status_policy(
+        self,
+        policy: StatusPolicy,
+    ) -> Printer<Port, Delay, Busy, StatusPolicy> {
+        let mut printer = Printer {
+            serial: self.serial,
+            delay: self.delay,
+            busy: self.busy,
+            head: self.head,
+            response_timeout: self.response_timeout,
+            busy_timeout: self.busy_timeout,
+            status_policy: None,
+            last_error: self.last_error,
+        };
+        printer.set_status_policy(policy);
+        printer
     }
 }
 
-impl<Port: serial::Write<u8>, Delay: delay::DelayUs<u32>> Write for Printer<Port, Delay> {
-    fn write_str(&mut self, s: &str) -> Result<(), Error> {
-        self.write(s.as_bytes());
+impl<Port: SerialWrite + SerialRead + ReadReady, Delay: DelayNs, Busy: InputPin>
+    Printer<Port, Delay, Busy, StatusPolicy>
+{
+    /// Change how the printer status is checked during long jobs. See [`StatusPolicy`] for more
+    /// information.
+    pub fn set_status_policy(&mut self, policy: StatusPolicy) {
+        self.status_policy = Some(policy);
+        self.head.status_checks = policy != StatusPolicy::Disabled;
+    }
+
+    /// Query the printer status and act on faults according to `policy`.
+    pub(crate) fn apply_status_policy(
+        &mut self,
+        policy: StatusPolicy,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        let mut status = match policy {
+            StatusPolicy::Disabled => return Ok(()),
+            _ => self.status()?,
+        };
+        if let StatusPolicy::Pause { interval, timeout } = policy {
+            let mut waited: u32 = 0;
+            while !status.is_ready() && waited < timeout {
+                self.delay.delay_us(interval);
+                waited = waited.saturating_add(interval);
+                status = self.status()?;
+            }
+        }
+        status.fault()
+    }
+}
+
+impl<
+        Port: SerialWrite + SerialRead + ReadReady,
+        Delay: DelayNs,
+        Busy: InputPin,
+        Status: StatusCheck<Port>,
+    > Printer<Port, Delay, Busy, Status>
+{
+    /// Query the printer status, e.g. to refuse print jobs while the paper is out. See
+    /// [`PrinterStatus`] for more information.
+    ///
+    /// Returns [`PrinterError::Timeout`] if the printer does not answer within the response
+    /// timeout (see [`Printer::set_response_timeout`]).
+    pub fn status(&mut self) -> Result<PrinterStatus, PrinterError<Port::Error>> {
+        self.discard_input()?;
+        self.write_bytes(&PRINTER_STATUS_QUERY)?;
+        let printer_status = self.read_response()?;
+        self.write_bytes(&PAPER_STATUS_QUERY)?;
+        let paper_status = self.read_response()?;
+        Ok(PrinterStatus::from_responses(printer_status, paper_status))
+    }
+
+    /// List the indices of the images stored in the non-volatile memory of the printer. Only
+    /// supported by [`NvImageCommands::Graphics`], otherwise [`PrinterError::InvalidArgument`] is
+    /// returned. Images not stored by this driver are not listed.
+    ///
+    /// Returns [`PrinterError::Timeout`] if the printer does not answer within the response
+    /// timeout.
+    pub fn nv_images(&mut self) -> Result<Vec<u8>, PrinterError<Port::Error>> {
+        if self.head.nv_commands != NvImageCommands::Graphics {
+            return Err(PrinterError::InvalidArgument(
+                "NV images can only be listed via GS ( L",
+            ));
+        }
+        self.discard_input()?;
+        self.write_bytes(&NV_GRAPHICS_LIST_QUERY)?;
+        let mut list = KeyList::default();
+        loop {
+            match list.push(self.read_response()?) {
+                KeyListProgress::Pending => {}
+                KeyListProgress::NextBlock => self.write_bytes(&[ACK])?,
+                KeyListProgress::Done => return Ok(list.indices),
+            }
+        }
+    }
+
+    /// Drop any bytes the printer sent without being asked, so they are not mistaken for the
+    /// response to a query.
+    fn discard_input(&mut self) -> Result<(), PrinterError<Port::Error>> {
+        let mut buffer = [0; 8];
+        while self.serial.read_ready().map_err(PrinterError::Serial)? {
+            self.serial
+                .read(&mut buffer)
+                .map_err(PrinterError::Serial)?;
+        }
         Ok(())
     }
 
+    /// Wait for the printer to send a single byte, giving up after the response timeout.
+    fn read_response(&mut self) -> Result<u8, PrinterError<Port::Error>> {
+        let mut waited = 0;
+        while !self.serial.read_ready().map_err(PrinterError::Serial)? {
+            if waited >= self.response_timeout {
+                return Err(PrinterError::Timeout);
+            }
+            self.delay.delay_us(RESPONSE_POLL_MICROS);
+            waited += RESPONSE_POLL_MICROS;
+        }
+        let mut response = [0];
+        self.serial
+            .read(&mut response)
+            .map_err(PrinterError::Serial)?;
+        Ok(response[0])
+    }
+}
+
+impl<Port: SerialWrite, Delay: DelayNs, Busy: InputPin, Status: StatusCheck<Port>> Write
+    for Printer<Port, Delay, Busy, Status>
+{
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        self.write_text(s).map_err(|e| {
+            self.last_error = Some(e);
+            Error
+        })
+    }
+
     fn write_char(&mut self, c: char) -> Result<(), Error> {
-        self.write_one(c as u8).unwrap();
-        Ok(())
+        self.write_text(c.encode_utf8(&mut [0; 4])).map_err(|e| {
+            self.last_error = Some(e);
+            Error
+        })
     }
 
     fn write_fmt(&mut self, args: Arguments<'_>) -> Result<(), Error> {
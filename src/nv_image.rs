@@ -0,0 +1,248 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Images stored in the non-volatile (NV) memory of the printer, which can be printed without
+//! sending their data again.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::{PrinterError, RasterBitImageMode, GS};
+
+const FS: u8 = 0x1C;
+
+/// Estimate of the time the printer needs to write an image to its NV memory, during which it
+/// does not process data, in microseconds
+pub(crate) const NV_WRITE_MICROS: u64 = 1_000_000;
+/// `GS ( L` function 64: transmit the key codes of the stored images
+pub(crate) const NV_GRAPHICS_LIST_QUERY: [u8; 9] = [GS, 0x28, 0x4C, 4, 0, 48, 64, b'K', b'C'];
+/// Status byte of a response block which is followed by further blocks
+pub(crate) const NV_GRAPHICS_LIST_CONTINUES: u8 = 0x41;
+/// Requests the next block of a response
+pub(crate) const ACK: u8 = 0x06;
+/// Maximum height of an image stored via `FS q`, in dots
+const LEGACY_MAX_ROWS: usize = 288 * 8;
+/// First byte of the key codes of images stored via `GS ( L`, which is followed by the index
+const KEY_PREFIX: u8 = 0x20;
+
+/// Determines which commands are used to store and print NV images, depending on the firmware
+/// of the printer. Default: `Legacy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NvImageCommands {
+    /// `FS q` and `FS p`, supported by most printers including the CSN-A2. Storing images
+    /// replaces all previously stored ones.
+    #[default]
+    Legacy,
+    /// the `GS ( L` NV graphics functions of newer firmware, which allow deleting and listing
+    /// single images
+    Graphics,
+}
+
+/// The key code under which the image with `index` is stored via `GS ( L`.
+fn key_code(index: u8) -> [u8; 2] {
+    [KEY_PREFIX, KEY_PREFIX + index]
+}
+
+/// The index of the image stored under `key`, if it was stored by this driver.
+fn key_index(key: [u8; 2]) -> Option<u8> {
+    match key {
+        [KEY_PREFIX, code] if code > KEY_PREFIX && code <= 0x7E => Some(code - KEY_PREFIX),
+        _ => None,
+    }
+}
+
+/// Parses the response to [`NV_GRAPHICS_LIST_QUERY`], which consists of blocks of a header, an
+/// identifier and a status byte, followed by pairs of key codes up to a NUL byte.
+#[derive(Default)]
+pub(crate) struct KeyList {
+    /// The indices of the images stored by this driver
+    pub(crate) indices: Vec<u8>,
+    /// The bytes of the current block received so far, excluding complete key codes
+    block: Vec<u8>,
+}
+
+/// What to do after a byte of the response to [`NV_GRAPHICS_LIST_QUERY`] was received.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum KeyListProgress {
+    /// The response continues
+    Pending,
+    /// The block is complete, request the next one by sending [`ACK`]
+    NextBlock,
+    /// The response is complete
+    Done,
+}
+
+impl KeyList {
+    /// Process the next `byte` of the response.
+    pub(crate) fn push(&mut self, byte: u8) -> KeyListProgress {
+        self.block.push(byte);
+        match self.block[..] {
+            [_, _, status, 0] => {
+                self.block.clear();
+                if status == NV_GRAPHICS_LIST_CONTINUES {
+                    KeyListProgress::NextBlock
+                } else {
+                    KeyListProgress::Done
+                }
+            }
+            [_, _, _, first, second] => {
+                if let Some(index) = key_index([first, second]) {
+                    self.indices.push(index);
+                }
+                self.block.truncate(3);
+                KeyListProgress::Pending
+            }
+            _ => KeyListProgress::Pending,
+        }
+    }
+}
+
+/// The commands storing `images`, given as raster bit image data with the number of bytes per
+/// row, under the indices 1, 2, and so on.
+pub(crate) fn store_commands<E>(
+    commands: NvImageCommands,
+    images: &[(Vec<u8>, usize)],
+) -> Result<Vec<Vec<u8>>, PrinterError<E>> {
+    if images.is_empty() || images.len() > max_index(commands) as usize {
+        return Err(PrinterError::InvalidArgument(
+            "number of NV images out of range",
+        ));
+    }
+    if images.iter().any(|(image, _)| image.is_empty()) {
+        return Err(PrinterError::InvalidArgument("NV image is empty"));
+    }
+
+    match commands {
+        NvImageCommands::Legacy => {
+            // FS q n [xL xH yL yH d1...dk]1...[xL xH yL yH d1...dk]n
+            if images
+                .iter()
+                .any(|(image, x_bytes)| image.len() / x_bytes > LEGACY_MAX_ROWS)
+            {
+                return Err(PrinterError::InvalidArgument("NV image too large"));
+            }
+            let mut command = vec![FS, 0x71, images.len() as u8];
+            for (image, x_bytes) in images {
+                append_columns(&mut command, image, *x_bytes);
+            }
+            Ok(vec![command])
+        }
+        NvImageCommands::Graphics => images
+            .iter()
+            .enumerate()
+            .map(|(i, (image, x_bytes))| {
+                let rows = image.len() / *x_bytes;
+                let [kc1, kc2] = key_code(i as u8 + 1);
+                // GS ( L pL pH m fn a kc1 kc2 b xL xH yL yH c d1...dk
+                let len = u16::try_from(11 + image.len())
+                    .map_err(|_| PrinterError::InvalidArgument("NV image too large"))?;
+                let [x_low, x_high] = (*x_bytes as u16 * 8).to_le_bytes();
+                let [y_low, y_high] = (rows as u16).to_le_bytes();
+                let [p_low, p_high] = len.to_le_bytes();
+                let mut command = vec![
+                    GS, 0x28, 0x4C, p_low, p_high, 48, 67, 48, kc1, kc2, 1, x_low, x_high, y_low,
+                    y_high, 49,
+                ];
+                command.extend_from_slice(image);
+                Ok(command)
+            })
+            .collect(),
+    }
+}
+
+/// Append an image to an `FS q` command: its width in bytes and height in multiples of 8 dots,
+/// followed by its columns, each of them top to bottom.
+fn append_columns(command: &mut Vec<u8>, image: &[u8], x_bytes: usize) {
+    let rows = image.len() / x_bytes;
+    let y_bytes = rows.div_ceil(8);
+    command.extend_from_slice(&(x_bytes as u16).to_le_bytes());
+    command.extend_from_slice(&(y_bytes as u16).to_le_bytes());
+    for x in 0..x_bytes * 8 {
+        for y_byte in 0..y_bytes {
+            let mut column = 0;
+            for bit in 0..8 {
+                let y = y_byte * 8 + bit;
+                if y < rows && image[y * x_bytes + x / 8] & (0x80 >> (x % 8)) != 0 {
+                    column |= 0x80 >> bit;
+                }
+            }
+            command.push(column);
+        }
+    }
+}
+
+/// The command deleting all stored images.
+pub(crate) fn erase_all_command(commands: NvImageCommands) -> Vec<u8> {
+    match commands {
+        // FS q 0 defines no images
+        NvImageCommands::Legacy => vec![FS, 0x71, 0],
+        NvImageCommands::Graphics => vec![GS, 0x28, 0x4C, 5, 0, 48, 65, b'C', b'L', b'R'],
+    }
+}
+
+/// The command deleting the image with `index`, which is only supported by
+/// [`NvImageCommands::Graphics`].
+pub(crate) fn erase_command<E>(
+    commands: NvImageCommands,
+    index: u8,
+) -> Result<[u8; 9], PrinterError<E>> {
+    check_index(commands, index)?;
+    match commands {
+        NvImageCommands::Legacy => Err(PrinterError::InvalidArgument(
+            "single NV images can only be erased via GS ( L",
+        )),
+        NvImageCommands::Graphics => {
+            let [kc1, kc2] = key_code(index);
+            Ok([GS, 0x28, 0x4C, 4, 0, 48, 66, kc1, kc2])
+        }
+    }
+}
+
+/// The command printing the image with `index` in `mode`.
+pub(crate) fn print_command<E>(
+    commands: NvImageCommands,
+    index: u8,
+    mode: RasterBitImageMode,
+) -> Result<Vec<u8>, PrinterError<E>> {
+    check_index(commands, index)?;
+    Ok(match commands {
+        NvImageCommands::Legacy => vec![FS, 0x70, index, mode.into()],
+        NvImageCommands::Graphics => {
+            let [kc1, kc2] = key_code(index);
+            let (x, y) = match mode {
+                RasterBitImageMode::Normal => (1, 1),
+                RasterBitImageMode::DoubleWidth => (2, 1),
+                RasterBitImageMode::DoubleHeight => (1, 2),
+                RasterBitImageMode::Quadruple => (2, 2),
+            };
+            vec![GS, 0x28, 0x4C, 6, 0, 48, 69, kc1, kc2, x, y]
+        }
+    })
+}
+
+/// The highest index of an image which can be stored via `commands`.
+fn max_index(commands: NvImageCommands) -> u8 {
+    match commands {
+        NvImageCommands::Legacy => u8::MAX,
+        NvImageCommands::Graphics => 0x7E - KEY_PREFIX,
+    }
+}
+
+fn check_index<E>(commands: NvImageCommands, index: u8) -> Result<(), PrinterError<E>> {
+    if (1..=max_index(commands)).contains(&index) {
+        Ok(())
+    } else {
+        Err(PrinterError::InvalidArgument("NV image index out of range"))
+    }
+}
@@ -0,0 +1,120 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Decoding of PBM (portable bitmap) files into gray levels.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::{GrayImage, ImageError};
+
+impl GrayImage {
+    /// Decode a PBM file, in the plain (`P1`) or the raw (`P4`) format. Available with the `pbm`
+    /// feature.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let label = GrayImage::from_pbm(&label_pbm).unwrap();
+    /// printer.print_bitmap(&label, RasterBitImageMode::Normal, BitmapOptions::default())?;
+    /// ```
+    pub fn from_pbm(bytes: &[u8]) -> Result<GrayImage, ImageError> {
+        let raw = match bytes.get(0..2) {
+            Some(b"P1") => false,
+            Some(b"P4") => true,
+            Some(b"P2" | b"P3" | b"P5" | b"P6") => {
+                return Err(ImageError::Unsupported("PGM or PPM file"))
+            }
+            _ => return Err(ImageError::Malformed),
+        };
+        let mut header = Header { bytes, position: 2 };
+        let width = header.number()?;
+        let height = header.number()?;
+        let pixels = width.checked_mul(height).ok_or(ImageError::Malformed)?;
+        // In the plain format, a 1 is black, and whitespace between the digits is optional
+        let mut dots = Vec::new();
+        if raw {
+            // A single whitespace character separates the header from the rows, which are padded
+            // to full bytes
+            let row_bytes = width.div_ceil(8);
+            let data = bytes
+                .get(header.position + 1..)
+                .and_then(|data| data.get(..row_bytes.checked_mul(height)?))
+                .ok_or(ImageError::Malformed)?;
+            dots.reserve(pixels);
+            for row in data.chunks(row_bytes.max(1)).take(height) {
+                dots.extend((0..width).map(|x| row[x / 8] & (0x80 >> (x % 8)) != 0));
+            }
+        } else {
+            while dots.len() < pixels {
+                header.skip_whitespace();
+                match bytes.get(header.position) {
+                    Some(b'0') => dots.push(false),
+                    Some(b'1') => dots.push(true),
+                    _ => return Err(ImageError::Malformed),
+                }
+                header.position += 1;
+            }
+        }
+        let luminance = dots
+            .into_iter()
+            .map(|dot| if dot { 0 } else { u8::MAX })
+            .collect();
+        GrayImage::from_luminance(width, height, luminance)
+    }
+}
+
+/// Reads the header of a PBM file.
+struct Header<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Header<'_> {
+    /// Skip whitespace and comments, which last until the end of the line.
+    fn skip_whitespace(&mut self) {
+        while let Some(byte) = self.bytes.get(self.position) {
+            match byte {
+                b'#' => {
+                    while !matches!(self.bytes.get(self.position), Some(b'\n' | b'\r') | None) {
+                        self.position += 1;
+                    }
+                }
+                byte if byte.is_ascii_whitespace() => self.position += 1,
+                _ => break,
+            }
+        }
+    }
+
+    /// Read a decimal number preceded by whitespace.
+    fn number(&mut self) -> Result<usize, ImageError> {
+        let start = self.position;
+        self.skip_whitespace();
+        if self.position == start {
+            return Err(ImageError::Malformed);
+        }
+        let mut number: usize = 0;
+        let digits = self.position;
+        while let Some(byte) = self.bytes.get(self.position).filter(|b| b.is_ascii_digit()) {
+            number = number
+                .checked_mul(10)
+                .and_then(|number| number.checked_add(usize::from(byte - b'0')))
+                .ok_or(ImageError::Malformed)?;
+            self.position += 1;
+        }
+        if self.position == digits {
+            return Err(ImageError::Malformed);
+        }
+        Ok(number)
+    }
+}
@@ -0,0 +1,349 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Decoding of PNG files into gray levels.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+use crate::image::{luma, on_paper};
+use crate::{GrayImage, ImageError};
+
+/// The first bytes of every PNG file
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// Pixels of the passes of Adam7 interlacing as (first column, first row, column step, row step)
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// The color types of PNG images, which determine the channels of a pixel.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ColorType {
+    Gray,
+    Rgb,
+    Indexed,
+    GrayAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    fn channels(self) -> usize {
+        match self {
+            ColorType::Gray | ColorType::Indexed => 1,
+            ColorType::GrayAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+}
+
+/// The parts of a PNG file needed to compute the luminance of its pixels.
+struct Png<'a> {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: ColorType,
+    interlaced: bool,
+    /// The palette of indexed images, 3 bytes per entry
+    palette: &'a [u8],
+    /// The contents of the `tRNS` chunk: the alpha of each palette entry, or the samples of the
+    /// transparent color
+    transparency: Option<&'a [u8]>,
+}
+
+impl GrayImage {
+    /// Decode a PNG file. Available with the `png` feature.
+    ///
+    /// All color types and bit depths are supported, as well as interlaced images. Transparent
+    /// pixels are blended with the white paper. Ancillary chunks such as gamma and color profiles
+    /// are ignored.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let logo = GrayImage::from_png(include_bytes!("../resources/logo.png")).unwrap();
+    /// printer.print_bitmap(&logo, RasterBitImageMode::Normal, BitmapOptions::default())?;
+    /// ```
+    pub fn from_png(bytes: &[u8]) -> Result<GrayImage, ImageError> {
+        if bytes.get(..SIGNATURE.len()) != Some(&SIGNATURE[..]) {
+            return Err(ImageError::Malformed);
+        }
+        let mut header = None;
+        let mut palette: &[u8] = &[];
+        let mut transparency = None;
+        let mut compressed = Vec::new();
+        let mut rest = &bytes[SIGNATURE.len()..];
+        loop {
+            let length = read_u32(rest, 0)? as usize;
+            let kind = rest.get(4..8).ok_or(ImageError::Malformed)?;
+            // The chunk data is followed by a CRC, which is not checked
+            if rest.len() < length.saturating_add(12) {
+                return Err(ImageError::Malformed);
+            }
+            let data = &rest[8..8 + length];
+            rest = &rest[12 + length..];
+            match kind {
+                b"IHDR" => header = Some(data),
+                b"PLTE" => palette = data,
+                b"tRNS" => transparency = Some(data),
+                b"IDAT" => compressed.extend_from_slice(data),
+                b"IEND" => break,
+                // Chunks starting with a lowercase letter are optional
+                _ if kind[0].is_ascii_lowercase() => {}
+                _ => return Err(ImageError::Unsupported("critical PNG chunk")),
+            }
+        }
+
+        let header = match header {
+            Some(header) if header.len() == 13 => header,
+            _ => return Err(ImageError::Malformed),
+        };
+        let color_type = match header[9] {
+            0 => ColorType::Gray,
+            2 => ColorType::Rgb,
+            3 => ColorType::Indexed,
+            4 => ColorType::GrayAlpha,
+            6 => ColorType::Rgba,
+            _ => return Err(ImageError::Malformed),
+        };
+        let png = Png {
+            width: read_u32(header, 0)? as usize,
+            height: read_u32(header, 4)? as usize,
+            bit_depth: header[8],
+            color_type,
+            interlaced: match header[12] {
+                0 => false,
+                1 => true,
+                _ => return Err(ImageError::Malformed),
+            },
+            palette,
+            transparency,
+        };
+        let valid_depth = match color_type {
+            ColorType::Gray => matches!(png.bit_depth, 1 | 2 | 4 | 8 | 16),
+            ColorType::Indexed => matches!(png.bit_depth, 1 | 2 | 4 | 8),
+            _ => matches!(png.bit_depth, 8 | 16),
+        };
+        if !valid_depth || header[10] != 0 || header[11] != 0 {
+            return Err(ImageError::Malformed);
+        }
+        png.decode(&compressed)
+    }
+}
+
+impl Png<'_> {
+    /// The passes the pixels are stored in, as in [`ADAM7`].
+    fn passes(&self) -> &'static [(usize, usize, usize, usize)] {
+        if self.interlaced {
+            &ADAM7
+        } else {
+            &[(0, 0, 1, 1)]
+        }
+    }
+
+    /// The number of bits per pixel.
+    fn pixel_bits(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+
+    /// Decompress and defilter the image data, and compute the luminance of each pixel.
+    fn decode(&self, compressed: &[u8]) -> Result<GrayImage, ImageError> {
+        // Each row of each pass starts with the filter type
+        let mut size = 0usize;
+        for &(x0, y0, dx, dy) in self.passes() {
+            let (columns, rows) = pass_size(self.width, self.height, x0, y0, dx, dy);
+            if columns > 0 {
+                size = columns
+                    .checked_mul(self.pixel_bits())
+                    .and_then(|bits| (bits.div_ceil(8) + 1).checked_mul(rows))
+                    .and_then(|pass_size| pass_size.checked_add(size))
+                    .ok_or(ImageError::Malformed)?;
+            }
+        }
+        let data = decompress_to_vec_zlib_with_limit(compressed, size)
+            .map_err(|_| ImageError::Malformed)?;
+        // Only allocate the image once its data is known to be complete
+        if data.len() != size {
+            return Err(ImageError::Malformed);
+        }
+        let pixels = self
+            .width
+            .checked_mul(self.height)
+            .ok_or(ImageError::Malformed)?;
+        let mut gray = vec![u8::MAX; pixels];
+
+        // Filters refer to the corresponding byte of the pixel to the left, or the previous byte
+        // if pixels are smaller
+        let stride = (self.pixel_bits() / 8).max(1);
+        let mut rest = &data[..];
+        for &(x0, y0, dx, dy) in self.passes() {
+            let (columns, rows) = pass_size(self.width, self.height, x0, y0, dx, dy);
+            if columns == 0 || rows == 0 {
+                continue;
+            }
+            let row_len = (columns * self.pixel_bits()).div_ceil(8);
+            let mut previous = vec![0; row_len];
+            let mut row = vec![0; row_len];
+            for pass_y in 0..rows {
+                row.copy_from_slice(&rest[1..1 + row_len]);
+                let filter = rest[0];
+                rest = &rest[1 + row_len..];
+                unfilter(filter, &mut row, &previous, stride)?;
+                let y = y0 + pass_y * dy;
+                for pass_x in 0..columns {
+                    let x = x0 + pass_x * dx;
+                    gray[y * self.width + x] = self.pixel_luminance(&row, pass_x)?;
+                }
+                core::mem::swap(&mut row, &mut previous);
+            }
+        }
+        GrayImage::from_luminance(self.width, self.height, gray)
+    }
+
+    /// The luminance of the pixel in column `x` of a defiltered `row`.
+    fn pixel_luminance(&self, row: &[u8], x: usize) -> Result<u8, ImageError> {
+        let channels = self.color_type.channels();
+        let sample = |channel: usize| sample(row, x * channels + channel, self.bit_depth);
+        // Scale a sample to 0..=255
+        let max = (1u32 << self.bit_depth) - 1;
+        let scale = |value: u32| value * u8::MAX as u32 / max;
+        // A pixel is transparent if its samples match those given in the tRNS chunk
+        let transparent = |samples: &[u32]| match self.transparency {
+            Some(trns) => samples
+                .iter()
+                .enumerate()
+                .all(|(i, value)| read_u16(trns, i * 2).map(u32::from).ok() == Some(*value)),
+            None => false,
+        };
+        let opaque = |transparent: bool| if transparent { 0 } else { u8::MAX as u32 };
+
+        let (luma, alpha) = match self.color_type {
+            ColorType::Gray => (scale(sample(0)), opaque(transparent(&[sample(0)]))),
+            ColorType::Rgb => {
+                let (red, green, blue) = (sample(0), sample(1), sample(2));
+                (
+                    luma(scale(red), scale(green), scale(blue)),
+                    opaque(transparent(&[red, green, blue])),
+                )
+            }
+            ColorType::Indexed => {
+                let index = sample(0) as usize;
+                let color = self
+                    .palette
+                    .get(index * 3..index * 3 + 3)
+                    .ok_or(ImageError::Malformed)?;
+                // Entries missing from the tRNS chunk are opaque
+                let alpha = self
+                    .transparency
+                    .and_then(|trns| trns.get(index))
+                    .map_or(u8::MAX, |alpha| *alpha);
+                (
+                    luma(
+                        u32::from(color[0]),
+                        u32::from(color[1]),
+                        u32::from(color[2]),
+                    ),
+                    u32::from(alpha),
+                )
+            }
+            ColorType::GrayAlpha => (scale(sample(0)), scale(sample(1))),
+            ColorType::Rgba => (
+                luma(scale(sample(0)), scale(sample(1)), scale(sample(2))),
+                scale(sample(3)),
+            ),
+        };
+        Ok(on_paper(luma, alpha))
+    }
+}
+
+/// The number of columns and rows of a pass whose pixels start at (`x0`, `y0`) and repeat every
+/// `dx` columns and `dy` rows.
+fn pass_size(
+    width: usize,
+    height: usize,
+    x0: usize,
+    y0: usize,
+    dx: usize,
+    dy: usize,
+) -> (usize, usize) {
+    let count = |size: usize, start: usize, step: usize| (size + step - 1 - start.min(size)) / step;
+    (count(width, x0, dx), count(height, y0, dy))
+}
+
+/// Reverse the `filter` applied to `row`, given the defiltered previous row of the same pass.
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], stride: usize) -> Result<(), ImageError> {
+    for i in 0..row.len() {
+        let left = if i >= stride { row[i - stride] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= stride { previous[i - stride] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(ImageError::Malformed),
+        };
+        row[i] = row[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+/// Predict a byte from its neighbours to the left, above and above left.
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(up) - i16::from(up_left);
+    let distance = |value: u8| (estimate - i16::from(value)).abs();
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// The `index`th sample of `bit_depth` bits in `row`. Samples smaller than a byte are packed
+/// starting with the most significant bit.
+fn sample(row: &[u8], index: usize, bit_depth: u8) -> u32 {
+    match bit_depth {
+        16 => u32::from(u16::from_be_bytes([row[index * 2], row[index * 2 + 1]])),
+        8 => u32::from(row[index]),
+        bits => {
+            let bits = bits as usize;
+            let shift = 8 - bits - index * bits % 8;
+            u32::from(row[index * bits / 8] >> shift) & ((1 << bits) - 1)
+        }
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, ImageError> {
+    match bytes.get(offset..offset + 2) {
+        Some(&[high, low]) => Ok(u16::from_be_bytes([high, low])),
+        _ => Err(ImageError::Malformed),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ImageError> {
+    match bytes.get(offset..offset + 4) {
+        Some(&[b0, b1, b2, b3]) => Ok(u32::from_be_bytes([b0, b1, b2, b3])),
+        _ => Err(ImageError::Malformed),
+    }
+}
@@ -0,0 +1,735 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! QR code support: the options for the printer's native `GS ( k` commands, and an encoder to
+//! print QR codes as raster images on printers which do not support them.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use bitvec::prelude::*;
+use derive_builder::Builder;
+use num_enum::IntoPrimitive;
+
+use crate::symbol::{ModuleMatrix, ReedSolomon, SymbolOptions, Symbology};
+use crate::{Justification, PrinterError, GS};
+
+/// The QR code model. Model 2 is an extension of model 1 and should be preferred. Only used for
+/// [`QrRendering::Native`], raster images are always encoded as model 2.
+#[derive(IntoPrimitive, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum QrModel {
+    Model1 = 49,
+    #[default]
+    Model2 = 50,
+}
+
+/// The error correction level of a QR code. Higher levels allow more of the code to be damaged
+/// or obscured, but require a larger code for the same data. Default: `Medium`.
+///
+/// | Level      | Recoverable Data |
+/// |------------|------------------|
+/// | `Low`      | 7%               |
+/// | `Medium`   | 15%              |
+/// | `Quartile` | 25%              |
+/// | `High`     | 30%              |
+#[derive(IntoPrimitive, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum QrErrorCorrection {
+    Low = 48,
+    #[default]
+    Medium = 49,
+    Quartile = 50,
+    High = 51,
+}
+
+impl QrErrorCorrection {
+    /// Index into the error correction tables
+    fn ordinal(self) -> usize {
+        match self {
+            QrErrorCorrection::Low => 0,
+            QrErrorCorrection::Medium => 1,
+            QrErrorCorrection::Quartile => 2,
+            QrErrorCorrection::High => 3,
+        }
+    }
+
+    /// The value encoded in the format information
+    fn format_bits(self) -> u32 {
+        match self {
+            QrErrorCorrection::Low => 1,
+            QrErrorCorrection::Medium => 0,
+            QrErrorCorrection::Quartile => 3,
+            QrErrorCorrection::High => 2,
+        }
+    }
+}
+
+/// Determines how a QR code is produced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QrRendering {
+    /// Let the printer encode and print the QR code via its `GS ( k` commands. Not all firmware
+    /// revisions support these.
+    #[default]
+    Native,
+    /// Encode the QR code in the driver and print it as a raster image, which works on all
+    /// printers. See [`SymbolOptions`] for more control over the rendering.
+    Raster,
+}
+
+/// Configures how a QR code is printed.
+///
+/// Prefer to use [`QrOptionsBuilder`] to construct.
+#[derive(Builder, Clone, Copy, Debug)]
+#[builder(default, setter(into), no_std)]
+pub struct QrOptions {
+    /// the [`QrModel`] to be used
+    pub model: QrModel,
+    /// the width and height of a single module in dots, from 1 to 16. Default: 3
+    pub module_size: u8,
+    /// the [`QrErrorCorrection`] level to be used
+    pub error_correction: QrErrorCorrection,
+    /// the horizontal position of the QR code
+    pub justification: Justification,
+    /// whether the printer or the driver encodes the QR code
+    pub rendering: QrRendering,
+}
+
+impl Default for QrOptions {
+    fn default() -> Self {
+        QrOptions {
+            model: QrModel::default(),
+            module_size: 3,
+            error_correction: QrErrorCorrection::default(),
+            justification: Justification::default(),
+            rendering: QrRendering::default(),
+        }
+    }
+}
+
+impl QrOptions {
+    /// The options for rendering the QR code as a raster image, checking that the module size is
+    /// supported.
+    pub(crate) fn symbol_options<E>(&self) -> Result<SymbolOptions, PrinterError<E>> {
+        if !(1..=16).contains(&self.module_size) {
+            return Err(PrinterError::InvalidArgument("QR module size out of range"));
+        }
+        Ok(SymbolOptions {
+            symbology: Symbology::Qr(self.error_correction),
+            scale: self.module_size,
+            quiet_zone: None,
+            justification: self.justification,
+        })
+    }
+}
+
+/// The `GS ( k` commands selecting the model, module size and error correction level, storing
+/// `data` in the symbol storage area and printing it. `data` must fit into a QR code.
+pub(crate) fn native_qr_command(data: &[u8], options: &QrOptions) -> Vec<u8> {
+    let [p_l, p_h] = ((data.len() + 3) as u16).to_le_bytes();
+
+    let mut command = vec![GS, 0x28, 0x6B, 4, 0, 49, 65, options.model.into(), 0];
+    command.extend_from_slice(&[GS, 0x28, 0x6B, 3, 0, 49, 67, options.module_size]);
+    command.extend_from_slice(&[
+        GS,
+        0x28,
+        0x6B,
+        3,
+        0,
+        49,
+        69,
+        options.error_correction.into(),
+    ]);
+    command.extend_from_slice(&[GS, 0x28, 0x6B, p_l, p_h, 49, 80, 48]);
+    command.extend_from_slice(data);
+    command.extend_from_slice(&[GS, 0x28, 0x6B, 3, 0, 49, 81, 48]);
+    command
+}
+
+/// The height of the QR code the printer prints for `data` via [`native_qr_command`], in dots,
+/// including the quiet zone. Estimated from the capacity of model 2 QR codes, without encoding
+/// the data. Returns `None` if the data does not fit into a QR code.
+pub(crate) fn native_qr_dots(data: &[u8], options: &QrOptions) -> Option<usize> {
+    let version = qr_version(data, Mode::select(data), options.error_correction)?;
+    let quiet_zone = Symbology::Qr(options.error_correction).quiet_zone() as usize;
+    Some((version * 4 + 17 + quiet_zone * 2) * options.module_size as usize)
+}
+
+/// The smallest version of a QR code holding `data` in `mode` with the given error correction
+/// level, or `None` if the data does not fit into a QR code.
+fn qr_version(data: &[u8], mode: Mode, ecc: QrErrorCorrection) -> Option<usize> {
+    (1..=40).find(|&version| {
+        let count_bits = mode.count_bits(version);
+        let used_bits = 4 + count_bits + mode.data_bits(data.len());
+        data.len() < 1 << count_bits && used_bits <= data_codewords(version, ecc) * 8
+    })
+}
+
+/// Encode `data` in the smallest QR code possible with the given error correction level.
+/// Returns `None` if the data does not fit into a QR code.
+pub(crate) fn encode_qr(data: &[u8], ecc: QrErrorCorrection) -> Option<ModuleMatrix> {
+    QrCode::encode(data, ecc).map(|qr| qr.modules)
+}
+
+/// Number of error correction codewords per block, by error correction level and version
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [
+        0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28,
+        30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28,
+        28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    ],
+    [
+        0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30,
+        30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24,
+        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+];
+
+/// Number of error correction blocks, by error correction level and version
+const NUM_ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [
+        0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13,
+        14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25,
+    ],
+    [
+        0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21,
+        23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+    ],
+    [
+        0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29,
+        34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68,
+    ],
+    [
+        0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32,
+        35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81,
+    ],
+];
+
+/// Characters which can be encoded in alphanumeric mode, in the order of their values
+const ALPHANUMERIC_CHARSET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Penalty weights for choosing the mask pattern
+const PENALTY_N1: i32 = 3;
+const PENALTY_N2: i32 = 3;
+const PENALTY_N3: i32 = 40;
+const PENALTY_N4: i32 = 10;
+
+/// The most compact encoding mode able to represent the whole payload.
+#[derive(Clone, Copy)]
+enum Mode {
+    Numeric,
+    Alphanumeric,
+    Byte,
+}
+
+impl Mode {
+    fn select(data: &[u8]) -> Mode {
+        if data.iter().all(u8::is_ascii_digit) {
+            Mode::Numeric
+        } else if data.iter().all(|b| ALPHANUMERIC_CHARSET.contains(b)) {
+            Mode::Alphanumeric
+        } else {
+            Mode::Byte
+        }
+    }
+
+    fn indicator(self) -> u32 {
+        match self {
+            Mode::Numeric => 0x1,
+            Mode::Alphanumeric => 0x2,
+            Mode::Byte => 0x4,
+        }
+    }
+
+    /// Width of the character count field
+    fn count_bits(self, version: usize) -> usize {
+        let index = match version {
+            1..=9 => 0,
+            10..=26 => 1,
+            _ => 2,
+        };
+        match self {
+            Mode::Numeric => [10, 12, 14][index],
+            Mode::Alphanumeric => [9, 11, 13][index],
+            Mode::Byte => [8, 16, 16][index],
+        }
+    }
+
+    /// Number of bits needed to encode `len` characters, excluding the header
+    fn data_bits(self, len: usize) -> usize {
+        match self {
+            Mode::Numeric => len / 3 * 10 + [0, 4, 7][len % 3],
+            Mode::Alphanumeric => len / 2 * 11 + (len % 2) * 6,
+            Mode::Byte => len * 8,
+        }
+    }
+}
+
+/// Append the `len` lowest bits of `value` to `bits`, most significant bit first.
+fn push_bits(bits: &mut BitVec<u8, Msb0>, value: u32, len: usize) {
+    for i in (0..len).rev() {
+        bits.push((value >> i) & 1 != 0);
+    }
+}
+
+/// A QR code (model 2), encoded as a square matrix of modules.
+struct QrCode {
+    version: usize,
+    size: usize,
+    modules: ModuleMatrix,
+    is_function: Vec<bool>,
+}
+
+impl QrCode {
+    fn encode(data: &[u8], ecc: QrErrorCorrection) -> Option<QrCode> {
+        let mode = Mode::select(data);
+        let version = qr_version(data, mode, ecc)?;
+
+        let codewords =
+            add_ecc_and_interleave(&data_codewords_for(data, mode, version, ecc), version, ecc);
+
+        let size = version * 4 + 17;
+        let mut qr = QrCode {
+            version,
+            size,
+            modules: ModuleMatrix::new(size),
+            is_function: vec![false; size * size],
+        };
+        qr.draw_function_patterns(ecc);
+        qr.draw_codewords(&codewords);
+
+        // Choose the mask with the lowest penalty
+        let mut best_mask = 0;
+        let mut min_penalty = i32::MAX;
+        for mask in 0..8 {
+            qr.apply_mask(mask);
+            qr.draw_format_bits(ecc, mask);
+            let penalty = qr.penalty_score();
+            if penalty < min_penalty {
+                best_mask = mask;
+                min_penalty = penalty;
+            }
+            // Masking twice undoes the mask
+            qr.apply_mask(mask);
+        }
+        qr.apply_mask(best_mask);
+        qr.draw_format_bits(ecc, best_mask);
+        Some(qr)
+    }
+
+    fn module(&self, x: usize, y: usize) -> bool {
+        self.modules.get(x, y)
+    }
+
+    fn set_function_module(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules.set(x, y, dark);
+        self.is_function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self, ecc: QrErrorCorrection) {
+        let size = self.size;
+
+        // Timing patterns
+        for i in 0..size {
+            self.set_function_module(6, i, i % 2 == 0);
+            self.set_function_module(i, 6, i % 2 == 0);
+        }
+
+        // Finder patterns in all corners except the bottom right one
+        self.draw_finder_pattern(3, 3);
+        self.draw_finder_pattern(size - 4, 3);
+        self.draw_finder_pattern(3, size - 4);
+
+        // Alignment patterns, except where they would overlap the finder patterns
+        let positions = alignment_pattern_positions(self.version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                let corner = (i == 0 || i == last) && (j == 0 || j == last);
+                if !corner || (i == last && j == last) {
+                    self.draw_alignment_pattern(x, y);
+                }
+            }
+        }
+
+        // Reserve the format bits, they are overwritten once the mask is chosen
+        self.draw_format_bits(ecc, 0);
+        self.draw_version();
+    }
+
+    /// Draw a finder pattern including its separator, centered at (`x`, `y`).
+    fn draw_finder_pattern(&mut self, x: usize, y: usize) {
+        for dy in -4..=4_i32 {
+            for dx in -4..=4_i32 {
+                let (xx, yy) = (x as i32 + dx, y as i32 + dy);
+                if (0..self.size as i32).contains(&xx) && (0..self.size as i32).contains(&yy) {
+                    let distance = dx.abs().max(dy.abs());
+                    self.set_function_module(
+                        xx as usize,
+                        yy as usize,
+                        distance != 2 && distance != 4,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Draw an alignment pattern centered at (`x`, `y`).
+    fn draw_alignment_pattern(&mut self, x: usize, y: usize) {
+        for dy in -2..=2_i32 {
+            for dx in -2..=2_i32 {
+                let distance = dx.abs().max(dy.abs());
+                self.set_function_module(
+                    (x as i32 + dx) as usize,
+                    (y as i32 + dy) as usize,
+                    distance != 1,
+                );
+            }
+        }
+    }
+
+    /// Draw both copies of the format information for the given error correction level and mask.
+    fn draw_format_bits(&mut self, ecc: QrErrorCorrection, mask: u8) {
+        let data = ecc.format_bits() << 3 | mask as u32;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = (data << 10 | remainder) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+
+        // First copy, around the top left finder pattern
+        for i in 0..6 {
+            self.set_function_module(8, i, bit(i));
+        }
+        self.set_function_module(8, 7, bit(6));
+        self.set_function_module(8, 8, bit(7));
+        self.set_function_module(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function_module(14 - i, 8, bit(i));
+        }
+
+        // Second copy, split between the other two finder patterns
+        let size = self.size;
+        for i in 0..8 {
+            self.set_function_module(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function_module(8, size - 15 + i, bit(i));
+        }
+        self.set_function_module(8, size - 8, true);
+    }
+
+    /// Draw both copies of the version information, which is only present from version 7 on.
+    fn draw_version(&mut self) {
+        if self.version < 7 {
+            return;
+        }
+        let data = self.version as u32;
+        let mut remainder = data;
+        for _ in 0..12 {
+            remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+        }
+        let bits = data << 12 | remainder;
+        for i in 0..18 {
+            let dark = (bits >> i) & 1 != 0;
+            let a = self.size - 11 + i % 3;
+            let b = i / 3;
+            self.set_function_module(a, b, dark);
+            self.set_function_module(b, a, dark);
+        }
+    }
+
+    /// Place the codewords in the zigzag pattern defined by the standard, skipping function
+    /// modules.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let bits = codewords.view_bits::<Msb0>();
+        let mut i = 0;
+        let mut right = self.size - 1;
+        loop {
+            // Skip the vertical timing pattern
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vertical in 0..self.size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let y = if upward {
+                        self.size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !self.is_function[y * self.size + x] && i < bits.len() {
+                        self.modules.set(x, y, bits[i]);
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// Invert the data modules according to the mask pattern. Applying a mask twice undoes it.
+    fn apply_mask(&mut self, mask: u8) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if invert && !self.is_function[y * self.size + x] {
+                    self.modules.set(x, y, !self.module(x, y));
+                }
+            }
+        }
+    }
+
+    /// Rate how hard the QR code is to scan, as defined by the standard. Used to choose the mask.
+    fn penalty_score(&self) -> i32 {
+        let size = self.size;
+        let mut result = 0;
+
+        // Runs of modules of the same color and finder-like patterns, in rows and columns
+        for transpose in [false, true] {
+            for a in 0..size {
+                let mut run_color = false;
+                let mut run_length = 0;
+                let mut history = RunHistory::new(size as i32);
+                for b in 0..size {
+                    let dark = if transpose {
+                        self.module(a, b)
+                    } else {
+                        self.module(b, a)
+                    };
+                    if dark == run_color {
+                        run_length += 1;
+                        if run_length == 5 {
+                            result += PENALTY_N1;
+                        } else if run_length > 5 {
+                            result += 1;
+                        }
+                    } else {
+                        history.push(run_length);
+                        if !run_color {
+                            result += history.count_patterns() * PENALTY_N3;
+                        }
+                        run_color = dark;
+                        run_length = 1;
+                    }
+                }
+                result += history.terminate_and_count(run_color, run_length) * PENALTY_N3;
+            }
+        }
+
+        // 2x2 blocks of the same color
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let dark = self.module(x, y);
+                if dark == self.module(x + 1, y)
+                    && dark == self.module(x, y + 1)
+                    && dark == self.module(x + 1, y + 1)
+                {
+                    result += PENALTY_N2;
+                }
+            }
+        }
+
+        // Balance of dark and light modules
+        let dark = self.modules.count_dark() as i32;
+        let total = (size * size) as i32;
+        let k = ((dark * 20 - total * 10).abs() + total - 1) / total - 1;
+        result + k * PENALTY_N4
+    }
+}
+
+/// The lengths of the last seven runs of modules in a row or column, used to detect patterns
+/// resembling a finder pattern.
+struct RunHistory {
+    size: i32,
+    runs: [i32; 7],
+}
+
+impl RunHistory {
+    fn new(size: i32) -> RunHistory {
+        RunHistory { size, runs: [0; 7] }
+    }
+
+    fn push(&mut self, mut run_length: i32) {
+        if self.runs[0] == 0 {
+            // The light border before the first run
+            run_length += self.size;
+        }
+        self.runs.copy_within(0..6, 1);
+        self.runs[0] = run_length;
+    }
+
+    /// Count finder-like patterns (1:1:3:1:1 with light runs of at least 4 on one side). Must be
+    /// called right after a light run was pushed.
+    fn count_patterns(&self) -> i32 {
+        let runs = &self.runs;
+        let n = runs[1];
+        let core = n > 0 && runs[2] == n && runs[3] == n * 3 && runs[4] == n && runs[5] == n;
+        i32::from(core && runs[0] >= n * 4 && runs[6] >= n)
+            + i32::from(core && runs[6] >= n * 4 && runs[0] >= n)
+    }
+
+    fn terminate_and_count(mut self, run_color: bool, mut run_length: i32) -> i32 {
+        if run_color {
+            self.push(run_length);
+            run_length = 0;
+        }
+        // The light border after the last run
+        run_length += self.size;
+        self.push(run_length);
+        self.count_patterns()
+    }
+}
+
+/// Positions of the alignment patterns on both axes.
+fn alignment_pattern_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = if version == 32 {
+        26
+    } else {
+        (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+    let size = version * 4 + 17;
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// Number of modules available for data and error correction, including remainder bits.
+fn raw_data_modules(version: usize) -> usize {
+    let mut result = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignment_count = version / 7 + 2;
+        result -= (25 * alignment_count - 10) * alignment_count - 55;
+        if version >= 7 {
+            result -= 36;
+        }
+    }
+    result
+}
+
+/// Number of data codewords (excluding error correction) of a QR code.
+fn data_codewords(version: usize, ecc: QrErrorCorrection) -> usize {
+    raw_data_modules(version) / 8
+        - ECC_CODEWORDS_PER_BLOCK[ecc.ordinal()][version] as usize
+            * NUM_ERROR_CORRECTION_BLOCKS[ecc.ordinal()][version] as usize
+}
+
+/// Encode `data` as a single segment, and pad it to the capacity of the QR code.
+fn data_codewords_for(data: &[u8], mode: Mode, version: usize, ecc: QrErrorCorrection) -> Vec<u8> {
+    let mut bits = BitVec::<u8, Msb0>::new();
+    push_bits(&mut bits, mode.indicator(), 4);
+    push_bits(&mut bits, data.len() as u32, mode.count_bits(version));
+    match mode {
+        Mode::Numeric => {
+            for digits in data.chunks(3) {
+                let value = digits
+                    .iter()
+                    .fold(0, |value, digit| value * 10 + (digit - b'0') as u32);
+                push_bits(&mut bits, value, digits.len() * 3 + 1);
+            }
+        }
+        Mode::Alphanumeric => {
+            let index = |c: &u8| ALPHANUMERIC_CHARSET.iter().position(|a| a == c).unwrap() as u32;
+            for pair in data.chunks(2) {
+                match pair {
+                    [a, b] => push_bits(&mut bits, index(a) * 45 + index(b), 11),
+                    [a] => push_bits(&mut bits, index(a), 6),
+                    _ => unreachable!(),
+                }
+            }
+        }
+        Mode::Byte => {
+            for byte in data {
+                push_bits(&mut bits, *byte as u32, 8);
+            }
+        }
+    }
+
+    // Terminator, then pad to a full byte, then alternate pad bytes up to the capacity
+    let capacity = data_codewords(version, ecc) * 8;
+    let terminator = (capacity - bits.len()).min(4);
+    push_bits(&mut bits, 0, terminator);
+    let padding = (8 - bits.len() % 8) % 8;
+    push_bits(&mut bits, 0, padding);
+    let mut codewords = bits.into_vec();
+    for pad in [0xEC, 0x11].iter().cycle() {
+        if codewords.len() * 8 >= capacity {
+            break;
+        }
+        codewords.push(*pad);
+    }
+    codewords
+}
+
+/// Split the data codewords into blocks, append the error correction codewords to each of them,
+/// and interleave the blocks.
+fn add_ecc_and_interleave(data: &[u8], version: usize, ecc: QrErrorCorrection) -> Vec<u8> {
+    let block_count = NUM_ERROR_CORRECTION_BLOCKS[ecc.ordinal()][version] as usize;
+    let ecc_len = ECC_CODEWORDS_PER_BLOCK[ecc.ordinal()][version] as usize;
+    let raw_codewords = raw_data_modules(version) / 8;
+    let short_block_count = block_count - raw_codewords % block_count;
+    let short_block_len = raw_codewords / block_count;
+
+    // Short blocks get a placeholder byte, so all blocks have the same length
+    let reed_solomon = ReedSolomon::new(0x1D, 0, ecc_len);
+    let mut blocks: Vec<Vec<u8>> = Vec::with_capacity(block_count);
+    let mut start = 0;
+    for i in 0..block_count {
+        let len = short_block_len - ecc_len + usize::from(i >= short_block_count);
+        let mut block = data[start..start + len].to_vec();
+        start += len;
+        let remainder = reed_solomon.remainder(&block);
+        if i < short_block_count {
+            block.push(0);
+        }
+        block.extend_from_slice(&remainder);
+        blocks.push(block);
+    }
+
+    let mut result = Vec::with_capacity(raw_codewords);
+    for i in 0..blocks[0].len() {
+        for (j, block) in blocks.iter().enumerate() {
+            // Skip the placeholder bytes
+            if i != short_block_len - ecc_len || j >= short_block_count {
+                result.push(block[i]);
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,136 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Querying the printer status over the serial port, and reacting to faults reported by the
+//! printer while printing.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::InputPin;
+use embedded_io::{Read as SerialRead, ReadReady, Write as SerialWrite};
+
+use crate::{Printer, PrinterError, ESC, GS};
+
+/// `ESC v 0`: Transmit printer status
+pub(crate) const PRINTER_STATUS_QUERY: [u8; 3] = [ESC, 0x76, 0x00];
+/// `GS r 1`: Transmit paper sensor status
+pub(crate) const PAPER_STATUS_QUERY: [u8; 3] = [GS, 0x72, 0x01];
+/// How long to wait for the printer to answer a status query by default, in microseconds
+pub(crate) const DEFAULT_RESPONSE_TIMEOUT_MICROS: u32 = 500_000;
+/// Interval in which the serial port is polled while waiting for a response, in microseconds
+pub(crate) const RESPONSE_POLL_MICROS: u32 = 1_000;
+/// Number of bitmap rows printed between two status checks (the height of a line of text)
+pub(crate) const STATUS_CHECK_ROWS: u8 = 24;
+
+/// Determines whether the [`Printer`] checks the printer status while printing
+/// long jobs, and how it reacts to faults. The status is checked before each line of text and
+/// each band of a bitmap, which requires a serial port connected to the printer's TX line.
+///
+/// Default: `Disabled`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatusPolicy {
+    /// never check the printer status
+    #[default]
+    Disabled,
+    /// abort the job as soon as the printer reports a fault
+    Abort,
+    /// wait for the fault to be resolved (e.g. for the paper to be replaced), querying the
+    /// printer every `interval` microseconds, and abort after `timeout` microseconds
+    Pause { interval: u32, timeout: u32 },
+}
+
+/// Placeholder for printers which do not check the printer status during long jobs. Being
+/// uninhabited, there is no way to construct it.
+#[derive(Clone, Copy)]
+pub enum NoStatusPolicy {}
+
+/// How a [`Printer`] checks the printer status before each line of text and each band of a
+/// bitmap. Implemented by [`StatusPolicy`], which requires a serial port able to read
+/// the printer's answers, and by [`NoStatusPolicy`].
+pub trait StatusCheck<Port: SerialWrite>: Copy {
+    /// Check the status of `printer`, returning the fault which aborts the job, if any.
+    fn check<Delay: DelayNs, Busy: InputPin>(
+        self,
+        printer: &mut Printer<Port, Delay, Busy, Self>,
+    ) -> Result<(), PrinterError<Port::Error>>;
+}
+
+impl<Port: SerialWrite> StatusCheck<Port> for NoStatusPolicy {
+    fn check<Delay: DelayNs, Busy: InputPin>(
+        self,
+        _: &mut Printer<Port, Delay, Busy, Self>,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        match self {}
+    }
+}
+
+impl<Port: SerialWrite + SerialRead + ReadReady> StatusCheck<Port> for StatusPolicy {
+    fn check<Delay: DelayNs, Busy: InputPin>(
+        self,
+        printer: &mut Printer<Port, Delay, Busy, Self>,
+    ) -> Result<(), PrinterError<Port::Error>> {
+        printer.apply_status_policy(self)
+    }
+}
+
+/// The printer status, as reported by the printer in response to the `ESC v` and `GS r` queries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrinterStatus {
+    /// there is paper in the printer
+    pub paper_present: bool,
+    /// the paper roll is almost used up
+    pub paper_near_end: bool,
+    /// the print head is hotter than 60°C
+    pub overheated: bool,
+    /// the supply voltage is higher than 9.5V
+    pub voltage_out_of_range: bool,
+}
+
+impl PrinterStatus {
+    /// Decode the responses to the printer status (`ESC v 0`) and the paper sensor status
+    /// (`GS r 1`) queries.
+    ///
+    /// | Response       | Bit(s) | Meaning                      |
+    /// |----------------|--------|------------------------------|
+    /// | printer status | 2      | no paper                     |
+    /// | printer status | 3      | voltage higher than 9.5V     |
+    /// | printer status | 6      | temperature higher than 60°C |
+    /// | paper status   | 0, 1   | paper near end               |
+    /// | paper status   | 2, 3   | paper end                    |
+    pub fn from_responses(printer_status: u8, paper_status: u8) -> PrinterStatus {
+        PrinterStatus {
+            paper_present: printer_status & 0b0000_0100 == 0 && paper_status & 0b0000_1100 == 0,
+            paper_near_end: paper_status & 0b0000_0011 != 0,
+            overheated: printer_status & 0b0100_0000 != 0,
+            voltage_out_of_range: printer_status & 0b0000_1000 != 0,
+        }
+    }
+
+    /// Whether the printer is able to print, i.e. there is paper and no fault is reported.
+    pub fn is_ready(&self) -> bool {
+        self.paper_present && !self.overheated && !self.voltage_out_of_range
+    }
+
+    /// Convert a reported fault into the corresponding [`PrinterError`].
+    pub(crate) fn fault<E>(&self) -> Result<(), PrinterError<E>> {
+        if !self.paper_present {
+            Err(PrinterError::PaperOut)
+        } else if self.overheated {
+            Err(PrinterError::Overheat)
+        } else if self.voltage_out_of_range {
+            Err(PrinterError::VoltageOutOfRange)
+        } else {
+            Ok(())
+        }
+    }
+}
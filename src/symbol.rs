@@ -0,0 +1,226 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Two-dimensional symbols which are encoded by the driver and printed as raster images, so they
+//! work regardless of the printer's firmware revision.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use bitvec::prelude::*;
+use derive_builder::Builder;
+
+use crate::datamatrix::encode_data_matrix;
+use crate::qr::encode_qr;
+use crate::{Justification, PrinterError, QrErrorCorrection, DOT_WIDTH};
+
+/// The symbology used to encode a two-dimensional symbol.
+///
+/// Default: `Qr(QrErrorCorrection::Medium)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symbology {
+    /// a QR code (model 2) with the given error correction level, in the smallest version the
+    /// data fits into
+    Qr(QrErrorCorrection),
+    /// an ECC 200 Data Matrix code, in the smallest square size the data fits into
+    DataMatrix,
+}
+
+impl Default for Symbology {
+    fn default() -> Self {
+        Symbology::Qr(QrErrorCorrection::default())
+    }
+}
+
+impl Symbology {
+    /// The width of the quiet zone required by the specification of the symbology, in modules.
+    pub(crate) fn quiet_zone(self) -> u8 {
+        match self {
+            Symbology::Qr(_) => 4,
+            Symbology::DataMatrix => 1,
+        }
+    }
+}
+
+/// Configures how a two-dimensional symbol is printed.
+///
+/// Prefer to use [`SymbolOptionsBuilder`] to construct.
+#[derive(Builder, Clone, Copy, Debug)]
+#[builder(default, setter(into), no_std)]
+pub struct SymbolOptions {
+    /// the [`Symbology`] to be used
+    pub symbology: Symbology,
+    /// the width and height of a single module in dots. Default: 3
+    pub scale: u8,
+    /// the width of the light border around the symbol in modules. Default: the minimum required
+    /// by the symbology
+    #[builder(setter(strip_option))]
+    pub quiet_zone: Option<u8>,
+    /// the horizontal position of the symbol
+    pub justification: Justification,
+}
+
+impl Default for SymbolOptions {
+    fn default() -> Self {
+        SymbolOptions {
+            symbology: Symbology::default(),
+            scale: 3,
+            quiet_zone: None,
+            justification: Justification::default(),
+        }
+    }
+}
+
+/// A square matrix of dark and light modules.
+pub(crate) struct ModuleMatrix {
+    size: usize,
+    modules: BitVec,
+}
+
+impl ModuleMatrix {
+    /// Create a matrix of `size` by `size` light modules.
+    pub(crate) fn new(size: usize) -> ModuleMatrix {
+        ModuleMatrix {
+            size,
+            modules: bitvec![0; size * size],
+        }
+    }
+
+    /// Whether the module at the given position is dark.
+    pub(crate) fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    pub(crate) fn set(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules.set(y * self.size + x, dark);
+    }
+
+    /// The number of dark modules.
+    pub(crate) fn count_dark(&self) -> usize {
+        self.modules.count_ones()
+    }
+
+    /// The width and height of the printed matrix including its quiet zone, in dots.
+    pub(crate) fn dots(&self, scale: u8, quiet_zone: u8) -> usize {
+        (self.size + quiet_zone as usize * 2) * scale as usize
+    }
+
+    /// Convert the matrix into raster bit image data, scaling each module to `scale` dots and
+    /// surrounding it by `quiet_zone` light modules. Also returns the number of bytes per row.
+    ///
+    /// The matrix must fit onto the paper, which [`encode_symbol`] ensures.
+    pub(crate) fn raster(&self, scale: u8, quiet_zone: u8) -> (Vec<u8>, usize) {
+        let dots = self.dots(scale, quiet_zone);
+        let x_bytes = dots.div_ceil(8);
+        let (scale, quiet_zone) = (scale as usize, quiet_zone as usize);
+        let symbol = quiet_zone..quiet_zone + self.size;
+
+        let mut image_bits = bitvec![u8, Msb0;];
+        for y in 0..dots {
+            for x in 0..x_bytes * 8 {
+                let (module_x, module_y) = (x / scale, y / scale);
+                image_bits.push(
+                    symbol.contains(&module_x)
+                        && symbol.contains(&module_y)
+                        && self.get(module_x - quiet_zone, module_y - quiet_zone),
+                );
+            }
+        }
+        (image_bits.into_vec(), x_bytes)
+    }
+}
+
+/// Encode `data` according to `options`, checking that the symbol fits onto the paper. Also
+/// returns the width of the quiet zone in modules.
+pub(crate) fn encode_symbol<E>(
+    data: &[u8],
+    options: &SymbolOptions,
+) -> Result<(ModuleMatrix, u8), PrinterError<E>> {
+    if options.scale == 0 {
+        return Err(PrinterError::InvalidArgument(
+            "symbol scale must not be zero",
+        ));
+    }
+    let matrix = match options.symbology {
+        Symbology::Qr(error_correction) => encode_qr(data, error_correction),
+        Symbology::DataMatrix => encode_data_matrix(data),
+    }
+    .ok_or(PrinterError::InvalidArgument("symbol data too long"))?;
+
+    let quiet_zone = options
+        .quiet_zone
+        .unwrap_or_else(|| options.symbology.quiet_zone());
+    if matrix.dots(options.scale, quiet_zone) > DOT_WIDTH as usize {
+        return Err(PrinterError::InvalidArgument("symbol too wide"));
+    }
+    Ok((matrix, quiet_zone))
+}
+
+/// A Reed-Solomon code over GF(2^8), used for the error correction of two-dimensional symbols.
+pub(crate) struct ReedSolomon {
+    /// The field's reduction polynomial, without the x^8 term
+    polynomial: u8,
+    /// The generator polynomial, highest degree first, without the leading term
+    divisor: Vec<u8>,
+}
+
+impl ReedSolomon {
+    /// Create a code producing `degree` error correction codewords, whose generator polynomial
+    /// has the roots 2^`first_root` to 2^(`first_root` + `degree` - 1).
+    pub(crate) fn new(polynomial: u8, first_root: u32, degree: usize) -> ReedSolomon {
+        let mut divisor = vec![0; degree];
+        divisor[degree - 1] = 1;
+        let mut root = 1;
+        for _ in 0..first_root {
+            root = gf_multiply(root, 0x02, polynomial);
+        }
+        for _ in 0..degree {
+            // Multiply the polynomial by (x - root)
+            for j in 0..degree {
+                divisor[j] = gf_multiply(divisor[j], root, polynomial);
+                if j + 1 < degree {
+                    divisor[j] ^= divisor[j + 1];
+                }
+            }
+            root = gf_multiply(root, 0x02, polynomial);
+        }
+        ReedSolomon {
+            polynomial,
+            divisor,
+        }
+    }
+
+    /// The error correction codewords for `data`.
+    pub(crate) fn remainder(&self, data: &[u8]) -> Vec<u8> {
+        let mut result = vec![0; self.divisor.len()];
+        for byte in data {
+            let factor = byte ^ result.remove(0);
+            result.push(0);
+            for (x, y) in result.iter_mut().zip(&self.divisor) {
+                *x ^= gf_multiply(*y, factor, self.polynomial);
+            }
+        }
+        result
+    }
+}
+
+/// Multiply two elements of GF(2^8) modulo x^8 + `polynomial`.
+fn gf_multiply(x: u8, y: u8, polynomial: u8) -> u8 {
+    let mut z: u8 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * polynomial);
+        z ^= ((y >> i) & 1) * x;
+    }
+    z
+}
@@ -0,0 +1,145 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::ops::RangeInclusive;
+
+use crate::{Bitmap, Font, PrinterError, ESC};
+
+/// Character codes which can be replaced by user-defined characters
+const USER_CHARACTER_CODES: RangeInclusive<u8> = 0x20..=0x7E;
+/// Number of bytes per column of a user-defined character
+const COLUMN_BYTES: usize = 3;
+
+/// The bitmap of a user-defined character, for one of the fonts. See
+/// [`Printer::define_user_characters`](crate::Printer::define_user_characters).
+///
+/// Glyphs of font A are 12 dots wide and 24 dots tall, glyphs of font B 9 dots wide and 17 dots
+/// tall.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserGlyph {
+    font: Font,
+    /// The dots of each column, top to bottom, MSB first
+    columns: Vec<u8>,
+}
+
+impl UserGlyph {
+    /// Create a glyph for `font` from a bitmap of its full size, row by row, each row padded to
+    /// two bytes with the leftmost dot in the MSB. Returns `None` unless `bitmap` contains 48 bytes
+    /// for font A or 34 bytes for font B.
+    ///
+    /// # Example
+    /// A check mark for font B:
+    /// ```
+    /// # use thermal_print::{Font, UserGlyph};
+    /// let mut bitmap = [0; 34];
+    /// for (row, dots) in [(6, 0x01), (7, 0x03), (8, 0x86), (9, 0xCC), (10, 0x78), (11, 0x30)] {
+    ///     bitmap[row * 2] = dots;
+    /// }
+    /// assert!(UserGlyph::from_bitmap(Font::FontB, &bitmap).is_some());
+    /// ```
+    pub fn from_bitmap(font: Font, bitmap: &[u8]) -> Option<UserGlyph> {
+        let (width, height) = glyph_size(font);
+        if bitmap.len() != height * 2 {
+            return None;
+        }
+        Some(UserGlyph::from_dots(font, |x, y| {
+            x < width && y < height && bitmap[y * 2 + x / 8] & (0x80 >> (x % 8)) != 0
+        }))
+    }
+
+    /// Create a glyph for `font` from a bitmap image, which must not be larger than a glyph of the
+    /// font. Smaller images are placed at the top left. Pixels darker than mid gray are printed,
+    /// as for [`Printer::print_bitmap`](crate::Printer::print_bitmap) with the default
+    /// [`Dithering`](crate::Dithering).
+    pub fn from_bmp<'a>(font: Font, bmp: impl Into<Bitmap<'a>>) -> Option<UserGlyph> {
+        let bmp = bmp.into();
+        let (width, height) = glyph_size(font);
+        if bmp.width() > width || bmp.height() > height {
+            return None;
+        }
+        let gray = bmp.luminance();
+        Some(UserGlyph::from_dots(font, |x, y| {
+            x < bmp.width() && y < bmp.height() && gray[y * bmp.width() + x] < 128
+        }))
+    }
+
+    fn from_dots(font: Font, dot: impl Fn(usize, usize) -> bool) -> UserGlyph {
+        let (width, height) = glyph_size(font);
+        let mut columns = vec![0; width * COLUMN_BYTES];
+        for x in 0..width {
+            for y in 0..height {
+                if dot(x, y) {
+                    columns[x * COLUMN_BYTES + y / 8] |= 0x80 >> (y % 8);
+                }
+            }
+        }
+        UserGlyph { font, columns }
+    }
+
+    /// The font the glyph is defined for.
+    pub fn font(&self) -> Font {
+        self.font
+    }
+}
+
+/// The width and height of a user-defined character of `font`, in dots.
+fn glyph_size(font: Font) -> (usize, usize) {
+    match font {
+        Font::FontA => (12, 24),
+        Font::FontB => (9, 17),
+    }
+}
+
+/// The `ESC &` command defining `glyphs` as the characters starting at the code `first`, for the
+/// selected `font`.
+pub(crate) fn define_user_characters_command<E>(
+    first: u8,
+    glyphs: &[UserGlyph],
+    font: Font,
+) -> Result<Vec<u8>, PrinterError<E>> {
+    let last = (first as usize + glyphs.len()).saturating_sub(1);
+    if glyphs.is_empty()
+        || !USER_CHARACTER_CODES.contains(&first)
+        || last > *USER_CHARACTER_CODES.end() as usize
+    {
+        return Err(PrinterError::InvalidArgument(
+            "user-defined character codes out of range",
+        ));
+    }
+    if glyphs.iter().any(|glyph| glyph.font != font) {
+        return Err(PrinterError::InvalidArgument(
+            "user-defined characters must match the selected font",
+        ));
+    }
+
+    let mut command = vec![ESC, 0x26, COLUMN_BYTES as u8, first, last as u8];
+    for glyph in glyphs {
+        command.push((glyph.columns.len() / COLUMN_BYTES) as u8);
+        command.extend_from_slice(&glyph.columns);
+    }
+    Ok(command)
+}
+
+/// The `ESC ?` command cancelling the user-defined character `code`.
+pub(crate) fn cancel_user_character_command<E>(code: u8) -> Result<[u8; 3], PrinterError<E>> {
+    if USER_CHARACTER_CODES.contains(&code) {
+        Ok([ESC, 0x3F, code])
+    } else {
+        Err(PrinterError::InvalidArgument(
+            "user-defined character code out of range",
+        ))
+    }
+}
@@ -0,0 +1,62 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Checks that the AsyncPrinter sends the same data as the Printer, and waits as long.
+
+#![cfg(feature = "async")]
+
+mod common;
+
+use core::fmt::Write;
+
+use common::{block_on, printer, Clock, Recorder};
+use thermal_print::{AsyncPrinter, Justification, RasterBitImageMode};
+
+/// A 16x3 image with a diagonal line
+const RASTER: [u8; 6] = [0x80, 0x00, 0x40, 0x00, 0x20, 0x00];
+
+#[test]
+fn sends_the_same_data_as_the_printer() {
+    let mut expected = printer();
+    expected.init().unwrap();
+    expected.set_justification(Justification::Center).unwrap();
+    expected
+        .write_str("Hello, world! This line is long enough to be wrapped.\n")
+        .unwrap();
+    expected
+        .print_raster(16, 3, &RASTER, RasterBitImageMode::Normal)
+        .unwrap();
+    expected.feed_n(2).unwrap();
+
+    let mut printer = AsyncPrinter::new(Recorder::default(), Clock::default());
+    block_on(async {
+        printer.init().await.unwrap();
+        printer
+            .set_justification(Justification::Center)
+            .await
+            .unwrap();
+        printer
+            .write_str("Hello, world! This line is long enough to be wrapped.\n")
+            .await
+            .unwrap();
+        printer
+            .print_raster(16, 3, &RASTER, RasterBitImageMode::Normal)
+            .await
+            .unwrap();
+        printer.feed_n(2).await.unwrap();
+    });
+
+    assert_eq!(printer.serial.0, expected.serial.0);
+    assert_eq!(printer.delay.0, expected.delay.0);
+}
@@ -0,0 +1,321 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Validates the text of UPC, EAN, Code39, ITF, Codabar and Code93 barcodes, checks the commands
+//! configuring the barcode style, and checks the `GS k` commands sent for Code128
+//! barcodes against their shortest encodings.
+
+mod common;
+
+use common::{Clock, Recorder};
+use thermal_print::{
+    check_digit, BarCodeSystem, BarcodeError, BarcodeStyleBuilder, BarcodeWidth, Code128, Font,
+    HriPosition, Printer, TimingProfileBuilder,
+};
+
+const GS: u8 = 0x1D;
+/// `GS h`, `GS w`, `GS x`, `GS H` and `GS f` with the default barcode style
+const DEFAULT_STYLE: [u8; 15] = [
+    GS, b'h', 162, GS, b'w', 3, GS, b'x', 0, GS, b'H', 0, GS, b'f', 0,
+];
+
+#[test]
+fn appends_or_verifies_check_digits() {
+    let cases = [
+        (BarCodeSystem::UpcA, "03600029145", "036000291452"),
+        (BarCodeSystem::UpcA, "036000291452", "036000291452"),
+        (BarCodeSystem::UpcE, "04210000526", "042100005264"),
+        (BarCodeSystem::UpcE, "042100005264", "042100005264"),
+        (BarCodeSystem::Ean13, "400638133393", "4006381333931"),
+        (BarCodeSystem::Ean13, "4006381333931", "4006381333931"),
+        (BarCodeSystem::Ean8, "9638507", "96385074"),
+        (BarCodeSystem::Ean8, "96385074", "96385074"),
+    ];
+    for (system, text, payload) in cases {
+        assert_eq!(
+            system.payload(text),
+            Ok(payload.as_bytes().to_vec()),
+            "{}",
+            text
+        );
+    }
+    assert_eq!(check_digit("9638507"), Some('4'));
+    assert_eq!(check_digit("96385O7"), None);
+}
+
+#[test]
+fn rejects_invalid_upc_and_ean_numbers() {
+    let cases = [
+        // Too short or too long
+        (
+            BarCodeSystem::UpcA,
+            "0360002914",
+            BarcodeError::InvalidLength(10),
+        ),
+        (
+            BarCodeSystem::UpcA,
+            "0360002914520",
+            BarcodeError::InvalidLength(13),
+        ),
+        (
+            BarCodeSystem::Ean13,
+            "40063813339",
+            BarcodeError::InvalidLength(11),
+        ),
+        (
+            BarCodeSystem::Ean8,
+            "963850745",
+            BarcodeError::InvalidLength(9),
+        ),
+        (BarCodeSystem::Ean8, "", BarcodeError::InvalidLength(0)),
+        // Wrong check digit, reporting the correct one
+        (
+            BarCodeSystem::UpcA,
+            "036000291453",
+            BarcodeError::InvalidCheckDigit('2'),
+        ),
+        (
+            BarCodeSystem::UpcE,
+            "042100005260",
+            BarcodeError::InvalidCheckDigit('4'),
+        ),
+        (
+            BarCodeSystem::Ean13,
+            "4006381333930",
+            BarcodeError::InvalidCheckDigit('1'),
+        ),
+        (
+            BarCodeSystem::Ean8,
+            "96385070",
+            BarcodeError::InvalidCheckDigit('4'),
+        ),
+        // Anything but ASCII digits, at its byte position
+        (
+            BarCodeSystem::UpcA,
+            "03600A29145",
+            BarcodeError::InvalidCharacter {
+                position: 5,
+                character: 'A',
+            },
+        ),
+        (
+            BarCodeSystem::Ean8,
+            "963 507",
+            BarcodeError::InvalidCharacter {
+                position: 3,
+                character: ' ',
+            },
+        ),
+        (
+            BarCodeSystem::Ean13,
+            "40063813339\u{661}",
+            BarcodeError::InvalidCharacter {
+                position: 11,
+                character: '\u{661}',
+            },
+        ),
+        // UPC-A numbers without enough zeros, or not starting with 0 or 1
+        (
+            BarCodeSystem::UpcE,
+            "03600029145",
+            BarcodeError::NotCompressible,
+        ),
+        (
+            BarCodeSystem::UpcE,
+            "24210000526",
+            BarcodeError::NotCompressible,
+        ),
+    ];
+    for (system, text, error) in cases {
+        assert_eq!(system.payload(text), Err(error), "{}", text);
+    }
+}
+
+#[test]
+fn accepts_valid_texts() {
+    let cases = [
+        (BarCodeSystem::Code39, "CODE-39 $12.50/%+"),
+        (BarCodeSystem::Code39, "*ABC*"),
+        (BarCodeSystem::Code39, "ABC*"),
+        (BarCodeSystem::Itf, "12"),
+        (BarCodeSystem::Itf, "00123456"),
+        (BarCodeSystem::Codabar, "A40156B"),
+        (BarCodeSystem::Codabar, "c$+-./:0d"),
+        (BarCodeSystem::Codabar, "AB"),
+        (BarCodeSystem::Code93, "Code 93 {~}\x7F"),
+    ];
+    for (system, text) in cases {
+        assert_eq!(
+            system.payload(text),
+            Ok(text.as_bytes().to_vec()),
+            "{}",
+            text
+        );
+    }
+}
+
+#[test]
+fn rejects_invalid_texts() {
+    let invalid = |position, character| BarcodeError::InvalidCharacter {
+        position,
+        character,
+    };
+    let cases = [
+        // Code39 has no lower case letters
+        (BarCodeSystem::Code39, "Code39", invalid(1, 'o')),
+        (BarCodeSystem::Code39, "A_B", invalid(1, '_')),
+        // `*` only starts and stops the symbol
+        (BarCodeSystem::Code39, "AB*CD", invalid(2, '*')),
+        (BarCodeSystem::Code39, "*AB*CD*", invalid(3, '*')),
+        (BarCodeSystem::Code39, "", BarcodeError::InvalidLength(0)),
+        // ITF encodes pairs of digits
+        (BarCodeSystem::Itf, "123", BarcodeError::InvalidLength(3)),
+        (BarCodeSystem::Itf, "1", BarcodeError::InvalidLength(1)),
+        (BarCodeSystem::Itf, "12A4", invalid(2, 'A')),
+        // Codabar needs start and stop characters, and only allows them at the ends
+        (
+            BarCodeSystem::Codabar,
+            "40156",
+            BarcodeError::MissingStartStop,
+        ),
+        (
+            BarCodeSystem::Codabar,
+            "A40156",
+            BarcodeError::MissingStartStop,
+        ),
+        (
+            BarCodeSystem::Codabar,
+            "40156B",
+            BarcodeError::MissingStartStop,
+        ),
+        (
+            BarCodeSystem::Codabar,
+            "E40156E",
+            BarcodeError::MissingStartStop,
+        ),
+        (BarCodeSystem::Codabar, "A401C56B", invalid(4, 'C')),
+        (BarCodeSystem::Codabar, "A", BarcodeError::InvalidLength(1)),
+        // Code93 is limited to ASCII
+        (BarCodeSystem::Code93, "Straße", invalid(4, 'ß')),
+        (BarCodeSystem::Code93, "", BarcodeError::InvalidLength(0)),
+    ];
+    for (system, text, error) in cases {
+        assert_eq!(system.payload(text), Err(error), "{}", text);
+    }
+}
+
+#[test]
+fn sends_the_barcode_style() {
+    let mut printer = common::printer();
+    let style = BarcodeStyleBuilder::default()
+        .height(80)
+        .width(BarcodeWidth::Width2)
+        .left_space(10)
+        .text_position(HriPosition::Below)
+        .text_font(Font::FontB)
+        .build()
+        .unwrap();
+    printer.set_barcode_style(style).unwrap();
+    assert_eq!(
+        printer.serial.0,
+        [GS, b'h', 80, GS, b'w', 2, GS, b'x', 10, GS, b'H', 2, GS, b'f', 1]
+    );
+
+    printer.serial.0.clear();
+    printer
+        .set_barcode_text_position(HriPosition::Both)
+        .unwrap();
+    printer.set_barcode_text_font(Font::FontA).unwrap();
+    assert_eq!(printer.serial.0, [GS, b'H', 3, GS, b'f', 0]);
+}
+
+#[test]
+fn resets_the_barcode_style() {
+    // Receiving data takes no time at this baud rate, so only printing rows is paced
+    let timing = TimingProfileBuilder::default()
+        .baud_rate(u32::MAX)
+        .dot_print_time(1000u32)
+        .dot_feed_time(2000u32)
+        .build()
+        .unwrap();
+    let mut printer = Printer::with_timing(Recorder::default(), Clock::default(), timing);
+    printer.set_barcode_height(100).unwrap();
+    printer
+        .set_barcode_text_position(HriPosition::Both)
+        .unwrap();
+    printer.set_barcode_text_font(Font::FontB).unwrap();
+    printer.print_barcode(BarCodeSystem::Code39, "ABC").unwrap();
+    // 100 rows of bars, and 17 rows of text above and below
+    assert_eq!(printer.delay.0, (100 + 2 * 17) * 3000 * 1000);
+
+    printer.reset().unwrap();
+    assert!(printer.serial.0.ends_with(&DEFAULT_STYLE));
+    printer.delay.0 = 0;
+    printer.print_barcode(BarCodeSystem::Code39, "ABC").unwrap();
+    // No text by default
+    assert_eq!(printer.delay.0, 162 * 3000 * 1000);
+}
+
+/// Print `barcode` and check that the printer is sent `GS k` with the Code128 `text`.
+fn assert_code128(barcode: &Code128, text: &[u8]) {
+    let mut printer = common::printer();
+    printer.print_code128(barcode).unwrap();
+    let mut expected = vec![0x1D, 0x6B, 73, text.len() as u8];
+    expected.extend_from_slice(text);
+    assert_eq!(printer.serial.0, expected);
+}
+
+#[test]
+fn encodes_digits_in_code_set_c() {
+    assert_code128(
+        Code128::new().text("0123456789"),
+        &[b'{', b'C', 1, 23, 45, 67, 89],
+    );
+}
+
+#[test]
+fn switches_code_sets_in_mixed_text() {
+    let cases: [(&str, &[u8]); 6] = [
+        // Six digits are shorter in code set C, even with two switches
+        ("ab123456cd", b"{Bab{C\x0C\x22\x38{Bcd"),
+        // Odd-length runs of digits leave the digit next to the other characters in code set B
+        ("1234567ab", b"{C\x0C\x22\x38{B7ab"),
+        ("ab1234567", b"{Bab1{C\x17\x2D\x43"),
+        // Switching to code set A for several control characters, shifting for a single one
+        ("\t\nab", b"{A\t\n{Bab"),
+        ("a\tb", b"{Ba{S\tb"),
+        ("a{b", b"{Ba{{b"),
+    ];
+    for (text, encoded) in cases {
+        assert_code128(Code128::new().text(text), encoded);
+    }
+}
+
+#[test]
+fn encodes_gs1_128() {
+    // GTIN (AI 01), expiry date (AI 17) and a variable-length batch number (AI 10)
+    let mut barcode = Code128::new();
+    barcode
+        .fnc1()
+        .text("0109501101530003")
+        .text("17261231")
+        .text("10abc");
+    assert_code128(
+        &barcode,
+        &[
+            b'{', b'C', b'{', b'1', 1, 9, 50, 11, 1, 53, 0, 3, 17, 26, 12, 31, 10, b'{', b'B',
+            b'a', b'b', b'c',
+        ],
+    );
+}
@@ -0,0 +1,494 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Prints the fixture images in `tests/fixtures`, which all show the same 12x6 pattern in
+//! different formats, and checks the raster data sent to the printer.
+
+mod common;
+
+use core::convert::Infallible;
+
+use common::{Clock, Recorder};
+use thermal_print::{
+    Bitmap, BitmapOptions, BitmapOptionsBuilder, BmpError, Dithering, GrayImage, ImageError,
+    Justification, Printer, PrinterError, RasterBitImageMode, Resampling, Rotation, Scaling,
+    TimingProfileBuilder,
+};
+use tinybmp::RawBmp;
+
+const WIDTH: usize = 12;
+const HEIGHT: usize = 6;
+/// `GS v 0` for the fixtures in normal mode: 2 bytes per row, 6 rows
+const RASTER_HEADER: [u8; 8] = [0x1D, 0x76, 0, 0, 2, 0, HEIGHT as u8, 0];
+
+/// Print `bmp` with the default options, returning the data sent and the time waited in ns.
+fn print<'a>(bmp: impl Into<Bitmap<'a>>) -> (Vec<u8>, u64) {
+    print_with(bmp, BitmapOptions::default()).unwrap()
+}
+
+/// Print `bmp` with `options`, returning the data sent and the time waited in ns.
+fn print_with<'a>(
+    bmp: impl Into<Bitmap<'a>>,
+    options: BitmapOptions,
+) -> Result<(Vec<u8>, u64), PrinterError<Infallible>> {
+    // Receiving data takes no time at this baud rate, so only printing rows is paced
+    let timing = TimingProfileBuilder::default()
+        .baud_rate(u32::MAX)
+        .dot_print_time(1000u32)
+        .dot_feed_time(2000u32)
+        .build()
+        .unwrap();
+    let mut printer = Printer::with_timing(Recorder::default(), Clock::default(), timing);
+    printer.print_bitmap(bmp, RasterBitImageMode::Normal, options)?;
+    Ok((printer.serial.0, printer.delay.0))
+}
+
+/// Whether the pixel at `x`, `y` of the pattern shown by all fixtures is dark.
+fn pattern(x: usize, y: usize) -> bool {
+    (x + 2 * y) % 5 == 0 || x == y
+}
+
+/// The raster data of the pattern shown by all fixtures.
+fn expected_raster() -> Vec<u8> {
+    raster(WIDTH, HEIGHT, pattern)
+}
+
+/// The raster data of an image of `width` and `height`, sent in a single band, whose dots are
+/// selected by `dot`.
+fn raster(width: usize, height: usize, dot: impl Fn(usize, usize) -> bool) -> Vec<u8> {
+    let x_bytes = width.div_ceil(8);
+    let mut raster = vec![0x1D, 0x76, 0, 0, x_bytes as u8, 0, height as u8, 0];
+    for y in 0..height {
+        let mut row = vec![0u8; x_bytes];
+        for x in (0..width).filter(|x| dot(*x, y)) {
+            row[x / 8] |= 0x80 >> (x % 8);
+        }
+        raster.extend_from_slice(&row);
+    }
+    raster
+}
+
+/// A bottom-up BMP with 1 bit per pixel and a black and white color table, in which every
+/// second row is black.
+fn striped_bmp(width: usize, height: usize) -> Vec<u8> {
+    let row_bytes = width.div_ceil(32) * 4;
+    let data_start = 14 + 40 + 8;
+    let mut bmp = b"BM".to_vec();
+    for value in [
+        (data_start + row_bytes * height) as u32,
+        0,
+        data_start as u32,
+    ] {
+        bmp.extend_from_slice(&value.to_le_bytes());
+    }
+    for value in [40, width as u32, height as u32] {
+        bmp.extend_from_slice(&value.to_le_bytes());
+    }
+    bmp.extend_from_slice(&[1, 0, 1, 0]);
+    bmp.extend_from_slice(&[0; 24]);
+    bmp.extend_from_slice(&[0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0]);
+    for y in (0..height).rev() {
+        let fill = if y % 2 == 0 { 0x00 } else { 0xFF };
+        bmp.extend(core::iter::repeat(fill).take(row_bytes));
+    }
+    bmp
+}
+
+#[test]
+fn prints_all_bit_depths() {
+    let fixtures: [(&str, &[u8]); 11] = [
+        ("1bit", include_bytes!("fixtures/1bit.bmp")),
+        (
+            "1bit-inverted",
+            include_bytes!("fixtures/1bit-inverted.bmp"),
+        ),
+        ("4bit", include_bytes!("fixtures/4bit.bmp")),
+        ("8bit", include_bytes!("fixtures/8bit.bmp")),
+        ("16bit-555", include_bytes!("fixtures/16bit-555.bmp")),
+        ("16bit-565", include_bytes!("fixtures/16bit-565.bmp")),
+        ("24bit", include_bytes!("fixtures/24bit.bmp")),
+        (
+            "24bit-top-down",
+            include_bytes!("fixtures/24bit-top-down.bmp"),
+        ),
+        ("32bit", include_bytes!("fixtures/32bit.bmp")),
+        ("32bit-alpha", include_bytes!("fixtures/32bit-alpha.bmp")),
+        (
+            "32bit-wide-masks",
+            include_bytes!("fixtures/32bit-wide-masks.bmp"),
+        ),
+    ];
+    for (name, bytes) in fixtures {
+        let bmp = Bitmap::from_slice(bytes).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        assert_eq!((bmp.width(), bmp.height()), (WIDTH, HEIGHT), "{}", name);
+        assert_eq!(print(bmp).0, expected_raster(), "{}", name);
+    }
+}
+
+#[test]
+fn prints_raw_bmp() {
+    for bytes in [
+        &include_bytes!("fixtures/1bit.bmp")[..],
+        include_bytes!("fixtures/24bit.bmp"),
+        include_bytes!("fixtures/24bit-top-down.bmp"),
+    ] {
+        assert_eq!(
+            print(RawBmp::from_slice(bytes).unwrap()).0,
+            expected_raster()
+        );
+    }
+}
+
+#[test]
+fn prints_raw_rows() {
+    let expected = expected_raster();
+    let rows: Vec<&[u8]> = expected[RASTER_HEADER.len()..].chunks(2).collect();
+    // Bits beyond the width must not be printed
+    let padded: Vec<u8> = rows
+        .iter()
+        .flat_map(|row| [row[0], row[1] | 0x0F])
+        .collect();
+
+    let mut printer = common::printer();
+    printer
+        .print_raster(WIDTH, HEIGHT, &padded, RasterBitImageMode::Normal)
+        .unwrap();
+    printer
+        .print_raster_iter(WIDTH, padded.chunks(2), RasterBitImageMode::Normal)
+        .unwrap();
+    assert_eq!(printer.serial.0, [&expected[..], &expected].concat());
+
+    assert!(printer
+        .print_raster(WIDTH, HEIGHT + 1, &padded, RasterBitImageMode::Normal)
+        .is_err());
+    assert!(printer
+        .print_raster_iter(WIDTH, [[0u8; 3]], RasterBitImageMode::Normal)
+        .is_err());
+    assert!(printer
+        .print_raster_iter(385, [[0u8; 49]], RasterBitImageMode::Normal)
+        .is_err());
+}
+
+#[test]
+fn rejects_unsupported_files() {
+    let bmp = include_bytes!("fixtures/8bit.bmp");
+    assert_eq!(
+        Bitmap::from_slice(&bmp[..bmp.len() - 1]),
+        Err(BmpError::Malformed)
+    );
+
+    let mut rle = bmp.to_vec();
+    rle[30] = 1;
+    assert_eq!(
+        Bitmap::from_slice(&rle),
+        Err(BmpError::UnsupportedCompression(1))
+    );
+
+    let mut two_bit = include_bytes!("fixtures/1bit.bmp").to_vec();
+    two_bit[28] = 2;
+    assert_eq!(
+        Bitmap::from_slice(&two_bit),
+        Err(BmpError::UnsupportedBitDepth(2))
+    );
+
+    // The color table would start beyond the end of the address space of 32 bit targets
+    let mut header = bmp.to_vec();
+    header[14..18].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(Bitmap::from_slice(&header), Err(BmpError::Malformed));
+
+    // The size of the pixel data does not fit in memory
+    let mut huge = include_bytes!("fixtures/24bit.bmp").to_vec();
+    huge[18..22].copy_from_slice(&i32::MAX.to_le_bytes());
+    huge[22..26].copy_from_slice(&i32::MIN.wrapping_add(1).to_le_bytes());
+    assert_eq!(Bitmap::from_slice(&huge), Err(BmpError::Malformed));
+}
+
+#[test]
+fn prints_tall_images_in_bands() {
+    let bmp = striped_bmp(384, 300);
+    let (data, _) = print(Bitmap::from_slice(&bmp).unwrap());
+
+    // Bands of at most 4096 bytes: 85 rows of 48 bytes each
+    let mut rows = Vec::new();
+    let mut rest = &data[..];
+    while !rest.is_empty() {
+        assert_eq!(rest[..4], [0x1D, 0x76, 0, 0]);
+        assert_eq!(u16::from_le_bytes([rest[4], rest[5]]), 48);
+        let band_rows = u16::from_le_bytes([rest[6], rest[7]]) as usize;
+        assert!(band_rows <= 85);
+        rows.extend(rest[8..8 + band_rows * 48].chunks(48));
+        rest = &rest[8 + band_rows * 48..];
+    }
+    assert_eq!(rows.len(), 300);
+    for (y, row) in rows.iter().enumerate() {
+        let fill = if y % 2 == 0 { 0xFF } else { 0x00 };
+        assert!(row.iter().all(|byte| *byte == fill), "row {}", y);
+    }
+}
+
+#[test]
+fn paces_every_row() {
+    let (_, delay) = print(Bitmap::from_slice(&striped_bmp(8, 700)).unwrap());
+    assert_eq!(delay, 700 * 3000 * 1000);
+}
+
+#[test]
+fn prints_luminance() {
+    let luminance = expected_raster()[RASTER_HEADER.len()..]
+        .chunks(2)
+        .flat_map(|row| (0..WIDTH).map(move |x| row[x / 8] << (x % 8) & 0x80))
+        .map(|dot| if dot != 0 { 30 } else { 220 })
+        .collect();
+    let image = GrayImage::from_luminance(WIDTH, HEIGHT, luminance).unwrap();
+    assert_eq!(print(&image).0, expected_raster());
+    assert_eq!(
+        GrayImage::from_luminance(WIDTH, HEIGHT, vec![0; 3]),
+        Err(ImageError::Malformed)
+    );
+}
+
+#[test]
+fn dithers_gray_levels() {
+    // A ramp from black to almost white, repeated on 4 rows
+    let luminance = (0..4).flat_map(|_| (0..8).map(|x| x * 36)).collect();
+    let ramp = GrayImage::from_luminance(8, 4, luminance).unwrap();
+    let dither = |dithering| {
+        let options = BitmapOptionsBuilder::default()
+            .dithering(dithering)
+            .build()
+            .unwrap();
+        let (data, _) = print_with(&ramp, options).unwrap();
+        common::raster_images(&data).remove(0)
+    };
+
+    // Every level below 200: 0 to 180
+    assert_eq!(
+        dither(Dithering::Threshold(200)),
+        ["######..", "######..", "######..", "######.."]
+    );
+    // The error of each pixel is passed on to the right and the row below
+    assert_eq!(
+        dither(Dithering::FloydSteinberg),
+        ["###.#...", "###.#...", "###.#...", "##.#..#."]
+    );
+    // Each pixel is compared with its cell of the matrix, scaled to 8, 24, ..., 248
+    assert_eq!(
+        dither(Dithering::Bayer),
+        ["##.#....", "###.#.#.", "##.#.#..", "###.#..."]
+    );
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn prints_png() {
+    let fixtures: [(&str, &[u8]); 4] = [
+        ("1bit", include_bytes!("fixtures/1bit.png")),
+        ("indexed-trns", include_bytes!("fixtures/indexed-trns.png")),
+        ("16bit-rgba", include_bytes!("fixtures/16bit-rgba.png")),
+        (
+            "rgb-interlaced",
+            include_bytes!("fixtures/rgb-interlaced.png"),
+        ),
+    ];
+    for (name, bytes) in fixtures {
+        let image = GrayImage::from_png(bytes).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        assert_eq!((image.width(), image.height()), (WIDTH, HEIGHT), "{}", name);
+        assert_eq!(print(&image).0, expected_raster(), "{}", name);
+    }
+
+    let png = include_bytes!("fixtures/1bit.png");
+    assert_eq!(
+        GrayImage::from_png(&png[..png.len() - 20]),
+        Err(ImageError::Malformed)
+    );
+}
+
+#[cfg(feature = "pbm")]
+#[test]
+fn prints_pbm() {
+    for bytes in [
+        &include_bytes!("fixtures/plain.pbm")[..],
+        include_bytes!("fixtures/raw.pbm"),
+    ] {
+        assert_eq!(
+            print(&GrayImage::from_pbm(bytes).unwrap()).0,
+            expected_raster()
+        );
+    }
+    assert_eq!(
+        GrayImage::from_pbm(b"P4 12 6\n\0\0"),
+        Err(ImageError::Malformed)
+    );
+}
+
+#[cfg(feature = "gif")]
+#[test]
+fn prints_gif() {
+    for bytes in [
+        &include_bytes!("fixtures/plain.gif")[..],
+        include_bytes!("fixtures/interlaced-transparent.gif"),
+    ] {
+        assert_eq!(
+            print(&GrayImage::from_gif(bytes).unwrap()).0,
+            expected_raster()
+        );
+    }
+    let gif = include_bytes!("fixtures/plain.gif");
+    assert_eq!(
+        GrayImage::from_gif(&gif[..gif.len() - 10]),
+        Err(ImageError::Malformed)
+    );
+
+    // The image is the frame, whatever the size of the logical screen, cropped to the screen
+    let frame = GrayImage::from_gif(gif).unwrap();
+    let mut huge_screen = gif.to_vec();
+    huge_screen[6..10].copy_from_slice(&[0xFF; 4]);
+    assert_eq!(GrayImage::from_gif(&huge_screen).unwrap(), frame);
+    let mut small_screen = gif.to_vec();
+    small_screen[6..10].copy_from_slice(&[10, 0, 4, 0]);
+    let cropped = GrayImage::from_gif(&small_screen).unwrap();
+    assert_eq!((cropped.width(), cropped.height()), (10, 4));
+    let expected: Vec<u8> = frame
+        .luminance()
+        .chunks(12)
+        .take(4)
+        .flat_map(|row| &row[..10])
+        .copied()
+        .collect();
+    assert_eq!(cropped.luminance(), expected);
+}
+
+#[test]
+fn scales_images() {
+    let bmp = Bitmap::from_slice(include_bytes!("fixtures/24bit.bmp")).unwrap();
+    let scaled = |scaling, resampling| {
+        let options = BitmapOptionsBuilder::default()
+            .scaling(scaling)
+            .resampling(resampling)
+            .build()
+            .unwrap();
+        print_with(bmp, options).map(|(data, _)| data)
+    };
+
+    let doubled = raster(24, 12, |x, y| pattern(x / 2, y / 2));
+    assert_eq!(
+        scaled(Scaling::Factor(2.0), Resampling::Nearest).unwrap(),
+        doubled
+    );
+    // The nearest pixel outweighs the others at this scale
+    assert_eq!(
+        scaled(Scaling::Factor(2.0), Resampling::Bilinear).unwrap(),
+        doubled
+    );
+    assert_eq!(
+        scaled(Scaling::Factor(1.0), Resampling::Bilinear).unwrap(),
+        expected_raster()
+    );
+
+    // 384 / 12 = 32 dots per pixel, sent in bands of 85 rows
+    let fitted = scaled(Scaling::FitWidth, Resampling::Nearest).unwrap();
+    let band = raster(384, 85, |x, y| pattern(x / 32, y / 32));
+    assert_eq!(fitted[..8 + 85 * 48], band[..]);
+    assert_eq!(fitted.len(), 3 * 8 + 192 * 48);
+
+    assert!(scaled(Scaling::Factor(0.0), Resampling::Nearest).is_err());
+    assert!(scaled(Scaling::Factor(f32::NAN), Resampling::Nearest).is_err());
+    assert!(scaled(Scaling::Factor(33.0), Resampling::Nearest).is_err());
+}
+
+#[test]
+fn rotates_images() {
+    let bmp = Bitmap::from_slice(include_bytes!("fixtures/8bit.bmp")).unwrap();
+    let rotated = |rotation| {
+        let options = BitmapOptionsBuilder::default()
+            .rotation(rotation)
+            .build()
+            .unwrap();
+        print_with(bmp, options).unwrap().0
+    };
+    assert_eq!(
+        rotated(Rotation::Clockwise90),
+        raster(HEIGHT, WIDTH, |x, y| pattern(y, HEIGHT - 1 - x))
+    );
+    assert_eq!(
+        rotated(Rotation::Clockwise180),
+        raster(WIDTH, HEIGHT, |x, y| pattern(WIDTH - 1 - x, HEIGHT - 1 - y))
+    );
+    assert_eq!(
+        rotated(Rotation::Clockwise270),
+        raster(HEIGHT, WIDTH, |x, y| pattern(WIDTH - 1 - y, x))
+    );
+}
+
+#[test]
+fn places_images() {
+    let bmp = Bitmap::from_slice(include_bytes!("fixtures/1bit.bmp")).unwrap();
+    let placed = |alignment: Option<Justification>, left_margin: u16| {
+        let options = BitmapOptionsBuilder::default()
+            .alignment(alignment)
+            .left_margin(left_margin)
+            .build()
+            .unwrap();
+        print_with(bmp, options).unwrap().0
+    };
+    let shifted = |offset: usize| {
+        raster(384, HEIGHT, move |x, y| {
+            x >= offset && x < offset + WIDTH && pattern(x - offset, y)
+        })
+    };
+    assert_eq!(placed(None, 0), expected_raster());
+    assert_eq!(placed(Some(Justification::Left), 0), shifted(0));
+    assert_eq!(placed(Some(Justification::Center), 0), shifted(186));
+    assert_eq!(placed(Some(Justification::Right), 0), shifted(372));
+    assert_eq!(placed(None, 10), shifted(10));
+    assert_eq!(placed(Some(Justification::Center), 10), shifted(191));
+    assert_eq!(placed(Some(Justification::Right), 10), shifted(372));
+
+    // Fitted into the 300 dots right of the margin: 25 dots per pixel
+    let options = BitmapOptionsBuilder::default()
+        .scaling(Scaling::FitWidth)
+        .left_margin(84u16)
+        .dithering(Dithering::Atkinson)
+        .build()
+        .unwrap();
+    let (fitted, _) = print_with(bmp, options).unwrap();
+    let band = raster(384, 85, |x, y| x >= 84 && pattern((x - 84) / 25, y / 25));
+    assert_eq!(fitted[..8 + 85 * 48], band[..]);
+    assert_eq!(fitted.len(), 2 * 8 + 150 * 48);
+}
+
+#[test]
+fn rejects_images_off_the_paper() {
+    let bmp = Bitmap::from_slice(include_bytes!("fixtures/1bit.bmp")).unwrap();
+    let options = BitmapOptionsBuilder::default()
+        .left_margin(384u16)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        print_with(bmp, options),
+        Err(PrinterError::InvalidArgument(_))
+    ));
+
+    // Fitted to the width, a column of 20 pixels becomes 7680 rows high
+    let column = GrayImage::from_luminance(1, 20, vec![0; 20]).unwrap();
+    let options = BitmapOptionsBuilder::default()
+        .scaling(Scaling::FitWidth)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        print_with(&column, options),
+        Err(PrinterError::InvalidArgument("bitmap too large"))
+    ));
+    assert!(print_with(&column, BitmapOptions::default()).is_ok());
+}
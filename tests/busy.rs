@@ -0,0 +1,150 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Drives the printer with a scripted busy pin, checking that bytes are only sent while the
+//! printer is ready.
+
+mod common;
+
+use core::convert::Infallible;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use common::Clock;
+use embedded_hal::digital::{self, ErrorKind, InputPin};
+use thermal_print::{Printer, PrinterError, TimingProfileBuilder};
+
+/// What happened on the lines to the printer, in order.
+#[derive(Debug, PartialEq)]
+enum Event {
+    /// A byte was sent
+    Byte(u8),
+    /// The busy pin was read, and was high
+    Busy,
+    /// The busy pin was read, and was low
+    Ready,
+}
+
+type Log = Rc<RefCell<Vec<Event>>>;
+
+/// A serial port logging the bytes written to it.
+struct Port(Log);
+
+impl embedded_io::ErrorType for Port {
+    type Error = Infallible;
+}
+
+impl embedded_io::Write for Port {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0
+            .borrow_mut()
+            .extend(buf.iter().map(|b| Event::Byte(*b)));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// A busy pin following the scripted `levels` (`true` is high), which stays low once they are
+/// used up. A level of `None` fails to be read.
+struct Pin {
+    log: Log,
+    levels: VecDeque<Option<bool>>,
+}
+
+impl digital::ErrorType for Pin {
+    type Error = ErrorKind;
+}
+
+impl InputPin for Pin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        let high = self
+            .levels
+            .pop_front()
+            .unwrap_or(Some(false))
+            .ok_or(ErrorKind::Other)?;
+        self.log
+            .borrow_mut()
+            .push(if high { Event::Busy } else { Event::Ready });
+        Ok(high)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+/// A printer with a busy pin following `levels`, and the log of what happened.
+fn printer(levels: &[Option<bool>]) -> (Printer<Port, Clock, Pin>, Log) {
+    let log = Log::default();
+    let pin = Pin {
+        log: log.clone(),
+        levels: levels.iter().copied().collect(),
+    };
+    // The estimates for this slow baud rate are not waited for, as the busy pin is polled instead
+    let timing = TimingProfileBuilder::default()
+        .baud_rate(9600u32)
+        .build()
+        .unwrap();
+    let printer =
+        Printer::with_busy_pin_and_timing(Port(log.clone()), Clock::default(), pin, timing);
+    (printer, log)
+}
+
+#[test]
+fn holds_bytes_while_the_printer_is_busy() {
+    let (mut printer, log) = printer(&[Some(true), Some(true), Some(false), Some(true)]);
+    printer.feed_n(3).unwrap();
+    assert_eq!(
+        *log.borrow(),
+        [
+            Event::Busy,
+            Event::Busy,
+            Event::Ready,
+            Event::Byte(0x1B),
+            Event::Busy,
+            Event::Ready,
+            Event::Byte(0x64),
+            Event::Ready,
+            Event::Byte(3),
+        ]
+    );
+    // Polled the pin every 100µs while it was high, without waiting for the estimated time
+    assert_eq!(printer.delay.0, 3 * 100_000);
+}
+
+#[test]
+fn reports_busy_pin_errors() {
+    let (mut printer, log) = printer(&[Some(false), None]);
+    let result = printer.feed_n(3);
+    assert!(matches!(
+        result,
+        Err(PrinterError::BusyPin(ErrorKind::Other))
+    ));
+    assert_eq!(*log.borrow(), [Event::Ready, Event::Byte(0x1B)]);
+}
+
+#[test]
+fn times_out_if_the_printer_stays_busy() {
+    let (mut printer, log) = printer(&[Some(true); 20]);
+    printer.set_busy_timeout(1_000);
+    assert!(matches!(printer.feed_n(3), Err(PrinterError::Timeout)));
+    // Gave up after 10 polls, without sending anything
+    assert_eq!(printer.delay.0, 1_000_000);
+    assert_eq!(log.borrow().len(), 11);
+    assert!(!log.borrow().contains(&Event::Byte(0x1B)));
+}
@@ -0,0 +1,72 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Draws on the `embedded-graphics` canvas and checks the bands in which it is printed.
+
+#![cfg(feature = "embedded-graphics")]
+
+mod common;
+
+use embedded_graphics_core::pixelcolor::BinaryColor;
+use embedded_graphics_core::prelude::{Dimensions, DrawTarget, Point, Size};
+use embedded_graphics_core::Pixel;
+
+/// Draw a dot at each of `points` in turn.
+fn draw<T>(canvas: &mut T, points: &[(i32, i32)])
+where
+    T: DrawTarget<Color = BinaryColor>,
+    T::Error: core::fmt::Debug,
+{
+    canvas
+        .draw_iter(
+            points
+                .iter()
+                .map(|&(x, y)| Pixel(Point::new(x, y), BinaryColor::On)),
+        )
+        .unwrap();
+}
+
+#[test]
+fn prints_rows_in_bands_while_drawing() {
+    let mut printer = common::printer();
+    let mut canvas = printer.canvas();
+    draw(&mut canvas, &[(0, 0), (383, 84), (100, 150)]);
+    // Beyond the 240 rows kept in memory: the first full band is printed
+    draw(&mut canvas, &[(200, 300)]);
+    // Rows which have been printed already, and pixels beside the paper, are discarded
+    draw(&mut canvas, &[(50, 10), (-1, 200), (384, 200)]);
+    // Far below: the rows in memory are printed, followed by a band of blank rows
+    draw(&mut canvas, &[(7, 600)]);
+    assert_eq!(canvas.bounding_box().size, Size::new(384, 601));
+    canvas.finish().unwrap();
+
+    let bands = common::raster_images(&printer.serial.0);
+    let heights: Vec<usize> = bands.iter().map(Vec::len).collect();
+    // Each print is split into bands of at most 85 rows of 48 bytes
+    assert_eq!(heights, [85, 85, 85, 70, 85, 85, 85, 21]);
+
+    let rows: Vec<&String> = bands.iter().flatten().collect();
+    for (y, row) in rows.iter().enumerate() {
+        let expected: &[usize] = match y {
+            0 => &[0],
+            84 => &[383],
+            150 => &[100],
+            300 => &[200],
+            600 => &[7],
+            _ => &[],
+        };
+        let dots: Vec<usize> = row.match_indices('#').map(|(x, _)| x).collect();
+        assert_eq!(dots, expected, "row {}", y);
+    }
+}
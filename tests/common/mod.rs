@@ -0,0 +1,219 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! A serial port and a delay recording what the printer driver does, shared by the tests.
+
+// Not every test uses every helper
+#![allow(dead_code)]
+
+use core::convert::Infallible;
+use std::collections::VecDeque;
+
+use embedded_hal::delay::DelayNs;
+use thermal_print::{NoBusyPin, Printer};
+
+/// A serial port which records everything written to it.
+#[derive(Default)]
+pub struct Recorder(pub Vec<u8>);
+
+impl embedded_io::ErrorType for Recorder {
+    type Error = Infallible;
+}
+
+impl embedded_io::Write for Recorder {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// A serial port which accepts `capacity` bytes, and then fails to transmit anything more.
+pub struct FailingPort {
+    pub written: Vec<u8>,
+    pub capacity: usize,
+}
+
+impl FailingPort {
+    /// A port failing after `capacity` bytes.
+    pub fn new(capacity: usize) -> FailingPort {
+        FailingPort {
+            written: Vec::new(),
+            capacity,
+        }
+    }
+}
+
+impl embedded_io::ErrorType for FailingPort {
+    type Error = embedded_io::ErrorKind;
+}
+
+impl embedded_io::Write for FailingPort {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let count = buf.len().min(self.capacity - self.written.len());
+        if count == 0 && !buf.is_empty() {
+            return Err(embedded_io::ErrorKind::Other);
+        }
+        self.written.extend_from_slice(&buf[..count]);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// A serial port which records everything written to it, and answers each status query
+/// (`ESC v 0` and `GS r 1`) with the next of the scripted `answers`. Once they are used up, the
+/// printer stays silent.
+#[derive(Default)]
+pub struct Responder {
+    pub written: Vec<u8>,
+    pub answers: VecDeque<u8>,
+    received: VecDeque<u8>,
+}
+
+impl Responder {
+    /// A port answering the status queries with `answers`, in order.
+    pub fn new(answers: &[u8]) -> Responder {
+        Responder {
+            answers: answers.iter().copied().collect(),
+            ..Responder::default()
+        }
+    }
+}
+
+impl embedded_io::ErrorType for Responder {
+    type Error = Infallible;
+}
+
+impl embedded_io::Write for Responder {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for b in buf {
+            self.written.push(*b);
+            if self.written.ends_with(&[0x1B, 0x76, 0]) || self.written.ends_with(&[0x1D, 0x72, 1])
+            {
+                self.received.extend(self.answers.pop_front());
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl embedded_io::Read for Responder {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let count = buf.len().min(self.received.len());
+        for (b, received) in buf.iter_mut().zip(self.received.drain(..count)) {
+            *b = received;
+        }
+        Ok(count)
+    }
+}
+
+impl embedded_io::ReadReady for Responder {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.received.is_empty())
+    }
+}
+
+/// A delay which returns immediately, adding up the time it should have waited.
+#[derive(Default)]
+pub struct Clock(pub u64);
+
+impl DelayNs for Clock {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0 += u64::from(ns);
+    }
+}
+
+/// A printer recording the data sent to it.
+pub fn printer() -> Printer<Recorder, Clock, NoBusyPin> {
+    Printer::new(Recorder::default(), Clock::default())
+}
+
+/// The rows of the `GS v 0` raster images in the data sent to the printer, as strings of `#`
+/// (dot) and `.` (blank). Images sent in several bands are returned band by band.
+pub fn raster_images(data: &[u8]) -> Vec<Vec<String>> {
+    let mut images = Vec::new();
+    let mut rest = data;
+    while let Some(start) = rest.windows(3).position(|w| w == [0x1D, 0x76, 0]) {
+        let header = &rest[start..start + 8];
+        let x_bytes = usize::from(u16::from_le_bytes([header[4], header[5]]));
+        let rows = usize::from(u16::from_le_bytes([header[6], header[7]]));
+        let image = &rest[start + 8..start + 8 + x_bytes * rows];
+        images.push(
+            image
+                .chunks(x_bytes)
+                .map(|row| {
+                    (0..x_bytes * 8)
+                        .map(|x| {
+                            if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                                '#'
+                            } else {
+                                '.'
+                            }
+                        })
+                        .collect()
+                })
+                .collect(),
+        );
+        rest = &rest[start + 8 + x_bytes * rows..];
+    }
+    images
+}
+
+#[cfg(feature = "async")]
+impl embedded_io_async::Write for Recorder {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for Clock {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.0 += u64::from(ns);
+    }
+}
+
+/// Runs a future which never waits for a wakeup, as the recorder and the clock always complete
+/// immediately.
+#[cfg(feature = "async")]
+pub fn block_on<F: core::future::Future>(future: F) -> F::Output {
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut context = Context::from_waker(&waker);
+    let mut future = core::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
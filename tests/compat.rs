@@ -0,0 +1,141 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Drives the `embedded-hal` 0.2 adapters with a mock serial port and delay.
+
+#![cfg(feature = "embedded-hal-02")]
+
+use std::collections::VecDeque;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal_02::blocking::delay::DelayUs;
+use embedded_hal_02::serial;
+use embedded_io::{Read, ReadReady, Write};
+use thermal_print::compat::{DelayCompat, SerialCompat};
+
+/// An `embedded-hal` 0.2 serial port which receives `incoming`, and reports `WouldBlock` while
+/// it is empty. Transmitting fails once after `fail_after` bytes.
+#[derive(Default)]
+struct MockSerial {
+    incoming: VecDeque<u8>,
+    written: Vec<u8>,
+    fail_after: Option<usize>,
+}
+
+impl serial::Read<u8> for MockSerial {
+    type Error = ();
+
+    fn read(&mut self) -> nb::Result<u8, ()> {
+        self.incoming.pop_front().ok_or(nb::Error::WouldBlock)
+    }
+}
+
+impl serial::Write<u8> for MockSerial {
+    type Error = ();
+
+    fn write(&mut self, byte: u8) -> nb::Result<(), ()> {
+        if self.fail_after == Some(self.written.len()) {
+            self.fail_after = None;
+            return Err(nb::Error::Other(()));
+        }
+        self.written.push(byte);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), ()> {
+        Ok(())
+    }
+}
+
+/// An `embedded-hal` 0.2 delay recording the requested durations.
+#[derive(Default)]
+struct MockDelay(Vec<u32>);
+
+impl DelayUs<u32> for MockDelay {
+    fn delay_us(&mut self, us: u32) {
+        self.0.push(us);
+    }
+}
+
+#[test]
+fn writes_and_reads_through_the_serial_port() {
+    let mut serial = SerialCompat::new(MockSerial::default());
+    serial.write_all(b"\x1B@").unwrap();
+    assert_eq!(serial.into_inner().written, b"\x1B@");
+
+    let mut serial = SerialCompat::new(MockSerial {
+        incoming: VecDeque::from(vec![1, 2, 3]),
+        ..MockSerial::default()
+    });
+    // Only the bytes already received are returned
+    let mut buffer = [0; 8];
+    assert_eq!(serial.read(&mut buffer).unwrap(), 3);
+    assert_eq!(buffer[..3], [1, 2, 3]);
+    assert!(!serial.read_ready().unwrap());
+}
+
+#[test]
+fn reports_errors_after_the_bytes_written() {
+    let mut serial = SerialCompat::new(MockSerial {
+        fail_after: Some(2),
+        ..MockSerial::default()
+    });
+    // The bytes sent before the failure are counted, and the error is reported next
+    assert_eq!(serial.write(b"abcd").unwrap(), 2);
+    assert!(serial.write(b"cd").is_err());
+    assert_eq!(serial.write(b"cd").unwrap(), 2);
+    assert_eq!(serial.into_inner().written, b"abcd");
+
+    // Failing on the first byte
+    let mut serial = SerialCompat::new(MockSerial {
+        fail_after: Some(0),
+        ..MockSerial::default()
+    });
+    assert!(serial.write(b"ab").is_err());
+    serial.write_all(b"ab").unwrap();
+    assert_eq!(serial.into_inner().written, b"ab");
+}
+
+#[test]
+fn keeps_the_byte_received_while_checking_readiness() {
+    let mut serial = SerialCompat::new(MockSerial {
+        incoming: VecDeque::from(vec![0x12, 0x34]),
+        ..MockSerial::default()
+    });
+    // Checking twice does not consume a second byte
+    assert!(serial.read_ready().unwrap());
+    assert!(serial.read_ready().unwrap());
+
+    let mut byte = [0];
+    assert_eq!(serial.read(&mut byte).unwrap(), 1);
+    assert_eq!(byte, [0x12]);
+    assert!(serial.read_ready().unwrap());
+    assert_eq!(serial.read(&mut byte).unwrap(), 1);
+    assert_eq!(byte, [0x34]);
+    assert!(!serial.read_ready().unwrap());
+    assert_eq!(serial.read(&mut []).unwrap(), 0);
+}
+
+#[test]
+fn rounds_delays_up_to_whole_microseconds() {
+    let mut delay = DelayCompat(MockDelay::default());
+    delay.delay_ns(0);
+    delay.delay_ns(999);
+    delay.delay_ns(1000);
+    delay.delay_ns(1001);
+    delay.delay_us(7);
+    delay.delay_ms(2);
+    assert_eq!(delay.0 .0[..5], [0, 1, 1, 2, 7]);
+    assert_eq!(delay.0 .0[5..].iter().sum::<u32>(), 2000);
+}
@@ -0,0 +1,53 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Checks that failures of the serial port are reported to the caller.
+
+mod common;
+
+use core::fmt::{self, Write};
+
+use common::{Clock, FailingPort};
+use embedded_io::ErrorKind;
+use thermal_print::{Printer, PrinterError};
+
+#[test]
+fn reports_serial_errors() {
+    let mut printer = Printer::new(FailingPort::new(0), Clock::default());
+    assert!(matches!(
+        printer.feed(),
+        Err(PrinterError::Serial(ErrorKind::Other))
+    ));
+
+    // The bytes sent before the failure are not repeated
+    let mut printer = Printer::new(FailingPort::new(2), Clock::default());
+    assert!(matches!(
+        printer.feed_n(3),
+        Err(PrinterError::Serial(ErrorKind::Other))
+    ));
+    assert_eq!(printer.serial.written, [0x1B, 0x64]);
+}
+
+#[test]
+fn keeps_the_cause_of_failed_writes() {
+    let mut printer = Printer::new(FailingPort::new(3), Clock::default());
+    assert_eq!(write!(printer, "Hello"), Err(fmt::Error));
+    assert_eq!(printer.serial.written, b"Hel");
+    assert!(matches!(
+        printer.take_error(),
+        Some(PrinterError::Serial(ErrorKind::Other))
+    ));
+    // The error is only reported once
+    assert!(printer.take_error().is_none());
+}
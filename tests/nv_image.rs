@@ -0,0 +1,202 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Checks the commands storing, printing and erasing NV images for both command sets, and lists
+//! the stored images of a scripted printer.
+
+mod common;
+
+use core::convert::Infallible;
+use std::collections::VecDeque;
+
+use common::Clock;
+use thermal_print::{GrayImage, NvImageCommands, Printer, PrinterError, RasterBitImageMode};
+
+const FS: u8 = 0x1C;
+const GS: u8 = 0x1D;
+
+/// A 9x3 image with the dots (0, 0), (1, 1), (8, 1) and (2, 2)
+fn image() -> GrayImage {
+    let mut luminance = vec![255; 27];
+    for (x, y) in [(0, 0), (1, 1), (8, 1), (2, 2)] {
+        luminance[y * 9 + x] = 0;
+    }
+    GrayImage::from_luminance(9, 3, luminance).unwrap()
+}
+
+/// An 8x1 image of dots only
+fn line() -> GrayImage {
+    GrayImage::from_luminance(8, 1, vec![0; 8]).unwrap()
+}
+
+#[test]
+fn stores_images_column_by_column_via_fs_q() {
+    let mut printer = common::printer();
+    printer.store_nv_images(&[&image(), &line()]).unwrap();
+
+    let expected = [
+        &[FS, b'q', 2][..],
+        // 2 bytes wide, 1 byte (8 dots) high
+        &[2, 0, 1, 0],
+        // Each column from the top, MSB first
+        &[0x80, 0x40, 0x20, 0, 0, 0, 0, 0, 0x40, 0, 0, 0, 0, 0, 0, 0],
+        &[1, 0, 1, 0],
+        &[0x80; 8],
+    ]
+    .concat();
+    assert_eq!(printer.serial.0, expected);
+
+    printer.serial.0.clear();
+    printer
+        .print_nv_image(2, RasterBitImageMode::Quadruple)
+        .unwrap();
+    printer.erase_nv_images().unwrap();
+    assert_eq!(printer.serial.0, [FS, b'p', 2, 3, FS, b'q', 0]);
+    // Single images cannot be erased
+    assert!(matches!(
+        printer.erase_nv_image(1),
+        Err(PrinterError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn stores_images_as_raster_data_via_gs_l() {
+    let mut printer = common::printer();
+    printer.set_nv_image_commands(NvImageCommands::Graphics);
+    printer.store_nv_images(&[&image(), &line()]).unwrap();
+
+    let expected = [
+        // Erase all images first
+        &[GS, b'(', b'L', 5, 0, 48, 65, b'C', b'L', b'R'][..],
+        // 11 bytes of parameters and 6 bytes of data, key code " !", 16x3 dots
+        &[
+            GS, b'(', b'L', 17, 0, 48, 67, 48, b' ', b'!', 1, 16, 0, 3, 0, 49,
+        ],
+        &[0x80, 0x00, 0x40, 0x80, 0x20, 0x00],
+        // 11 bytes of parameters and 1 byte of data, key code " \"", 8x1 dots
+        &[
+            GS, b'(', b'L', 12, 0, 48, 67, 48, b' ', b'"', 1, 8, 0, 1, 0, 49,
+        ],
+        &[0xFF],
+    ]
+    .concat();
+    assert_eq!(printer.serial.0, expected);
+
+    printer.serial.0.clear();
+    printer
+        .print_nv_image(2, RasterBitImageMode::DoubleHeight)
+        .unwrap();
+    printer.erase_nv_image(1).unwrap();
+    let expected = [
+        &[GS, b'(', b'L', 6, 0, 48, 69, b' ', b'"', 1, 2][..],
+        &[GS, b'(', b'L', 4, 0, 48, 66, b' ', b'!'],
+    ]
+    .concat();
+    assert_eq!(printer.serial.0, expected);
+}
+
+#[test]
+fn encodes_the_length_of_large_images() {
+    let mut printer = common::printer();
+    printer.set_nv_image_commands(NvImageCommands::Graphics);
+    // 384x100 dots: 4800 bytes of data
+    let image = GrayImage::from_luminance(384, 100, vec![0; 38_400]).unwrap();
+    printer.store_nv_images(&[&image]).unwrap();
+    let command = &printer.serial.0[10..];
+    assert_eq!(command.len(), 16 + 4800);
+    // pL and pH count the bytes following them
+    assert_eq!(command[3..5], (4811u16).to_le_bytes());
+    assert_eq!(
+        usize::from(u16::from_le_bytes([command[3], command[4]])),
+        command.len() - 5
+    );
+    assert_eq!(command[11..15], [0x80, 1, 100, 0]);
+}
+
+/// A serial port which sends each of the scripted replies once the printer was sent the data
+/// preceding it.
+struct Script {
+    written: Vec<u8>,
+    replies: VecDeque<(Vec<u8>, Vec<u8>)>,
+    received: VecDeque<u8>,
+}
+
+impl embedded_io::ErrorType for Script {
+    type Error = Infallible;
+}
+
+impl embedded_io::Write for Script {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.written.extend_from_slice(buf);
+        if let Some((trigger, _)) = self.replies.front() {
+            if self.written.ends_with(trigger) {
+                let (_, reply) = self.replies.pop_front().unwrap();
+                self.received.extend(reply);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl embedded_io::Read for Script {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let count = buf.len().min(self.received.len());
+        for (b, received) in buf.iter_mut().zip(self.received.drain(..count)) {
+            *b = received;
+        }
+        Ok(count)
+    }
+}
+
+impl embedded_io::ReadReady for Script {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.received.is_empty())
+    }
+}
+
+#[test]
+fn lists_the_stored_images() {
+    const QUERY: [u8; 9] = [GS, b'(', b'L', 4, 0, 48, 64, b'K', b'C'];
+    const ACK: u8 = 0x06;
+    let replies = [
+        // Header, identifier and status (more blocks follow), the key codes of images 1 and 3,
+        // and of an image not stored by the driver
+        (
+            QUERY.to_vec(),
+            vec![0x37, 0x72, 0x41, b' ', b'!', b' ', b'#', b'A', b'B', 0],
+        ),
+        // The last block, with the key code of image 5
+        (vec![ACK], vec![0x37, 0x72, 0x40, b' ', b'%', 0]),
+    ];
+    let port = Script {
+        written: Vec::new(),
+        replies: replies.into_iter().collect(),
+        received: VecDeque::new(),
+    };
+    let mut printer = Printer::new(port, Clock::default());
+    printer.set_nv_image_commands(NvImageCommands::Graphics);
+    assert_eq!(printer.nv_images().unwrap(), [1, 3, 5]);
+    assert_eq!(printer.serial.written, [&QUERY[..], &[ACK]].concat());
+
+    // Not supported by FS q
+    printer.set_nv_image_commands(NvImageCommands::Legacy);
+    assert!(matches!(
+        printer.nv_images(),
+        Err(PrinterError::InvalidArgument(_))
+    ));
+}
@@ -0,0 +1,250 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Decodes the answers to the status queries, queries the status of a scripted printer, and
+//! checks how long jobs react to the faults it reports.
+
+mod common;
+
+use core::fmt::Write;
+
+use common::{Clock, Responder};
+use thermal_print::{
+    NoBusyPin, Printer, PrinterError, PrinterStatus, RasterBitImageMode, StatusPolicy,
+};
+
+/// The printer and paper status queries sent at each check
+const QUERIES: [u8; 6] = [0x1B, 0x76, 0, 0x1D, 0x72, 1];
+/// The answers to both queries if the printer is ready
+const OK: [u8; 2] = [0x00, 0x00];
+/// The answers to both queries if the paper is out
+const PAPER_OUT: [u8; 2] = [0b0000_0100, 0x00];
+
+const READY: PrinterStatus = PrinterStatus {
+    paper_present: true,
+    paper_near_end: false,
+    overheated: false,
+    voltage_out_of_range: false,
+};
+
+#[test]
+fn decodes_the_status_responses() {
+    let cases = [
+        // (printer status, paper status, decoded status)
+        (0x00, 0x00, READY),
+        // Paper out, reported by either query
+        (
+            0b0000_0100,
+            0x00,
+            PrinterStatus {
+                paper_present: false,
+                ..READY
+            },
+        ),
+        (
+            0x00,
+            0b0000_1100,
+            PrinterStatus {
+                paper_present: false,
+                ..READY
+            },
+        ),
+        (
+            0x00,
+            0b0000_0011,
+            PrinterStatus {
+                paper_near_end: true,
+                ..READY
+            },
+        ),
+        (
+            0b0100_0000,
+            0x00,
+            PrinterStatus {
+                overheated: true,
+                ..READY
+            },
+        ),
+        (
+            0b0000_1000,
+            0x00,
+            PrinterStatus {
+                voltage_out_of_range: true,
+                ..READY
+            },
+        ),
+        // Unrelated bits are ignored
+        (0b1011_0011, 0b1111_0000, READY),
+    ];
+    for (printer_status, paper_status, expected) in cases {
+        let status = PrinterStatus::from_responses(printer_status, paper_status);
+        assert_eq!(
+            status, expected,
+            "{printer_status:#010b} {paper_status:#010b}"
+        );
+    }
+}
+
+#[test]
+fn is_ready_unless_a_fault_is_reported() {
+    assert!(READY.is_ready());
+    // The printer keeps printing until the paper actually runs out
+    assert!(PrinterStatus::from_responses(0x00, 0b0000_0011).is_ready());
+    for (printer_status, paper_status) in [(0b0000_0100, 0), (0b0100_0000, 0), (0b0000_1000, 0)] {
+        assert!(!PrinterStatus::from_responses(printer_status, paper_status).is_ready());
+    }
+}
+
+#[test]
+fn queries_the_printer_and_paper_status() {
+    let mut printer = Printer::new(
+        Responder::new(&[0b0100_0000, 0b0000_0011]),
+        Clock::default(),
+    );
+    let status = printer.status().unwrap();
+    assert_eq!(
+        status,
+        PrinterStatus {
+            paper_near_end: true,
+            overheated: true,
+            ..READY
+        }
+    );
+    assert_eq!(printer.serial.written, [0x1B, 0x76, 0, 0x1D, 0x72, 1]);
+}
+
+#[test]
+fn times_out_if_the_printer_does_not_answer() {
+    let mut printer = Printer::new(Responder::default(), Clock::default());
+    printer.set_response_timeout(20_000);
+    assert!(matches!(printer.status(), Err(PrinterError::Timeout)));
+    // The second query is not sent
+    assert_eq!(printer.serial.written, [0x1B, 0x76, 0]);
+    // Waited for the whole timeout, in addition to sending the query
+    assert!(printer.delay.0 >= 20_000_000);
+    assert!(printer.delay.0 < 25_000_000);
+
+    // Only the paper status is missing
+    let mut printer = Printer::new(Responder::new(&[0x00]), Clock::default());
+    printer.set_response_timeout(20_000);
+    assert!(matches!(printer.status(), Err(PrinterError::Timeout)));
+}
+
+/// A printer checking its status according to `policy`, which answers the status queries with
+/// `answers`.
+fn checking_printer(
+    policy: StatusPolicy,
+    answers: &[[u8; 2]],
+) -> Printer<Responder, Clock, NoBusyPin, StatusPolicy> {
+    let mut printer = Printer::new(
+        Responder::new(answers.concat().as_slice()),
+        Clock::default(),
+    )
+    .with_status_policy(policy);
+    printer.set_response_timeout(10_000);
+    printer
+}
+
+#[test]
+fn aborts_text_at_the_first_fault() {
+    let mut printer = checking_printer(StatusPolicy::Abort, &[OK, OK, PAPER_OUT]);
+    assert!(write!(printer, "one\ntwo\nthree\n").is_err());
+    assert!(matches!(printer.take_error(), Some(PrinterError::PaperOut)));
+    // The third line is not sent
+    let expected = [&QUERIES[..], b"one\n", &QUERIES, b"two\n", &QUERIES].concat();
+    assert_eq!(printer.serial.written, expected);
+}
+
+#[test]
+fn refuses_jobs_while_the_printer_reports_a_fault() {
+    let mut printer = checking_printer(StatusPolicy::Abort, &[OK, PAPER_OUT]);
+    writeln!(printer, "one").unwrap();
+    // Nothing of the next job is sent
+    assert!(writeln!(printer, "two").is_err());
+    assert!(matches!(printer.take_error(), Some(PrinterError::PaperOut)));
+    let expected = [&QUERIES[..], b"one\n", &QUERIES].concat();
+    assert_eq!(printer.serial.written, expected);
+}
+
+#[test]
+fn checks_the_status_when_lines_wrap() {
+    let mut printer = checking_printer(StatusPolicy::Abort, &[OK, PAPER_OUT]);
+    let text = "x".repeat(96);
+    // The line is printed once the character following a full line arrives
+    assert!(write!(printer, "{text}").is_err());
+    assert!(matches!(printer.take_error(), Some(PrinterError::PaperOut)));
+    let expected = [&QUERIES[..], &text.as_bytes()[..33], &QUERIES].concat();
+    assert_eq!(printer.serial.written, expected);
+}
+
+#[test]
+fn checks_the_status_before_each_band() {
+    let mut printer = checking_printer(StatusPolicy::Abort, &[OK, OK, PAPER_OUT]);
+    // 72 rows are sent in 3 bands as high as a line of text
+    let data = [0xFF; 72];
+    let result = printer.print_raster(8, 72, &data, RasterBitImageMode::Normal);
+    assert!(matches!(result, Err(PrinterError::PaperOut)));
+    let band = [&[0x1D, 0x76, 0, 0, 1, 0, 24, 0][..], &[0xFF; 24]].concat();
+    let expected = [&QUERIES[..], &band, &QUERIES, &band, &QUERIES].concat();
+    assert_eq!(printer.serial.written, expected);
+}
+
+#[test]
+fn pauses_until_the_fault_is_resolved() {
+    let policy = StatusPolicy::Pause {
+        interval: 100_000,
+        timeout: 1_000_000,
+    };
+    let mut printer = checking_printer(policy, &[PAPER_OUT, PAPER_OUT, OK, OK]);
+    write!(printer, "one\ntwo\n").unwrap();
+    // The printer is queried until it is ready again, then the job goes on
+    let expected = [
+        &QUERIES[..],
+        &QUERIES,
+        &QUERIES,
+        b"one\n",
+        &QUERIES,
+        b"two\n",
+    ]
+    .concat();
+    assert_eq!(printer.serial.written, expected);
+    // Waited two intervals for the paper to be replaced
+    assert!(printer.delay.0 >= 200_000_000);
+}
+
+#[test]
+fn aborts_once_the_pause_times_out() {
+    let policy = StatusPolicy::Pause {
+        interval: 100_000,
+        timeout: 250_000,
+    };
+    let mut printer = checking_printer(policy, &[PAPER_OUT; 5]);
+    assert!(write!(printer, "one\ntwo\n").is_err());
+    assert!(matches!(printer.take_error(), Some(PrinterError::PaperOut)));
+    // Queried once, then after each of three intervals, without sending the first line
+    let expected = [QUERIES; 4].concat();
+    assert_eq!(printer.serial.written, expected);
+    assert_eq!(printer.serial.answers.len(), 2);
+}
+
+#[test]
+fn does_not_query_the_printer_if_disabled() {
+    let mut printer = checking_printer(StatusPolicy::Disabled, &[PAPER_OUT]);
+    write!(printer, "one\ntwo\n").unwrap();
+    assert_eq!(printer.serial.written, b"one\ntwo\n");
+
+    // Enabling the policy later
+    printer.set_status_policy(StatusPolicy::Abort);
+    assert!(writeln!(printer, "three").is_err());
+}
@@ -0,0 +1,507 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Prints QR and Data Matrix codes encoded by the driver and compares them with reference
+//! symbols. The QR codes were produced by an implementation of Nayuki's QR code generator
+//! (without boosting the error correction level), the Data Matrix codes by the `datamatrix`
+//! crate restricted to ASCII encodation and square symbols. `#` is a dark module.
+
+mod common;
+
+use common::{Clock, Recorder};
+use thermal_print::{
+    Justification, Printer, PrinterError, QrErrorCorrection, QrOptionsBuilder, QrRendering,
+    SymbolOptionsBuilder, Symbology, TimingProfileBuilder,
+};
+
+/// Text for the larger symbols
+const LONG_TEXT: &[u8] = b"thermal-print thermal-print thermal-print thermal-print thermal-print \
+    thermal-print thermal-print thermal-print ";
+
+/// Print `data` with one dot per module and no quiet zone, returning the rows of the symbol.
+fn print_symbol(data: &[u8], symbology: Symbology) -> Vec<String> {
+    let mut printer = common::printer();
+    let options = SymbolOptionsBuilder::default()
+        .symbology(symbology)
+        .scale(1)
+        .quiet_zone(0)
+        .build()
+        .unwrap();
+    printer.print_symbol(data, options).unwrap();
+    let images = common::raster_images(&printer.serial.0);
+    assert_eq!(images.len(), 1);
+    images[0].clone()
+}
+
+/// Check that `rows` show `expected`, followed by the blank padding to full bytes.
+fn assert_symbol(rows: &[String], expected: &[&str]) {
+    assert_eq!(rows.len(), expected.len());
+    for (y, (row, expected)) in rows.iter().zip(expected).enumerate() {
+        assert_eq!(&row[..expected.len()], *expected, "row {}", y);
+        assert!(row[expected.len()..].chars().all(|c| c == '.'), "row {}", y);
+    }
+}
+
+#[test]
+fn encodes_qr_codes() {
+    let cases: [(&[u8], QrErrorCorrection, &[&str]); 5] = [
+        (b"01234567", QrErrorCorrection::Low, &QR_NUMERIC_L),
+        (
+            b"HELLO WORLD",
+            QrErrorCorrection::Medium,
+            &QR_ALPHANUMERIC_M,
+        ),
+        (
+            b"HELLO WORLD",
+            QrErrorCorrection::Quartile,
+            &QR_ALPHANUMERIC_Q,
+        ),
+        (b"thermal-print", QrErrorCorrection::High, &QR_BYTE_H),
+        (
+            &LONG_TEXT[..110],
+            QrErrorCorrection::Medium,
+            &QR_VERSION_7_M,
+        ),
+    ];
+    for (data, ecc, expected) in cases {
+        assert_symbol(&print_symbol(data, Symbology::Qr(ecc)), expected);
+    }
+}
+
+#[test]
+fn encodes_data_matrix_codes() {
+    let long_text: Vec<u8> = b"The quick brown fox jumps over the lazy dog. "
+        .iter()
+        .cycle()
+        .take(190)
+        .copied()
+        .collect();
+    let cases: [(&[u8], &[&str]); 4] = [
+        (b"123456", &DM_ISO_EXAMPLE),
+        (b"Hello, World!", &DM_TEXT),
+        (
+            b"thermal-print 0.1.1 \xE4 ABCDEFGHIJKLMNOPQRSTUVWXYZ 0123456789",
+            &DM_UPPER_SHIFT,
+        ),
+        (&long_text, &DM_INTERLEAVED),
+    ];
+    for (data, expected) in cases {
+        assert_symbol(&print_symbol(data, Symbology::DataMatrix), expected);
+    }
+}
+
+#[test]
+fn rejects_data_beyond_capacity() {
+    let options = |symbology| {
+        SymbolOptionsBuilder::default()
+            .symbology(symbology)
+            .scale(1)
+            .build()
+            .unwrap()
+    };
+    let too_long = |result| {
+        matches!(
+            result,
+            Err(PrinterError::InvalidArgument("symbol data too long"))
+        )
+    };
+    let mut printer = common::printer();
+
+    // Version 40: 2953 bytes at level L, 1273 at level H
+    let qr_l = options(Symbology::Qr(QrErrorCorrection::Low));
+    let qr_h = options(Symbology::Qr(QrErrorCorrection::High));
+    assert!(printer.print_symbol(&[b'a'; 2953], qr_l).is_ok());
+    assert!(too_long(printer.print_symbol(&[b'a'; 2954], qr_l)));
+    assert!(printer.print_symbol(&[b'a'; 1273], qr_h).is_ok());
+    assert!(too_long(printer.print_symbol(&[b'a'; 1274], qr_h)));
+
+    // 144x144: 1558 codewords, of which each holds one letter or two digits
+    let data_matrix = options(Symbology::DataMatrix);
+    assert!(printer.print_symbol(&[b'a'; 1558], data_matrix).is_ok());
+    assert!(too_long(printer.print_symbol(&[b'a'; 1559], data_matrix)));
+    assert!(printer.print_symbol(&[b'0'; 3116], data_matrix).is_ok());
+    assert!(too_long(printer.print_symbol(&[b'0'; 3117], data_matrix)));
+}
+
+#[test]
+fn scales_and_places_symbols() {
+    let mut printer = common::printer();
+    let options = QrOptionsBuilder::default()
+        .module_size(2)
+        .error_correction(QrErrorCorrection::Quartile)
+        .justification(Justification::Center)
+        .rendering(QrRendering::Raster)
+        .build()
+        .unwrap();
+    printer.print_qr(b"HELLO WORLD", options).unwrap();
+
+    // Centered, then back to the previous justification
+    let data = &printer.serial.0;
+    assert_eq!(data[..3], [0x1B, 0x61, 1]);
+    assert_eq!(data[data.len() - 3..], [0x1B, 0x61, 0]);
+
+    // Two dots per module, with a quiet zone of four modules
+    let images = common::raster_images(data);
+    let rows = &images[0];
+    assert_eq!(rows.len(), (21 + 8) * 2);
+    for (y, row) in rows.iter().enumerate() {
+        let module_y = (y / 2).checked_sub(4).filter(|y| *y < 21);
+        for (x, dot) in row.chars().enumerate() {
+            let module_x = (x / 2).checked_sub(4).filter(|x| *x < 21);
+            let dark = match (module_x, module_y) {
+                (Some(x), Some(y)) => QR_ALPHANUMERIC_Q[y].as_bytes()[x] == b'#',
+                _ => false,
+            };
+            assert_eq!(dot == '#', dark, "dot {}, {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn sends_native_qr_commands() {
+    // Receiving data takes no time at this baud rate, so only printing rows is paced
+    let timing = TimingProfileBuilder::default()
+        .baud_rate(u32::MAX)
+        .dot_print_time(1000u32)
+        .dot_feed_time(2000u32)
+        .build()
+        .unwrap();
+    let mut printer = Printer::with_timing(Recorder::default(), Clock::default(), timing);
+    // Wider than the paper if rendered by the driver, which only matters for raster images
+    let options = QrOptionsBuilder::default()
+        .module_size(16)
+        .error_correction(QrErrorCorrection::Quartile)
+        .justification(Justification::Center)
+        .build()
+        .unwrap();
+    printer.print_qr(b"HELLO WORLD", options).unwrap();
+
+    let expected = [
+        &[0x1B, 0x61, 1][..],
+        // Model 2
+        &[0x1D, 0x28, 0x6B, 4, 0, 49, 65, 50, 0],
+        // Module size
+        &[0x1D, 0x28, 0x6B, 3, 0, 49, 67, 16],
+        // Error correction level Q
+        &[0x1D, 0x28, 0x6B, 3, 0, 49, 69, 50],
+        // Store the data, 3 bytes more than its length
+        &[0x1D, 0x28, 0x6B, 14, 0, 49, 80, 48],
+        b"HELLO WORLD",
+        // Print
+        &[0x1D, 0x28, 0x6B, 3, 0, 49, 81, 48],
+        &[0x1B, 0x61, 0],
+    ]
+    .concat();
+    assert_eq!(printer.serial.0, expected);
+    // Version 1 with a quiet zone of 4 modules: 29 modules of 16 dots
+    assert_eq!(printer.delay.0, 29 * 16 * 3000 * 1000);
+
+    // 26 bytes need version 2 at level M, 25 modules wide
+    printer.delay.0 = 0;
+    let options = QrOptionsBuilder::default().build().unwrap();
+    printer.print_qr(&[b'a'; 26], options).unwrap();
+    assert_eq!(printer.delay.0, (25 + 8) * 3 * 3000 * 1000);
+
+    // Version 40: 2953 bytes at level L
+    let options = QrOptionsBuilder::default()
+        .error_correction(QrErrorCorrection::Low)
+        .build()
+        .unwrap();
+    assert!(printer.print_qr(&[b'a'; 2953], options).is_ok());
+    assert!(matches!(
+        printer.print_qr(&[b'a'; 2954], options),
+        Err(PrinterError::InvalidArgument("symbol data too long"))
+    ));
+}
+
+/// Numeric mode, version 1-L, mask 7
+const QR_NUMERIC_L: [&str; 21] = [
+    "#######...#.#.#######",
+    "#.....#.#.#.#.#.....#",
+    "#.###.#.#.##..#.###.#",
+    "#.###.#.....#.#.###.#",
+    "#.###.#.#####.#.###.#",
+    "#.....#.###...#.....#",
+    "#######.#.#.#.#######",
+    "........#............",
+    "##.#..##..###.###.##.",
+    "..##.#..#..#.#.#...#.",
+    "##..#.#.##.#..#####..",
+    "####...#.#..######.##",
+    "#.#.#.####.#.###....#",
+    "........#.#..##.#....",
+    "#######.#####.#.##...",
+    "#.....#.......#..#.##",
+    "#.###.#..#..###..####",
+    "#.###.#.###..###..###",
+    "#.###.#..###.###..#.#",
+    "#.....#.#.###..#.#.#.",
+    "#######.##...#.#.##..",
+];
+
+/// Alphanumeric mode, version 1-M, mask 0
+const QR_ALPHANUMERIC_M: [&str; 21] = [
+    "#######...#.#.#######",
+    "#.....#.###...#.....#",
+    "#.###.#...#.#.#.###.#",
+    "#.###.#...#.#.#.###.#",
+    "#.###.#.#.###.#.###.#",
+    "#.....#..###..#.....#",
+    "#######.#.#.#.#######",
+    ".....................",
+    "#.#.#.#..#..#...#..#.",
+    ".####...#..#....#...#",
+    "...#######.#..#.##...",
+    "####.#.##..###.#.###.",
+    ".#..####.#.#..###.#.#",
+    "........#.#...#...#.#",
+    "#######.....#..#.##..",
+    "#.....#..##...##.#...",
+    "#.###.#.##..#.#######",
+    "#.###.#...##.#.#...#.",
+    "#.###.#.####.###.#..#",
+    "#.....#....###...#.##",
+    "#######.##.#.###....#",
+];
+
+/// Alphanumeric mode, version 1-Q, mask 0, the worked example of Thonky's QR code tutorial
+const QR_ALPHANUMERIC_Q: [&str; 21] = [
+    "#######.##....#######",
+    "#.....#.#..#..#.....#",
+    "#.###.#.#..##.#.###.#",
+    "#.###.#.#.....#.###.#",
+    "#.###.#.#.#...#.###.#",
+    "#.....#...#...#.....#",
+    "#######.#.#.#.#######",
+    "........#............",
+    ".##.#.##....#.#.#####",
+    ".#......####....#...#",
+    "..##.###.##...#.##...",
+    ".##.##.#..##.#.#.###.",
+    "#...#.#.#.###.###.#.#",
+    "........##.#..#...#.#",
+    "#######.#.#....#.##..",
+    "#.....#..#.##.##.#...",
+    "#.###.#.#.#...#######",
+    "#.###.#..#.#.#.#...#.",
+    "#.###.#.#..#.###.#..#",
+    "#.....#.#.####...#.##",
+    "#######....#.###....#",
+];
+
+/// Byte mode, version 2-H with an alignment pattern, mask 0
+const QR_BYTE_H: [&str; 25] = [
+    "#######.#.#.#.##..#######",
+    "#.....#...#.##.#..#.....#",
+    "#.###.#..##.#####.#.###.#",
+    "#.###.#.#.####.#..#.###.#",
+    "#.###.#..#......#.#.###.#",
+    "#.....#..#.######.#.....#",
+    "#######.#.#.#.#.#.#######",
+    ".........##.#..##........",
+    "..#.###.##.....#.#...#..#",
+    "#.####...#...####.#....##",
+    "..#.#.#.#....##.#.#.#####",
+    ".##.##.#..#..##..###...#.",
+    "..#...#..#.#########.#...",
+    "..##.#.....##..####..#.##",
+    "#.##.###.#.###.##..##..##",
+    ".#..#..###....#.#...#..#.",
+    "#.###.###.#.##.######..##",
+    "........####...##...#.#.#",
+    "#######....####.#.#.#####",
+    "#.....#.###.#####...#....",
+    "#.###.#.#####...#####..#.",
+    "#.###.#...#....#...###.#.",
+    "#.###.#.##..#####...###.#",
+    "#.....#...##....#.##...#.",
+    "#######..#.#.####..#...##",
+];
+
+/// Byte mode, version 7-M with version information and six alignment patterns, mask 2
+const QR_VERSION_7_M: [&str; 45] = [
+    "#######....###.###.#.....#...##..#..#.#######",
+    "#.....#...#...#.#.##.#.##.####.....#..#.....#",
+    "#.###.#.###...######.#......######.#..#.###.#",
+    "#.###.#.###..#.########.##...###...##.#.###.#",
+    "#.###.#.###...###..######....###..###.#.###.#",
+    "#.....#.#.#.####.#..#...##..##.##.....#.....#",
+    "#######.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#######",
+    "........#..##.#.....#...#..##.#.##.##........",
+    "#.#####..#.#..####..######...###.#.#..#####..",
+    ".#..##..####...#..#..#####...###...###....#.#",
+    "..#...###...##.#####.#..#.#.#..##.#.#.#.#..#.",
+    "..#..#.##.#.###.#.#....##..#..#.#.#.#######..",
+    "....###..#.#.#...###.##.#...#.##.##..#.#...#.",
+    "##.###..###..#........#..#.####....###.#.#.##",
+    "...#..#.#......#..#.#######.##..#.#####...##.",
+    "##.###....###..#...#..#.#######.#.....###.#..",
+    "##..#####.###.######.#.##.#...#..#.#.#.......",
+    "###..#...#..##.....#####.#.####....###.##.#.#",
+    "####.##..##..##..#..#..#####...#.####.##...#.",
+    "#.#....#####..###.##.....####.#.##.#...#####.",
+    ".#..#####.#.##..#.#.#####.##.....##.#####....",
+    ".####...#..##.###...#...####.##....##...#..##",
+    "##.##.#.#....#....#.#.#.#...#..######.#.#.##.",
+    "###.#...#..##.##.####...######..##..#...####.",
+    "#..######..##..#..#.#####.....##.##.#####..#.",
+    "..#.##...##..#...###.###...##.#.#...#..#.#.##",
+    "##..#.#..###...#.....#.#..##.....#####.#.###.",
+    "#####..#.####.##..#.##.##..#..#.#..####.###.#",
+    "##.#.##.##..####........#.##...#.#..#..##..##",
+    "..#.##.##..#.###.##.#.#.##.####..#...#...#.##",
+    "..##..#...#....##..#....#.#.#..#.##.##.#####.",
+    "###.#..#####.#.##.###..#.#..#..#####..##.###.",
+    ".###.##..##.....#.##..........##.#..#...##.##",
+    "######....#..###.####.##...#.##.#..##.......#",
+    "....#.###....#..#....#.#..##...####.##.#####.",
+    ".####....##.####.#.#...##..###..#.#####.####.",
+    "#..##.#..#..#.#.#...######...###....#####..##",
+    "........#.#..#.#....#...#....###....#...###.#",
+    "#######...#...#.#..##.#.##.#.....##.#.#.####.",
+    "#.....#.##...#...####...###.##.######...###..",
+    "#.###.#.#.###..#....######...###...#######..#",
+    "#.###.#.#...#..#.##..#..##...###.....##.#.#.#",
+    "#.###.#.##..#####.##....###..#.#.####.#..###.",
+    "#.....#..###....#..##.#...###..###..##.#.##..",
+    "#######.#####.##...#.#####...###.#.#####...#.",
+];
+
+/// The example of ISO/IEC 16022 "123456" in 10x10, codewords 142 164 186 114 25 5 88 102
+const DM_ISO_EXAMPLE: [&str; 10] = [
+    "#.#.#.#.#.",
+    "##..#.##.#",
+    "##.....#..",
+    "##...###.#",
+    "##....#...",
+    "#.....####",
+    "###.##....",
+    "####.##..#",
+    "#..###.#..",
+    "##########",
+];
+
+/// Mixed case text and punctuation in 18x18
+const DM_TEXT: [&str; 18] = [
+    "#.#.#.#.#.#.#.#.#.",
+    "#.##.##.#....#.#.#",
+    "##...##.##..#####.",
+    "###.##..#.#.#.#.##",
+    "##.....#..#.#.#...",
+    "###.#.##.#.......#",
+    "#.#.##.#..#.###...",
+    "#...##.#.##....###",
+    "#.###..##..#####..",
+    "#.#.###.#..##.####",
+    "###...###...###...",
+    "#...#.#.#.#.#.#.##",
+    "######.###..##..#.",
+    "#...###.#.##.#...#",
+    "#.##.....#####.#..",
+    "###.##....##.#..##",
+    "#..#.#.#.#.....#..",
+    "##################",
+];
+
+/// Digit pairs, an odd digit and an upper shift in 32x32 with four data regions
+const DM_UPPER_SHIFT: [&str; 32] = [
+    "#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.",
+    "#.###.##..#..#####..#.##..#.####",
+    "##.#.#.####.....#.#..#.##.......",
+    "#..##.#.##.##.###.#.#...##...#.#",
+    "#..###.#.#..###.#..#.##.##.###..",
+    "###...######..###..#..#...#..#.#",
+    "##.#.#.#...#....##.###..#..####.",
+    "#.#.#.#.#.#.#..##...##.#..#.#.##",
+    "#.###...###.....#####.....##.##.",
+    "#.#.####..####.###.##..#....##.#",
+    "###....#.....#..#...######...#..",
+    "#..##...###..#.####.##.#.#....##",
+    "####..#....#..#.#.........#.#.#.",
+    "#.#.#.##.#.#.#.###.#.###..###.##",
+    "##.##....#.#.#..##.##.#...#.....",
+    "################################",
+    "#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.",
+    "#...##...#.#.#####..#...#......#",
+    "#.#....#.##.##..#.###...##.###..",
+    "#.#.##.#.#...#############...###",
+    "#....##.....##..#.##..###.#.....",
+    "#.#..#....##.####.####..#..#####",
+    "#..##.##....###.###..###..#.#.#.",
+    "##.#..#..##.##.##.###.###..#.#.#",
+    "####..##.#.##.#.##.####.##..###.",
+    "##..#.####....#####.#.#.#.###..#",
+    "###.####..#..#..##...#.####..#..",
+    "#.#.#....#.#...##.###.#.#...#..#",
+    "#.####.####.#...#...##.#.###.#..",
+    "#..###..#...##.##.......#.#..#.#",
+    "#.#..###...##.#.##....#.#..#.##.",
+    "################################",
+];
+
+/// 190 characters in 52x52, whose error correction is interleaved in two blocks
+const DM_INTERLEAVED: [&str; 52] = [
+    "#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.",
+    "#.###.#...#...#.#.#..##..##.#####.....#.##..###.#..#",
+    "##.#.#..###...#####...##..####.#..##...###.#.#.#..#.",
+    "#..###..#...#....#.##...###...#...#..##.#.#..###..##",
+    "#..#.#.##..#..##...#..###.#..#####...#.#..#..#.#..#.",
+    "####..#.###...#..##.#.#..##.##..###.#....#.###..##.#",
+    "####.#...######..##.###...##...#.#.##..#...###.#....",
+    "#.#...####..#.###...#.#######.#..##.#....##.#.###.##",
+    "##.#...#...#...#.#.#......#.#.#...#..#.#.#.#.#.#....",
+    "#.#..####.###.###...###..##..#.##...#####...#..##..#",
+    "##.#....###..#.###...#..#.##...#..##.#.##...#..#..#.",
+    "##..#...#######.#..##.#..##..##...#...#.#..#.###..##",
+    "#..#..###....#.#.#.#.###..##.#.####...#..#...#.#.#..",
+    "#.#.#.....#...##..##..########..#...#......###.#.#.#",
+    "##.###...#..##...###.#.#..##.###...#..##.#.###.#....",
+    "##..#..##...##..#.#.#...####...#.##...#####.##....##",
+    "##.##..##.##.#.###.##..#..#..#...#.###.#.#.#....###.",
+    "#.##..##..#.#.##..#...#####.#..###..#...#..#.##.####",
+    "###..#...##..#..##...#....####.#.####..##...#.#..#..",
+    "#..##.#.###.##...#.##.#.###.#.##..#...#.##.....#..##",
+    "##.....##...#..#....#####.##.#...#....#..##..#...#..",
+    "#.#.#...#.#...#..##...#.#######..#.##.....#.##.#.###",
+    "#.####...#..####.#..##....##...#..#...#..#.##...#...",
+    "#...#..###..#.#.#...#.#.###.#.#..#...#.#..##...#..##",
+    "#.##...#...###.#####.#..#.#######...#.....#..##.....",
+    "####################################################",
+    "#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.",
+    "#.###.##..#.####...#.##..##.#.#..#.#####..#..##...##",
+    "##.#.#####...#...#...#..#.####..#.###..#####.#.#.#..",
+    "#..##...#.#.###.#..##...###.###.#.####.###.#...#..##",
+    "#..###.#.#....##...#####..#.###..##...#.##......#.#.",
+    "####...#.##...###.##..#.###....#..##.####.##.#.#.#.#",
+    "#..###...#..##.#.#...#.#..##.#.#....#.#....##...#...",
+    "#...#..##.#.#..####..#.#.#####..####.#####..#.#.####",
+    "#.##...#.####.....##..##..#.######.#..#.###......#..",
+    "#.###.##...#.##...#..##.###..##...#...###.#.###.####",
+    "##.#.###.#...#..##..#.###.#..#.##.###..##....###..#.",
+    "#..##.#.#..##.#.###....#.##.....###.###.##...##...##",
+    "#..###.##..#.###.#..#####.##...##.###..##..#.#.#.##.",
+    "####..#.####..#..#.##..#.##..##...#...#.###....###.#",
+    "##..##...###.##.#.##..#...######.#.#..#..#.##.#.#...",
+    "##..###.#.#..#.###.#.##..##.##.#..#....#####.#.#####",
+    "#..##..###.#...##.......#.###.##.#.#....###.##.#.##.",
+    "#.######..#..#..#.###....##.##.#.#.##..####.#.######",
+    "###..#..###.##.##.######..#......####.###.##.##.#.#.",
+    "#...##..#.###..###.....#.##.##.##.#..#####.#.##.#..#",
+    "#...#..#...#..##.#.#.###..#.#.#...####..#.#####.##..",
+    "###...###.####.#...##.##.###...####..#.##.#.#......#",
+    "##..###......#..###...###.#..###..##.#.....##.#.##..",
+    "##..####.#..###.#...#.######..#.#.#...#..####.#.####",
+    "#..#.......###.#...#.#.##.#..#.#...####..#...#......",
+    "####################################################",
+];
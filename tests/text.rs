@@ -0,0 +1,184 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Checks the bytes text is transcoded into, the fallbacks for characters the code table lacks,
+//! and the `ESC t` commands switching code tables.
+
+mod common;
+
+use core::fmt::Write;
+
+use thermal_print::{CodeTable, PrinterError, TextFallback};
+
+const ESC: u8 = 0x1B;
+
+#[test]
+fn switches_code_tables_for_mixed_scripts() {
+    let mut printer = common::printer();
+    printer.set_code_table(CodeTable::WCP1252).unwrap();
+    printer.set_code_table_switching(&[CodeTable::WCP1251, CodeTable::WCP1253]);
+    printer.serial.0.clear();
+
+    write!(printer, "Café, Привет, Γειά σου, é, ёж").unwrap();
+    let expected = [
+        &b"Caf\xE9, "[..],
+        &[ESC, b't', 6],
+        b"\xCF\xF0\xE8\xE2\xE5\xF2, ",
+        &[ESC, b't', 17],
+        b"\xC3\xE5\xE9\xDC \xF3\xEF\xF5, ",
+        // Back to the selected code table, which contains the most of the following characters
+        &[ESC, b't', 16],
+        b"\xE9, ",
+        &[ESC, b't', 6],
+        b"\xB8\xE6",
+        // Restoring the selected code table
+        &[ESC, b't', 16],
+    ]
+    .concat();
+    assert_eq!(printer.serial.0, expected);
+
+    // Text in the selected code table is sent without switching
+    printer.serial.0.clear();
+    write!(printer, "Crème brûlée").unwrap();
+    assert_eq!(printer.serial.0, b"Cr\xE8me br\xFBl\xE9e");
+}
+
+#[test]
+fn replaces_unsupported_characters() {
+    // `?` by default
+    let mut printer = common::printer();
+    write!(printer, "5€ für Kaffee ☕").unwrap();
+    assert_eq!(printer.serial.0, b"5? f\x81r Kaffee ?");
+
+    printer.serial.0.clear();
+    printer.set_text_fallback(TextFallback::Replace(0xFE));
+    write!(printer, "5€").unwrap();
+    assert_eq!(printer.serial.0, b"5\xFE");
+}
+
+#[test]
+fn transliterates_missing_characters() {
+    let mut printer = common::printer();
+    printer.set_text_fallback(TextFallback::Transliterate);
+    printer.set_code_table(CodeTable::CP866).unwrap();
+    printer.serial.0.clear();
+    // CP866 has Cyrillic letters, but no typographic punctuation or Western accents
+    write!(printer, "Straße – “Café” Щи ☕").unwrap();
+    assert_eq!(printer.serial.0, b"Strasse - \"Cafe\" \x99\xA8 ?");
+
+    // Characters of the code table are not transliterated
+    printer.set_code_table(CodeTable::CP437).unwrap();
+    printer.serial.0.clear();
+    write!(printer, "Straße").unwrap();
+    assert_eq!(printer.serial.0, b"Stra\xE1e");
+
+    // Custom transliterations take precedence
+    printer.set_transliterations(&[('–', "--"), ('☕', "[coffee]")]);
+    printer.serial.0.clear();
+    write!(printer, "tea – ☕ “").unwrap();
+    assert_eq!(printer.serial.0, b"tea -- [coffee] \"");
+}
+
+#[test]
+fn rejects_unsupported_characters() {
+    let mut printer = common::printer();
+    printer.set_text_fallback(TextFallback::Error);
+    assert!(write!(printer, "Price: 5€").is_err());
+    assert!(matches!(
+        printer.take_error(),
+        Some(PrinterError::UnsupportedCharacter('€'))
+    ));
+    // None of the text is sent
+    assert!(printer.serial.0.is_empty());
+
+    // Characters of the selected code table are fine
+    printer.set_code_table(CodeTable::WCP1252).unwrap();
+    printer.serial.0.clear();
+    write!(printer, "Price: 5€").unwrap();
+    assert_eq!(printer.serial.0, b"Price: 5\x80");
+}
+
+#[test]
+fn encodes_the_upper_half_of_wcp1252() {
+    // 0x81, 0x8D, 0x8F, 0x90 and 0x9D are undefined
+    let specials = "€\0‚ƒ„…†‡ˆ‰Š‹Œ\0Ž\0\0‘’“”•–—˜™š›œ\0žŸ";
+    for (i, c) in specials.chars().enumerate().filter(|(_, c)| *c != '\0') {
+        assert_eq!(CodeTable::WCP1252.encode(c), Some(0x80 + i as u8), "{}", c);
+    }
+    // The rest matches Latin-1
+    for byte in 0xA0..=0xFF {
+        assert_eq!(CodeTable::WCP1252.encode(char::from(byte)), Some(byte));
+    }
+    assert_eq!(CodeTable::WCP1252.encode('Ā'), None);
+}
+
+#[test]
+fn limits_unknown_code_tables_to_ascii() {
+    let code_tables = [
+        CodeTable::MIK,
+        CodeTable::CP755,
+        CodeTable::Iran,
+        CodeTable::IranII,
+        CodeTable::Latvian,
+        CodeTable::Thai,
+        CodeTable::Thai2,
+    ];
+    for code_table in code_tables {
+        assert_eq!(code_table.encode('A'), Some(b'A'));
+        assert_eq!(code_table.encode('~'), Some(b'~'));
+        let mut printer = common::printer();
+        printer.set_code_table(code_table).unwrap();
+        printer.serial.0.clear();
+        write!(printer, "Ä€").unwrap();
+        assert_eq!(printer.serial.0, b"??", "{:?}", code_table);
+    }
+}
+
+#[cfg(feature = "glyphs")]
+#[test]
+fn prints_lines_with_glyphs_as_one_image() {
+    let mut printer = common::printer();
+    printer.set_glyph_rendering(true);
+
+    // The whole line, including its text, is one image, which advances the paper by itself
+    writeln!(printer, "Thank you ♥").unwrap();
+    let images = common::raster_images(&printer.serial.0);
+    assert_eq!(images.len(), 1);
+    // Eleven glyphs of 10 dots, on a line as tall as font A
+    assert_eq!(images[0].len(), 24);
+    assert_eq!(images[0][0].len(), 112);
+    assert_eq!(printer.serial.0.len(), 8 + 14 * 24);
+    let dots = |x: core::ops::Range<usize>| {
+        images[0]
+            .iter()
+            .map(|row| row[x.clone()].matches('#').count())
+            .sum::<usize>()
+    };
+    // "T", the space between the words and the heart
+    assert!(dots(0..10) > 0);
+    assert_eq!(dots(50..60), 0);
+    assert!(dots(100..110) > 0);
+
+    // Lines without glyphs are sent as text
+    printer.serial.0.clear();
+    writeln!(printer, "Total: 3").unwrap();
+    assert_eq!(printer.serial.0, b"Total: 3\n");
+
+    // Text sent for the line before is printed on its own line
+    printer.serial.0.clear();
+    write!(printer, "Thanks ").unwrap();
+    write!(printer, "♥").unwrap();
+    assert!(printer.serial.0.starts_with(b"Thanks \n\x1D\x76\x00"));
+    assert_eq!(common::raster_images(&printer.serial.0).len(), 1);
+}
@@ -0,0 +1,69 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Checks the time the printer waits according to its TimingProfile, at different baud rates.
+
+mod common;
+
+use common::{Clock, Recorder};
+use thermal_print::{Printer, RasterBitImageMode, TimingProfileBuilder};
+
+/// A printer with the given baud rate, printing a row of dots in 1ms and feeding it in 2ms.
+fn printer(baud_rate: u32) -> Printer<Recorder, Clock> {
+    let timing = TimingProfileBuilder::default()
+        .baud_rate(baud_rate)
+        .dot_print_time(1000u32)
+        .dot_feed_time(2000u32)
+        .build()
+        .unwrap();
+    Printer::with_timing(Recorder::default(), Clock::default(), timing)
+}
+
+#[test]
+fn paces_feeds_by_baud_rate() {
+    // Receiving a byte is estimated to take 11 bit times, in µs and rounded
+    for (baud_rate, byte_time) in [(9600, 1146), (115_200, 95)] {
+        let mut printer = printer(baud_rate);
+        printer.feed_n(2).unwrap();
+        // 3 bytes of `ESC d`, and 2 lines of 24 dots with 6 dots of spacing
+        let expected = 3 * byte_time + 2 * (24 + 6) * 2000;
+        assert_eq!(printer.delay.0, expected * 1000, "{baud_rate} baud");
+    }
+}
+
+#[test]
+fn waits_for_pauses_longer_than_u32_microseconds() {
+    let timing = TimingProfileBuilder::default()
+        .baud_rate(9600u32)
+        .dot_feed_time(u32::MAX)
+        .build()
+        .unwrap();
+    let mut printer = Printer::with_timing(Recorder::default(), Clock::default(), timing);
+    printer.feed_n(255).unwrap();
+    let expected = 3 * 1146 + 255 * (24 + 6) * u64::from(u32::MAX);
+    assert_eq!(printer.delay.0, expected * 1000);
+}
+
+#[test]
+fn paces_raster_bands_by_baud_rate() {
+    for (baud_rate, byte_time) in [(9600, 1146), (115_200, 95)] {
+        let mut printer = printer(baud_rate);
+        printer
+            .print_raster(16, 3, &[0xFF; 6], RasterBitImageMode::Normal)
+            .unwrap();
+        // The 8 bytes of the header and 2 bytes per row, then printing and feeding 3 rows
+        let expected = (8 + 6) * byte_time + 3 * (1000 + 2000);
+        assert_eq!(printer.delay.0, expected * 1000, "{baud_rate} baud");
+    }
+}
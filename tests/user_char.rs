@@ -0,0 +1,147 @@
+/*  This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Checks the `ESC &` commands defining user-defined characters, and the validation of their
+//! fonts and character codes.
+
+mod common;
+
+use common::{Clock, Recorder};
+use thermal_print::{Font, PrintModeBuilder, Printer, PrinterError, UserGlyph};
+
+const ESC: u8 = 0x1B;
+
+/// A check mark for font B, as in the documentation of `UserGlyph::from_bitmap`
+fn check_mark() -> UserGlyph {
+    let mut bitmap = [0; 34];
+    for (row, dots) in [
+        (6, 0x01),
+        (7, 0x03),
+        (8, 0x86),
+        (9, 0xCC),
+        (10, 0x78),
+        (11, 0x30),
+    ] {
+        bitmap[row * 2] = dots;
+    }
+    UserGlyph::from_bitmap(Font::FontB, &bitmap).unwrap()
+}
+
+/// A printer with font B selected.
+fn printer() -> Printer<Recorder, Clock> {
+    let mut printer = common::printer();
+    let mode = PrintModeBuilder::default()
+        .font(Font::FontB)
+        .build()
+        .unwrap();
+    printer.set_print_mode(mode).unwrap();
+    printer.serial.0.clear();
+    printer
+}
+
+#[test]
+fn defines_user_characters() {
+    let mut printer = printer();
+    let blank = UserGlyph::from_bitmap(Font::FontB, &[0; 34]).unwrap();
+    printer
+        .define_user_characters(b'A', &[check_mark(), blank])
+        .unwrap();
+
+    // 3 bytes per column, codes `A` to `B`
+    let mut expected = vec![ESC, b'&', 3, b'A', b'B'];
+    // 9 columns, each 24 dots from the top, MSB first
+    expected.push(9);
+    expected.extend_from_slice(&[
+        0x00, 0xC0, 0x00, // rows 8 and 9
+        0x00, 0x60, 0x00, // rows 9 and 10
+        0x00, 0x30, 0x00, // rows 10 and 11
+        0x00, 0x30, 0x00, //
+        0x00, 0x60, 0x00, //
+        0x00, 0xC0, 0x00, //
+        0x01, 0x80, 0x00, // rows 7 and 8
+        0x03, 0x00, 0x00, // rows 6 and 7
+        0x00, 0x00, 0x00, //
+    ]);
+    expected.push(9);
+    expected.extend_from_slice(&[0; 27]);
+    assert_eq!(printer.serial.0, expected);
+
+    printer.serial.0.clear();
+    printer.set_user_characters(true).unwrap();
+    printer.cancel_user_character(b'B').unwrap();
+    printer.set_user_characters(false).unwrap();
+    assert_eq!(
+        printer.serial.0,
+        [ESC, b'%', 1, ESC, b'?', b'B', ESC, b'%', 0]
+    );
+}
+
+#[test]
+fn rejects_glyphs_of_another_font() {
+    let mut printer = printer();
+    let glyph = UserGlyph::from_bitmap(Font::FontA, &[0xFF; 48]).unwrap();
+    assert_eq!(glyph.font(), Font::FontA);
+    assert!(matches!(
+        printer.define_user_characters(b'A', &[check_mark(), glyph]),
+        Err(PrinterError::InvalidArgument(
+            "user-defined characters must match the selected font"
+        ))
+    ));
+    assert!(printer.serial.0.is_empty());
+
+    // Bitmaps of the wrong size
+    assert!(UserGlyph::from_bitmap(Font::FontA, &[0; 34]).is_none());
+    assert!(UserGlyph::from_bitmap(Font::FontB, &[0; 48]).is_none());
+}
+
+#[test]
+fn checks_the_character_codes() {
+    let mut printer = printer();
+    let out_of_range = |result| {
+        matches!(
+            result,
+            Err(PrinterError::InvalidArgument(
+                "user-defined character codes out of range"
+            ))
+        )
+    };
+    // Only printable ASCII can be replaced
+    assert!(out_of_range(
+        printer.define_user_characters(0x1F, &[check_mark()])
+    ));
+    assert!(out_of_range(
+        printer.define_user_characters(0x7F, &[check_mark()])
+    ));
+    assert!(out_of_range(
+        printer.define_user_characters(0x7E, &[check_mark(), check_mark()])
+    ));
+    assert!(out_of_range(printer.define_user_characters(b'A', &[])));
+    assert!(printer.serial.0.is_empty());
+
+    // The first and the last code
+    assert!(printer
+        .define_user_characters(0x20, &[check_mark()])
+        .is_ok());
+    assert!(printer
+        .define_user_characters(0x7E, &[check_mark()])
+        .is_ok());
+
+    assert!(printer.cancel_user_character(0x20).is_ok());
+    assert!(matches!(
+        printer.cancel_user_character(0x7F),
+        Err(PrinterError::InvalidArgument(
+            "user-defined character code out of range"
+        ))
+    ));
+}